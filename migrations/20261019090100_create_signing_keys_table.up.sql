@@ -0,0 +1,17 @@
+-- JWT signing keys; the newest key without retired_at signs new tokens,
+-- retired keys keep verifying tokens until those have expired
+CREATE TABLE signing_keys
+(
+    kid        TEXT PRIMARY KEY,
+    secret     BYTEA       NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    retired_at TIMESTAMPTZ
+);
+
+-- Tokens revoked before their expiry, by JWT ID
+CREATE TABLE revoked_tokens
+(
+    jti        TEXT PRIMARY KEY,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
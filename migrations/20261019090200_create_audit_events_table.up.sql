@@ -0,0 +1,37 @@
+-- Append-only audit log. Every row carries the SHA-256 hash of the previous
+-- row (zeroes for the first one) and a hash over its own contents, so any
+-- edit or removal breaks the chain; `rustrest-admin verify-audit-chain` checks it.
+CREATE TABLE audit_events
+(
+    id          BIGSERIAL PRIMARY KEY,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    actor       TEXT        NOT NULL,
+    ip          TEXT,
+    action      TEXT        NOT NULL,
+    resource    TEXT,
+    outcome     TEXT        NOT NULL,
+    request_id  TEXT,
+    details     JSONB       NOT NULL DEFAULT '{}',
+    prev_hash   BYTEA       NOT NULL,
+    hash        BYTEA       NOT NULL UNIQUE
+);
+
+CREATE INDEX audit_events_occurred_at_idx ON audit_events (occurred_at);
+CREATE INDEX audit_events_actor_idx ON audit_events (actor);
+CREATE INDEX audit_events_action_idx ON audit_events (action);
+CREATE INDEX audit_events_request_id_idx ON audit_events (request_id);
+
+CREATE FUNCTION audit_events_append_only() RETURNS trigger AS
+$$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_no_update_or_delete
+    BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW EXECUTE FUNCTION audit_events_append_only();
+
+CREATE TRIGGER audit_events_no_truncate
+    BEFORE TRUNCATE ON audit_events
+    FOR EACH STATEMENT EXECUTE FUNCTION audit_events_append_only();
@@ -0,0 +1 @@
+DROP TABLE csp_reports;
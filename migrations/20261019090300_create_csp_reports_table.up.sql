@@ -0,0 +1,17 @@
+-- Content-Security-Policy violations reported by browsers, kept for review
+CREATE TABLE csp_reports
+(
+    id                 BIGSERIAL PRIMARY KEY,
+    received_at        TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    document_uri       TEXT,
+    blocked_uri        TEXT,
+    effective_directive TEXT,
+    source_file        TEXT,
+    line_number        BIGINT,
+    disposition        TEXT,
+    user_agent         TEXT,
+    ip                 TEXT,
+    report             JSONB       NOT NULL
+);
+
+CREATE INDEX csp_reports_received_at_idx ON csp_reports (received_at);
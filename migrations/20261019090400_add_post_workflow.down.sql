@@ -0,0 +1,10 @@
+DROP TABLE post_reviews;
+
+DROP INDEX posts_user_id_idx;
+DROP INDEX posts_status_idx;
+ALTER TABLE posts
+    DROP COLUMN updated_at,
+    DROP COLUMN published_at,
+    DROP COLUMN status,
+    ALTER COLUMN created_at DROP NOT NULL,
+    ALTER COLUMN created_at TYPE TIMESTAMP;
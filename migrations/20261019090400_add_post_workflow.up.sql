@@ -0,0 +1,30 @@
+-- Posts go through draft -> in_review -> published (-> archived). Posts that
+-- existed before the workflow were live, so they start out published.
+ALTER TABLE posts
+    ADD COLUMN status       TEXT        NOT NULL DEFAULT 'published'
+        CHECK (status IN ('draft', 'in_review', 'published', 'archived')),
+    ADD COLUMN published_at TIMESTAMPTZ,
+    ADD COLUMN updated_at   TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+UPDATE posts SET created_at = NOW() WHERE created_at IS NULL;
+ALTER TABLE posts
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN created_at SET NOT NULL;
+UPDATE posts SET published_at = created_at, updated_at = created_at;
+ALTER TABLE posts ALTER COLUMN status SET DEFAULT 'draft';
+
+CREATE INDEX posts_status_idx ON posts (status);
+CREATE INDEX posts_user_id_idx ON posts (user_id);
+
+-- Editor decisions on submitted posts, the comment tells the author why
+CREATE TABLE post_reviews
+(
+    id          BIGSERIAL PRIMARY KEY,
+    post_id     INTEGER     NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    reviewer_id INTEGER     REFERENCES users (id) ON DELETE SET NULL,
+    decision    TEXT        NOT NULL CHECK (decision IN ('approved', 'rejected')),
+    comment     TEXT,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX post_reviews_post_id_idx ON post_reviews (post_id);
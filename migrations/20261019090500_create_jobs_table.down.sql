@@ -0,0 +1,10 @@
+DROP TABLE jobs;
+
+UPDATE posts SET status = 'in_review' WHERE status = 'scheduled';
+ALTER TABLE posts
+    DROP CONSTRAINT posts_schedule_check,
+    DROP COLUMN unpublish_at,
+    DROP COLUMN publish_at,
+    DROP CONSTRAINT posts_status_check;
+ALTER TABLE posts
+    ADD CONSTRAINT posts_status_check CHECK (status IN ('draft', 'in_review', 'published', 'archived'));
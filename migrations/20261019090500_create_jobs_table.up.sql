@@ -0,0 +1,30 @@
+-- Embargoes: approved posts wait in `scheduled` until `publish_at` and are
+-- archived at `unpublish_at`. Background jobs make both happen on time.
+ALTER TABLE posts DROP CONSTRAINT posts_status_check;
+ALTER TABLE posts
+    ADD CONSTRAINT posts_status_check CHECK (status IN ('draft', 'in_review', 'scheduled', 'published', 'archived')),
+    ADD COLUMN publish_at   TIMESTAMPTZ,
+    ADD COLUMN unpublish_at TIMESTAMPTZ,
+    ADD CONSTRAINT posts_schedule_check CHECK (unpublish_at > publish_at);
+
+-- Work for the in-process job runner. Workers claim due jobs with
+-- `FOR UPDATE SKIP LOCKED`, so replicas never run the same job twice.
+CREATE TABLE jobs
+(
+    id           BIGSERIAL PRIMARY KEY,
+    kind         TEXT        NOT NULL,
+    payload      JSONB       NOT NULL DEFAULT '{}',
+    status       TEXT        NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'succeeded', 'failed')),
+    run_at       TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    attempts     INTEGER     NOT NULL DEFAULT 0,
+    max_attempts INTEGER     NOT NULL DEFAULT 5,
+    last_error   TEXT,
+    locked_by    TEXT,
+    locked_at    TIMESTAMPTZ,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at  TIMESTAMPTZ
+);
+
+CREATE INDEX jobs_due_idx ON jobs (run_at) WHERE status = 'pending';
+CREATE INDEX jobs_status_idx ON jobs (status, id);
@@ -0,0 +1,18 @@
+-- Every version of a post's title and body, numbered per post from 1
+CREATE TABLE post_revisions
+(
+    id            BIGSERIAL PRIMARY KEY,
+    post_id       INTEGER     NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    revision      INTEGER     NOT NULL,
+    title         TEXT        NOT NULL,
+    body          TEXT        NOT NULL,
+    author_id     INTEGER     REFERENCES users (id) ON DELETE SET NULL,
+    -- Set when the revision brought back the content of an earlier one
+    restored_from INTEGER,
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (post_id, revision)
+);
+
+-- Existing posts start with their current content as the first revision
+INSERT INTO post_revisions (post_id, revision, title, body, author_id, created_at)
+SELECT id, 1, title, body, user_id, updated_at FROM posts;
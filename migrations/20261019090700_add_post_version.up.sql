@@ -0,0 +1,2 @@
+-- Bumped on every change, the posts' ETag
+ALTER TABLE posts ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,7 @@
+DROP INDEX posts_title_trgm_idx;
+DROP INDEX posts_search_vector_idx;
+DROP TRIGGER posts_search_vector ON posts;
+DROP FUNCTION posts_search_vector();
+ALTER TABLE posts DROP COLUMN search_vector;
+ALTER TABLE posts DROP COLUMN language;
+-- pg_trgm stays installed, other objects may depend on it
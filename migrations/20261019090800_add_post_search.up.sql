@@ -0,0 +1,26 @@
+-- Trigram matching for title autocomplete
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Text search configuration the post is indexed with, e.g. 'english' or 'simple'
+ALTER TABLE posts ADD COLUMN language TEXT NOT NULL DEFAULT 'english';
+ALTER TABLE posts ADD COLUMN search_vector TSVECTOR;
+
+-- Title matches rank above body matches
+CREATE FUNCTION posts_search_vector() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.search_vector :=
+        setweight(to_tsvector(NEW.language::REGCONFIG, NEW.title), 'A') ||
+        setweight(to_tsvector(NEW.language::REGCONFIG, NEW.body), 'B');
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER posts_search_vector
+    BEFORE INSERT OR UPDATE OF title, body, language ON posts
+    FOR EACH ROW EXECUTE FUNCTION posts_search_vector();
+
+-- Fires the trigger for the existing posts
+UPDATE posts SET title = title;
+
+CREATE INDEX posts_search_vector_idx ON posts USING GIN (search_vector);
+CREATE INDEX posts_title_trgm_idx ON posts USING GIN (title gin_trgm_ops);
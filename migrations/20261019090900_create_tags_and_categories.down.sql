@@ -0,0 +1,4 @@
+ALTER TABLE posts DROP COLUMN category_id;
+DROP TABLE categories;
+DROP TABLE post_tags;
+DROP TABLE tags;
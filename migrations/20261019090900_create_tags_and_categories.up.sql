@@ -0,0 +1,33 @@
+-- Free-form labels, stored lowercase
+CREATE TABLE tags
+(
+    id         SERIAL PRIMARY KEY,
+    name       TEXT        NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE post_tags
+(
+    post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    tag_id  INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    PRIMARY KEY (post_id, tag_id)
+);
+
+CREATE INDEX post_tags_tag_id_idx ON post_tags (tag_id);
+
+-- A tree of sections, a post belongs to at most one
+CREATE TABLE categories
+(
+    id         SERIAL PRIMARY KEY,
+    parent_id  INTEGER REFERENCES categories (id) ON DELETE RESTRICT,
+    name       TEXT        NOT NULL,
+    slug       TEXT        NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (parent_id <> id)
+);
+
+CREATE INDEX categories_parent_id_idx ON categories (parent_id);
+
+ALTER TABLE posts ADD COLUMN category_id INTEGER REFERENCES categories (id) ON DELETE SET NULL;
+
+CREATE INDEX posts_category_id_idx ON posts (category_id);
@@ -0,0 +1,3 @@
+DROP TABLE comment_bans;
+DROP TABLE comments;
+ALTER TABLE posts DROP COLUMN comments;
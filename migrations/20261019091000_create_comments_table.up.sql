@@ -0,0 +1,32 @@
+-- open: anyone may comment, closed: existing comments stay readable, disabled: no comments shown
+ALTER TABLE posts ADD COLUMN comments TEXT NOT NULL DEFAULT 'open'
+    CHECK (comments IN ('open', 'closed', 'disabled'));
+
+CREATE TABLE comments
+(
+    id         BIGSERIAL PRIMARY KEY,
+    post_id    INTEGER     NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    -- The comment replied to, and the top-level comment of the thread
+    parent_id  BIGINT REFERENCES comments (id) ON DELETE CASCADE,
+    root_id    BIGINT REFERENCES comments (id) ON DELETE CASCADE,
+    author_id  INTEGER     REFERENCES users (id) ON DELETE SET NULL,
+    body       TEXT        NOT NULL,
+    status     TEXT        NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'hidden')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    edited_at  TIMESTAMPTZ,
+    -- Deleted comments keep their place in the thread without a body
+    deleted_at TIMESTAMPTZ
+);
+
+CREATE INDEX comments_post_id_idx ON comments (post_id, created_at);
+CREATE INDEX comments_root_id_idx ON comments (root_id);
+CREATE INDEX comments_pending_idx ON comments (created_at) WHERE status = 'pending';
+
+-- Users an editor banned from commenting
+CREATE TABLE comment_bans
+(
+    user_id    INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    banned_by  INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    reason     TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
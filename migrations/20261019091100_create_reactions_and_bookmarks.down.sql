@@ -0,0 +1,5 @@
+DROP TABLE bookmarks;
+DROP TRIGGER post_reaction_counts ON post_reactions;
+DROP FUNCTION post_reaction_counts();
+DROP TABLE post_reaction_counts;
+DROP TABLE post_reactions;
@@ -0,0 +1,53 @@
+-- One row per user, post and reaction; which reactions exist is configuration
+CREATE TABLE post_reactions
+(
+    post_id    INTEGER     NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    user_id    INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    reaction   TEXT        NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (post_id, user_id, reaction)
+);
+
+CREATE INDEX post_reactions_user_id_idx ON post_reactions (user_id);
+
+-- Totals per post and reaction, so that listing posts doesn't count rows
+CREATE TABLE post_reaction_counts
+(
+    post_id  INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    reaction TEXT    NOT NULL,
+    count    INTEGER NOT NULL CHECK (count > 0),
+    PRIMARY KEY (post_id, reaction)
+);
+
+-- Kept up to date here rather than in the application, so reactions removed
+-- along with their user are subtracted too
+CREATE FUNCTION post_reaction_counts() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO post_reaction_counts (post_id, reaction, count)
+        VALUES (NEW.post_id, NEW.reaction, 1)
+        ON CONFLICT (post_id, reaction) DO UPDATE SET count = post_reaction_counts.count + 1;
+    ELSE
+        UPDATE post_reaction_counts SET count = count - 1
+        WHERE post_id = OLD.post_id AND reaction = OLD.reaction AND count > 1;
+        IF NOT FOUND THEN
+            DELETE FROM post_reaction_counts WHERE post_id = OLD.post_id AND reaction = OLD.reaction;
+        END IF;
+    END IF;
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER post_reaction_counts
+    AFTER INSERT OR DELETE ON post_reactions
+    FOR EACH ROW EXECUTE FUNCTION post_reaction_counts();
+
+CREATE TABLE bookmarks
+(
+    user_id    INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    post_id    INTEGER     NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, post_id)
+);
+
+CREATE INDEX bookmarks_user_id_idx ON bookmarks (user_id, created_at DESC);
@@ -0,0 +1,5 @@
+DROP TRIGGER posts_stale_rendering ON posts;
+DROP FUNCTION posts_stale_rendering();
+ALTER TABLE posts DROP COLUMN reading_minutes;
+ALTER TABLE posts DROP COLUMN excerpt;
+ALTER TABLE posts DROP COLUMN body_html;
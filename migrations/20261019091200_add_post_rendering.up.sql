@@ -0,0 +1,21 @@
+-- The body rendered from Markdown by the application, NULL until it was
+ALTER TABLE posts ADD COLUMN body_html TEXT;
+ALTER TABLE posts ADD COLUMN excerpt TEXT;
+ALTER TABLE posts ADD COLUMN reading_minutes INTEGER;
+
+-- A body changed without rendering it along is rendered again on the next read
+CREATE FUNCTION posts_stale_rendering() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.body_html IS NOT DISTINCT FROM OLD.body_html THEN
+        NEW.body_html := NULL;
+        NEW.excerpt := NULL;
+        NEW.reading_minutes := NULL;
+    END IF;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER posts_stale_rendering
+    BEFORE UPDATE OF body ON posts
+    FOR EACH ROW WHEN (OLD.body IS DISTINCT FROM NEW.body)
+    EXECUTE FUNCTION posts_stale_rendering();
@@ -0,0 +1,5 @@
+DROP TABLE post_slug_redirects;
+DROP TRIGGER posts_default_slug ON posts;
+DROP FUNCTION posts_default_slug();
+ALTER TABLE posts DROP COLUMN custom_slug;
+ALTER TABLE posts DROP COLUMN slug;
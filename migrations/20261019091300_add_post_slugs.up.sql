@@ -0,0 +1,42 @@
+-- Generated from the title by the application unless the author chose one
+ALTER TABLE posts ADD COLUMN slug TEXT;
+ALTER TABLE posts ADD COLUMN custom_slug BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Existing posts get an ASCII-only approximation, the application
+-- transliterates from here on. Repeated titles are told apart by id.
+WITH base AS (
+    SELECT id,
+           COALESCE(NULLIF(TRIM(BOTH '-' FROM REGEXP_REPLACE(LOWER(title), '[^a-z0-9]+', '-', 'g')), ''), 'post') AS slug
+    FROM posts
+)
+UPDATE posts SET slug = CASE
+    WHEN (SELECT COUNT(*) FROM base other WHERE other.slug = base.slug AND other.id < base.id) = 0 THEN base.slug
+    ELSE base.slug || '-' || base.id
+END
+FROM base WHERE base.id = posts.id;
+
+ALTER TABLE posts ALTER COLUMN slug SET NOT NULL;
+ALTER TABLE posts ADD CONSTRAINT posts_slug_key UNIQUE (slug);
+
+-- Rows written without a slug get a placeholder instead of failing
+CREATE FUNCTION posts_default_slug() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.slug := 'post-' || NEW.id;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER posts_default_slug
+    BEFORE INSERT ON posts
+    FOR EACH ROW WHEN (NEW.slug IS NULL)
+    EXECUTE FUNCTION posts_default_slug();
+
+-- Slugs a post had before, answered with a redirect to its current one
+CREATE TABLE post_slug_redirects
+(
+    slug       TEXT PRIMARY KEY,
+    post_id    INTEGER     NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX post_slug_redirects_post_id_idx ON post_slug_redirects (post_id);
@@ -0,0 +1,4 @@
+-- Sealed secrets can't be decrypted here, those keys go; tokens they signed
+-- stop verifying and their users have to sign in again
+DELETE FROM signing_keys WHERE sealed;
+ALTER TABLE signing_keys DROP COLUMN sealed;
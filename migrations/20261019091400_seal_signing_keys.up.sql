@@ -0,0 +1,3 @@
+-- Signing keys are stored encrypted under a key derived from JWT_SECRET.
+-- Existing rows keep their plain secret until the next rotation seals them.
+ALTER TABLE signing_keys ADD COLUMN sealed BOOLEAN NOT NULL DEFAULT FALSE;
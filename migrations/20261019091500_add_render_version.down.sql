@@ -0,0 +1,12 @@
+CREATE OR REPLACE FUNCTION posts_stale_rendering() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.body_html IS NOT DISTINCT FROM OLD.body_html THEN
+        NEW.body_html := NULL;
+        NEW.excerpt := NULL;
+        NEW.reading_minutes := NULL;
+    END IF;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE posts DROP COLUMN render_version;
@@ -0,0 +1,16 @@
+-- The renderer that produced body_html; `rustrest-admin rerender-posts`
+-- renders the posts of older renderers, or none, again
+ALTER TABLE posts ADD COLUMN render_version INTEGER;
+
+-- A body changed without rendering it along waits for the next rerender
+CREATE OR REPLACE FUNCTION posts_stale_rendering() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.body_html IS NOT DISTINCT FROM OLD.body_html THEN
+        NEW.body_html := NULL;
+        NEW.excerpt := NULL;
+        NEW.reading_minutes := NULL;
+        NEW.render_version := NULL;
+    END IF;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,2 @@
+DROP INDEX post_slug_redirects_slug_prefix_idx;
+DROP INDEX posts_slug_prefix_idx;
@@ -0,0 +1,4 @@
+-- Prefix matches for the "-2", "-3" suffixes of generated slugs, whatever
+-- the database's collation
+CREATE INDEX posts_slug_prefix_idx ON posts (slug text_pattern_ops);
+CREATE INDEX post_slug_redirects_slug_prefix_idx ON post_slug_redirects (slug text_pattern_ops);
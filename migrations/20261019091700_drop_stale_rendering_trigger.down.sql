@@ -0,0 +1,16 @@
+CREATE FUNCTION posts_stale_rendering() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.body_html IS NOT DISTINCT FROM OLD.body_html THEN
+        NEW.body_html := NULL;
+        NEW.excerpt := NULL;
+        NEW.reading_minutes := NULL;
+        NEW.render_version := NULL;
+    END IF;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER posts_stale_rendering
+    BEFORE UPDATE OF body ON posts
+    FOR EACH ROW WHEN (OLD.body IS DISTINCT FROM NEW.body)
+    EXECUTE FUNCTION posts_stale_rendering();
@@ -0,0 +1,5 @@
+-- The application renders every body it writes. Telling a body changed
+-- behind its back from one rendered to the same HTML isn't possible here,
+-- so bodies written straight into the database need `rerender-posts`.
+DROP TRIGGER posts_stale_rendering ON posts;
+DROP FUNCTION posts_stale_rendering();
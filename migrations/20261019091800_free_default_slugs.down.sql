@@ -0,0 +1,6 @@
+CREATE OR REPLACE FUNCTION posts_default_slug() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.slug := 'post-' || NEW.id;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,16 @@
+-- `post-<id>` may already be a post's slug, or one it had before: count up
+-- `post-<id>-2`, `-3`, ... until one is free
+CREATE OR REPLACE FUNCTION posts_default_slug() RETURNS TRIGGER AS $$
+DECLARE
+    candidate TEXT := 'post-' || NEW.id;
+    suffix    INTEGER := 1;
+BEGIN
+    WHILE EXISTS (SELECT 1 FROM posts WHERE slug = candidate)
+        OR EXISTS (SELECT 1 FROM post_slug_redirects WHERE slug = candidate) LOOP
+        suffix := suffix + 1;
+        candidate := 'post-' || NEW.id || '-' || suffix;
+    END LOOP;
+    NEW.slug := candidate;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,203 @@
+use chrono::Duration;
+use clap::Subcommand;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+
+use crate::audit::{AuditEvent, Auditor};
+use crate::auth::jwt::{self, Claims, JwtAuth, TOKEN_LIFETIME_MINUTES};
+use crate::auth::rbac::Role;
+use crate::auth::revocation;
+use crate::markdown;
+use crate::models::audit_record::AuditRecord;
+use crate::models::post;
+use crate::models::user::{NewUser, User};
+
+// Operations for the `rustrest-admin` binary. Each returns a JSON value so the
+// binary can print it either for humans or, with --json, for scripts.
+#[derive(Subcommand, Debug)]
+pub enum AdminCommand {
+    /// Create a user (the password is read from stdin when --password is omitted)
+    CreateUser {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Set a new password for a user (read from stdin when --password is omitted)
+    ResetPassword {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Grant a role (editor, admin) to a user
+    GrantRole {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        role: String,
+    },
+    /// Revoke a previously granted role
+    RevokeRole {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        role: String,
+    },
+    /// Issue an access token for a user, for debugging
+    MintToken {
+        #[arg(long)]
+        username: String,
+        /// Tokens living longer than the default stop verifying early after a key rotation
+        #[arg(long, default_value_t = TOKEN_LIFETIME_MINUTES)]
+        ttl_minutes: i64,
+    },
+    /// Retire the current signing key and generate a new one
+    RotateKeys,
+    /// Revoke an access token before it expires
+    RevokeToken {
+        token: String,
+    },
+    /// Remove revocations of tokens that have expired anyway
+    PurgeExpiredTokens,
+    /// Check the audit log's hash chain, fails at the first tampered event
+    VerifyAuditChain,
+    /// Render the post bodies that an older renderer, or none, turned into HTML
+    RerenderPosts,
+}
+
+impl AdminCommand {
+    // The password argument, for commands that take one
+    pub fn password_mut(&mut self) -> Option<&mut Option<String>> {
+        match self {
+            AdminCommand::CreateUser { password, .. } | AdminCommand::ResetPassword { password, .. } => Some(password),
+            _ => None,
+        }
+    }
+}
+
+pub async fn execute(
+    command: AdminCommand,
+    pool: &Pool<Postgres>,
+    jwt_auth: &JwtAuth,
+) -> anyhow::Result<Value> {
+    let auditor = Auditor::system(pool.clone(), cli_actor());
+    let output = match command {
+        AdminCommand::CreateUser { username, email, password } => {
+            let new_user = NewUser {
+                username,
+                email,
+                password: String::new(),
+            };
+            let user = User::create(new_user, required(password)?, pool).await?;
+            auditor
+                .record(AuditEvent::new("user.created").resource(user_resource(&user)).change(None, Some(&user)))
+                .await;
+            serde_json::to_value(user)?
+        }
+        AdminCommand::ResetPassword { username, password } => {
+            let user = User::find_by_username(&username, pool).await?;
+            user.set_password(required(password)?, pool).await?;
+            auditor.record(AuditEvent::new("password.reset").resource(user_resource(&user))).await;
+            json!({ "username": user.username, "password_reset": true })
+        }
+        AdminCommand::GrantRole { username, role } => {
+            let role: Role = role.parse()?;
+            let user = User::find_by_username(&username, pool).await?;
+            let before = user.roles(pool).await?;
+            let changed = user.grant_role(&role, pool).await?;
+            let roles = user.roles(pool).await?;
+            if changed {
+                auditor
+                    .record(
+                        AuditEvent::new("role.granted")
+                            .resource(user_resource(&user))
+                            .detail("role", role.to_string())
+                            .change(Some(&json!({ "roles": before })), Some(&json!({ "roles": roles }))),
+                    )
+                    .await;
+            }
+            json!({ "username": user.username, "role": role.to_string(), "granted": changed, "roles": roles })
+        }
+        AdminCommand::RevokeRole { username, role } => {
+            let role: Role = role.parse()?;
+            let user = User::find_by_username(&username, pool).await?;
+            let before = user.roles(pool).await?;
+            let changed = user.revoke_role(&role, pool).await?;
+            let roles = user.roles(pool).await?;
+            if changed {
+                auditor
+                    .record(
+                        AuditEvent::new("role.revoked")
+                            .resource(user_resource(&user))
+                            .detail("role", role.to_string())
+                            .change(Some(&json!({ "roles": before })), Some(&json!({ "roles": roles }))),
+                    )
+                    .await;
+            }
+            json!({ "username": user.username, "role": role.to_string(), "revoked": changed, "roles": roles })
+        }
+        AdminCommand::MintToken { username, ttl_minutes } => {
+            let user = User::find_by_username(&username, pool).await?;
+            let claims = Claims::new(user.id.to_string(), user.roles(pool).await?, Duration::minutes(ttl_minutes));
+            auditor
+                .record(
+                    AuditEvent::new("token.minted")
+                        .resource(user_resource(&user))
+                        .detail("jti", &claims.jti)
+                        .detail("ttl_minutes", ttl_minutes),
+                )
+                .await;
+            json!({
+                "access_token": jwt_auth.create_token(&claims)?,
+                "token_type": "Bearer",
+                "expires_in": ttl_minutes * 60,
+                "jti": claims.jti,
+                "roles": claims.roles,
+            })
+        }
+        AdminCommand::RotateKeys => {
+            let kid = jwt::rotate_signing_key(jwt_auth, pool).await?;
+            auditor.record(AuditEvent::new("signing_key.rotated").resource(format!("signing_key:{}", kid))).await;
+            json!({ "kid": kid, "retired_keys_valid_for_minutes": TOKEN_LIFETIME_MINUTES })
+        }
+        AdminCommand::RevokeToken { token } => {
+            let claims = jwt_auth.verify_token(&token)?;
+            revocation::revoke(&claims, pool).await?;
+            auditor
+                .record(AuditEvent::new("token.revoked").resource(format!("user:{}", claims.sub)).detail("jti", &claims.jti))
+                .await;
+            json!({ "jti": claims.jti, "user_id": claims.sub, "revoked": true })
+        }
+        AdminCommand::PurgeExpiredTokens => {
+            json!({ "purged": revocation::purge_expired(pool).await? })
+        }
+        AdminCommand::VerifyAuditChain => {
+            let verification = AuditRecord::verify_chain(pool).await?;
+            if let (Some(id), Some(reason)) = (verification.broken_at, verification.reason) {
+                anyhow::bail!("audit chain broken at event {} ({}), {} events before it are intact", id, reason, verification.events);
+            }
+            json!({ "events": verification.events, "intact": true })
+        }
+        AdminCommand::RerenderPosts => {
+            json!({ "rerendered": post::rerender_outdated(pool).await?, "renderer_version": markdown::RENDERER_VERSION })
+        }
+    };
+    Ok(output)
+}
+
+// Operator running the command, as far as the environment tells
+fn cli_actor() -> String {
+    let user = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+    format!("rustrest-admin:{}", user)
+}
+
+fn user_resource(user: &User) -> String {
+    format!("user:{}", user.id)
+}
+
+fn required(password: Option<String>) -> anyhow::Result<String> {
+    password.ok_or_else(|| anyhow::anyhow!("a password is required"))
+}
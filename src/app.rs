@@ -0,0 +1,193 @@
+use axum::extract::{DefaultBodyLimit, FromRef};
+use axum::routing::{delete, get, post, put};
+use axum::{middleware, Router};
+use metrics_exporter_prometheus::PrometheusHandle;
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+use std::time::Duration;
+use tower_http::trace::TraceLayer;
+
+use crate::audit::{self, DenialLog};
+use crate::auth;
+use crate::auth::jwt::{self, JwtAuth};
+use crate::auth::rbac::{require_role, Role};
+use crate::auth::revocation::RevocationCache;
+use crate::config::Config;
+use crate::jobs::{self, JobRunner};
+use crate::middleware::cors::cors_layer;
+use crate::middleware::rate_limit::RateLimiter;
+use crate::middleware::security_headers::HeaderPolicy;
+use crate::middleware::{audit_log, auth_middleware, request_id, security_headers, track_metrics};
+use crate::services::audit::get_audit_events;
+use crate::services::categories::{create_category, delete_category, get_categories, update_category};
+use crate::services::comments::{
+    approve_comment, ban_comment_author, create_comment, delete_comment, edit_comment, get_comments,
+    get_moderation_queue, hide_comment, lift_comment_ban, set_comment_settings,
+};
+use crate::services::csp::{csp_report, get_csp_reports};
+use crate::services::health::{healthz, readyz};
+use crate::services::jobs::{get_jobs, retry_job};
+use crate::services::logging::{get_log_level, set_log_level};
+use crate::services::metrics::metrics;
+use crate::services::posts::{
+    approve_post, archive_post, autocomplete_posts, create_post, diff_post_revisions, get_post, get_post_by_slug,
+    get_post_reviews, get_post_revision, get_post_revisions, get_post_tags, get_posts, reject_post,
+    restore_post_revision, schedule_post, search_posts, set_post_category, set_post_slug, set_post_tags, submit_post,
+    update_post,
+};
+use crate::services::reactions::{delete_bookmark, delete_reaction, get_bookmarks, put_bookmark, put_reaction};
+use crate::services::tags::{create_tag, delete_tag, get_tags, merge_tag, rename_tag};
+use crate::shutdown::Lifecycle;
+use crate::telemetry;
+
+// Shared application state, handed to every handler and middleware.
+// Handlers extract only the part they need, e.g. `State<Pool<Postgres>>`.
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub pool: Pool<Postgres>,
+    pub jwt_auth: Arc<JwtAuth>,
+    pub revocations: Arc<RevocationCache>,
+    pub config: Arc<Config>,
+    pub lifecycle: Lifecycle,
+    pub denials: Arc<DenialLog>,
+    pub csp_report_limiter: Arc<RateLimiter>,
+    // Renders what the installed recorder collected, see `metrics::install_recorder`
+    pub metrics: PrometheusHandle,
+}
+
+// CSP reports are small, anything bigger is not a report
+const CSP_REPORT_MAX_BYTES: usize = 64 * 1024;
+
+// How often queued `access.denied` events are written
+const DENIAL_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+impl AppState {
+    pub fn new(pool: Pool<Postgres>, config: Config, metrics: PrometheusHandle) -> Self {
+        Self {
+            denials: Arc::new(DenialLog::new(pool.clone())),
+            pool,
+            jwt_auth: Arc::new(JwtAuth::new(config.jwt_secret.as_bytes())),
+            revocations: Arc::new(RevocationCache::new(config.revocation_cache_ttl)),
+            csp_report_limiter: Arc::new(RateLimiter::per_minute(config.csp_reports_per_minute)),
+            config: Arc::new(config),
+            lifecycle: Lifecycle::new(),
+            metrics,
+        }
+    }
+
+    // Starts the server's background tasks, they stop once shutdown begins
+    pub fn spawn_background_tasks(&self) {
+        self.lifecycle.spawn(jwt::refresh_keys(
+            Arc::clone(&self.jwt_auth),
+            self.pool.clone(),
+            self.config.key_refresh_interval,
+            self.lifecycle.shutdown_requested(),
+        ));
+        self.lifecycle.spawn(jobs::run(
+            JobRunner::new(self.pool.clone()),
+            self.config.job_poll_interval,
+            self.lifecycle.shutdown_requested(),
+        ));
+        self.lifecycle.spawn(audit::write_denials(
+            Arc::clone(&self.denials),
+            DENIAL_FLUSH_INTERVAL,
+            self.lifecycle.shutdown_requested(),
+        ));
+    }
+}
+
+// Builds the complete API router. The caller decides how to serve it;
+// `audit_log` needs `ConnectInfo<SocketAddr>`, so serve it with
+// `into_make_service_with_connect_info::<SocketAddr>()` (or add `MockConnectInfo` in tests).
+pub fn app(state: AppState) -> Router {
+    // Security headers per route group, see `SecurityHeadersConfig`
+    let headers = |group| {
+        middleware::from_fn_with_state(HeaderPolicy::for_group(&state.config.security_headers, group), security_headers)
+    };
+
+    // Public routes
+    let public = Router::new()
+        .route("/login", post(auth::login))
+        .route("/register", post(auth::register))
+        .route("/csp-report", post(csp_report).layer(DefaultBodyLimit::max(CSP_REPORT_MAX_BYTES)))
+        .layer(headers("public"));
+
+    // Probes and metrics
+    let ops = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .layer(headers("ops"));
+
+    // Admin routes, the role check runs after authentication
+    let admin = Router::new()
+        .route("/admin/log-level", get(get_log_level).put(set_log_level))
+        .route("/admin/audit-events", get(get_audit_events))
+        .route("/admin/csp-reports", get(get_csp_reports))
+        .route("/admin/jobs", get(get_jobs))
+        .route("/admin/jobs/{id}/retry", post(retry_job))
+        .route("/admin/tags/{id}", put(rename_tag))
+        .route("/admin/tags/{id}/merge", post(merge_tag))
+        .route_layer(middleware::from_fn(|request, next| require_role(Role::Admin, request, next)))
+        .layer(headers("admin"));
+
+    // Protected routes, authentication only applies to matched routes
+    let protected = Router::new()
+        .route("/posts", get(get_posts).post(create_post))
+        .route("/posts/search", get(search_posts))
+        .route("/posts/autocomplete", get(autocomplete_posts))
+        .route("/posts/by-slug/{slug}", get(get_post_by_slug))
+        .route("/posts/{id}", get(get_post).put(update_post))
+        .route("/posts/{id}/submit", post(submit_post))
+        .route("/posts/{id}/approve", post(approve_post))
+        .route("/posts/{id}/reject", post(reject_post))
+        .route("/posts/{id}/schedule", put(schedule_post))
+        .route("/posts/{id}/archive", post(archive_post))
+        .route("/posts/{id}/reviews", get(get_post_reviews))
+        .route("/posts/{id}/revisions", get(get_post_revisions))
+        .route("/posts/{id}/revisions/diff", get(diff_post_revisions))
+        .route("/posts/{id}/revisions/{rev}", get(get_post_revision))
+        .route("/posts/{id}/revisions/{rev}/restore", post(restore_post_revision))
+        .route("/posts/{id}/tags", get(get_post_tags).put(set_post_tags))
+        .route("/posts/{id}/category", put(set_post_category))
+        .route("/posts/{id}/slug", put(set_post_slug))
+        .route("/posts/{id}/comments", get(get_comments).post(create_comment))
+        .route("/posts/{id}/comments/settings", put(set_comment_settings))
+        .route("/posts/{id}/reactions/{reaction}", put(put_reaction).delete(delete_reaction))
+        .route("/posts/{id}/bookmark", put(put_bookmark).delete(delete_bookmark))
+        .route("/me/bookmarks", get(get_bookmarks))
+        .route("/comments/moderation", get(get_moderation_queue))
+        .route("/comments/{id}", put(edit_comment).delete(delete_comment))
+        .route("/comments/{id}/approve", post(approve_comment))
+        .route("/comments/{id}/hide", post(hide_comment))
+        .route("/comments/{id}/ban", post(ban_comment_author))
+        .route("/comments/bans/{user_id}", delete(lift_comment_ban))
+        .route("/tags", get(get_tags).post(create_tag))
+        .route("/tags/{id}", delete(delete_tag))
+        .route("/categories", get(get_categories).post(create_category))
+        .route("/categories/{id}", put(update_category).delete(delete_category))
+        .merge(admin)
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth_middleware))
+        .layer(headers("api"));
+
+    let mut router = Router::new()
+        .merge(public)
+        .merge(ops)
+        .merge(protected)
+        .layer(middleware::from_fn(track_metrics))
+        .layer(middleware::from_fn_with_state(state.clone(), audit_log))
+        .layer(headers("default")); // Security headers for unmatched requests
+    // Preflights are answered here, before any route or authentication sees them
+    if let Some(cors) = &state.config.cors {
+        router = router.layer(cors_layer(cors));
+    }
+
+    router
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::request_span)
+                .on_response(telemetry::record_response),
+        ) // Request tracing
+        .layer(middleware::from_fn(request_id))
+        .with_state(state)
+}
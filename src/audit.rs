@@ -0,0 +1,273 @@
+use axum::extract::{ConnectInfo, FromRef, FromRequestParts};
+use axum::http::request::Parts;
+use metrics::counter;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sqlx::{Pool, Postgres};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{error, info, warn};
+
+use crate::auth::jwt::Claims;
+use crate::middleware::RequestId;
+use crate::redact::redactor;
+use crate::models::audit_record::{AuditRecord, NewAuditRecord, OUTCOME_FAILURE, OUTCOME_SUCCESS};
+
+// Actor of requests without a verified identity
+pub const ANONYMOUS: &str = "anonymous";
+
+// A domain action worth keeping in the audit log, named `<resource>.<verb>`
+// (`user.registered`, `login.failed`, `role.granted`, ...). Handlers build one
+// and hand it to their `Auditor`:
+//
+//     auditor.record(AuditEvent::new("role.granted").resource(format!("user:{}", id)).detail("role", "editor")).await;
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    action: &'static str,
+    actor: Option<String>,
+    resource: Option<String>,
+    outcome: &'static str,
+    details: Map<String, Value>,
+}
+
+impl AuditEvent {
+    pub fn new(action: &'static str) -> Self {
+        Self {
+            action,
+            actor: None,
+            resource: None,
+            outcome: OUTCOME_SUCCESS,
+            details: Map::new(),
+        }
+    }
+
+    pub fn failed(mut self) -> Self {
+        self.outcome = OUTCOME_FAILURE;
+        self
+    }
+
+    // Overrides the actor, for actions that establish the identity such as a login
+    pub fn actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    pub fn resource(mut self, resource: impl Into<String>) -> Self {
+        self.resource = Some(resource.into());
+        self
+    }
+
+    pub fn detail(mut self, key: &str, value: impl Serialize) -> Self {
+        self.details.insert(key.to_string(), json!(value));
+        self
+    }
+
+    // Records the fields that differ between the two states as `changes`, each
+    // with its `before` and `after` value. Pass `None` for a created or deleted resource.
+    pub fn change<T: Serialize>(mut self, before: Option<&T>, after: Option<&T>) -> Self {
+        let before = fields(before);
+        let after = fields(after);
+        let mut changes = Map::new();
+        for key in before.keys().chain(after.keys()) {
+            let (old, new) = (before.get(key), after.get(key));
+            if old != new && !changes.contains_key(key) {
+                changes.insert(key.clone(), json!({ "before": old, "after": new }));
+            }
+        }
+        self.details.insert("changes".to_string(), Value::Object(changes));
+        self
+    }
+}
+
+fn fields<T: Serialize>(state: Option<&T>) -> Map<String, Value> {
+    match state.map(serde_json::to_value) {
+        Some(Ok(Value::Object(fields))) => fields,
+        Some(Ok(value)) => Map::from_iter([("value".to_string(), value)]),
+        _ => Map::new(),
+    }
+}
+
+// Shared between `audit_log`, which runs outside of authentication, and the
+// inner layers: `auth_middleware` fills in the verified caller, and handlers
+// mark that they recorded an event, so request-level events carry the right
+// actor and aren't recorded twice.
+#[derive(Clone, Default)]
+pub struct AuditScope(Arc<ScopeState>);
+
+#[derive(Default)]
+struct ScopeState {
+    actor: OnceLock<String>,
+    recorded: AtomicBool,
+}
+
+impl AuditScope {
+    pub fn set_actor(&self, actor: &str) {
+        let _ = self.0.actor.set(actor.to_string());
+    }
+
+    pub fn actor(&self) -> Option<&str> {
+        self.0.actor.get().map(String::as_str)
+    }
+
+    pub fn is_recorded(&self) -> bool {
+        self.0.recorded.load(Ordering::Relaxed)
+    }
+}
+
+// Writes audit events on behalf of whoever is making the request: the actor is
+// the verified token subject, with the client address and request id attached.
+#[derive(Clone)]
+pub struct Auditor {
+    pool: Pool<Postgres>,
+    actor: String,
+    ip: Option<String>,
+    request_id: Option<String>,
+    scope: Option<AuditScope>,
+}
+
+impl Auditor {
+    // For actions outside of a request, such as the admin CLI
+    pub fn system(pool: Pool<Postgres>, actor: impl Into<String>) -> Self {
+        Self { pool, actor: actor.into(), ip: None, request_id: None, scope: None }
+    }
+
+    // Logs the event under the `AUDIT` target and stores it. A failure to store
+    // is logged, the action itself has already happened.
+    pub async fn record(&self, event: AuditEvent) {
+        if let Some(scope) = &self.scope {
+            scope.0.recorded.store(true, Ordering::Relaxed);
+        }
+        let redactor = redactor();
+        let mut details = Value::Object(event.details);
+        redactor.json(&mut details);
+        let record = NewAuditRecord {
+            actor: event.actor.unwrap_or_else(|| self.actor.clone()),
+            ip: self.ip.clone(),
+            action: event.action.to_string(),
+            resource: event.resource,
+            outcome: event.outcome,
+            request_id: self.request_id.clone(),
+            details,
+        };
+
+        // Log lines name users by pseudonym when pseudonymisation is on, the stored record keeps the id
+        let request_id = record.request_id.as_deref().unwrap_or_default();
+        let actor = redactor.user(&record.actor);
+        let resource = record.resource.as_deref().map(|resource| match resource.strip_prefix("user:") {
+            Some(user_id) => format!("user:{}", redactor.user(user_id)),
+            None => resource.to_string(),
+        });
+        if record.outcome == OUTCOME_FAILURE {
+            warn!(target: "AUDIT", request_id, action = %record.action, actor, resource, outcome = record.outcome, "Audit event");
+        } else {
+            info!(target: "AUDIT", request_id, action = %record.action, actor, resource, outcome = record.outcome, "Audit event");
+        }
+
+        if let Err(e) = AuditRecord::append(record, &self.pool).await {
+            error!(target: "AUDIT", action = event.action, "Failed to store audit event: {}", e);
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for Auditor
+where
+    Pool<Postgres>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let actor = parts
+            .extensions
+            .get::<Claims>()
+            .map(|claims| claims.sub.clone())
+            .unwrap_or_else(|| ANONYMOUS.to_string());
+        let ip = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        let request_id = parts.extensions.get::<RequestId>().map(RequestId::to_string);
+
+        Ok(Self {
+            pool: Pool::<Postgres>::from_ref(state),
+            actor,
+            ip,
+            request_id,
+            scope: parts.extensions.get::<AuditScope>().cloned(),
+        })
+    }
+}
+
+// Queued rejections the denial log holds at most, anything beyond is dropped
+const DENIAL_QUEUE: usize = 10_000;
+// Rejections appended per transaction
+const DENIAL_BATCH: usize = 500;
+
+// `access.denied` events for requests rejected before any handler recorded
+// them. These cost the client nothing to cause, so they are queued and
+// appended in batches in the background rather than taking the chain lock
+// once per request; when the queue is full they are dropped and counted.
+pub struct DenialLog {
+    pool: Pool<Postgres>,
+    sender: mpsc::Sender<NewAuditRecord>,
+    receiver: tokio::sync::Mutex<mpsc::Receiver<NewAuditRecord>>,
+}
+
+impl DenialLog {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self::with_capacity(pool, DENIAL_QUEUE)
+    }
+
+    pub fn with_capacity(pool: Pool<Postgres>, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(capacity);
+        Self { pool, sender, receiver: tokio::sync::Mutex::new(receiver) }
+    }
+
+    pub fn record(&self, event: NewAuditRecord) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(event) {
+            counter!("audit_denials_dropped_total").increment(1);
+            warn!(target: "AUDIT", "Denial log is full, dropping access.denied event");
+        }
+    }
+
+    // Appends everything queued so far, returns the number of events stored
+    pub async fn flush(&self) -> Result<usize, sqlx::Error> {
+        let mut receiver = self.receiver.lock().await;
+        let mut stored = 0;
+        loop {
+            let mut batch = Vec::new();
+            while batch.len() < DENIAL_BATCH
+                && let Ok(event) = receiver.try_recv()
+            {
+                batch.push(event);
+            }
+            if batch.is_empty() {
+                return Ok(stored);
+            }
+            stored += batch.len();
+            AuditRecord::append_all(batch, &self.pool).await?;
+        }
+    }
+}
+
+// Flushes the denial log every `interval`, and a last time on shutdown
+pub async fn write_denials(log: Arc<DenialLog>, interval: Duration, shutdown: impl Future<Output = ()>) {
+    tokio::pin!(shutdown);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        let stop = tokio::select! {
+            _ = &mut shutdown => true,
+            _ = ticker.tick() => false,
+        };
+        if let Err(e) = log.flush().await {
+            error!(target: "AUDIT", "Failed to store access.denied events: {}", e);
+        }
+        if stop {
+            break;
+        }
+    }
+}
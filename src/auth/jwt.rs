@@ -1,19 +1,25 @@
 use axum::{
     http::request::Parts,
     RequestPartsExt,
-    extract::FromRequestParts,
+    extract::{FromRef, FromRequestParts},
 };
 use axum_extra::headers::Authorization;
 use axum_extra::headers::authorization::Bearer;
 use axum_extra::TypedHeader;
-use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use chrono::{DateTime, Duration, Utc};
+use metrics::counter;
+use jsonwebtoken::{decode, decode_header, encode, DecodingKey, EncodingKey, Header, Validation};
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, NONCE_LEN};
+use ring::hkdf;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use sqlx::{Pool, Postgres};
+use std::sync::{Arc, RwLock};
 use tracing;
 use uuid::Uuid;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
 
 use crate::services::error::AppError;
+use crate::db::traced;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
@@ -35,49 +41,262 @@ impl Claims {
             roles,
         }
     }
+
+    // Tokens are issued for users, the subject is their numeric id
+    pub fn user_id(&self) -> Result<i32, AppError> {
+        self.sub.parse().map_err(|_| AppError::InvalidToken)
+    }
+}
+
+// Lifetime of tokens issued by /login. Retired signing keys keep verifying for
+// this long after rotation, so no token that is still valid gets rejected.
+pub const TOKEN_LIFETIME_MINUTES: i64 = 15;
+
+struct VerifyingKey {
+    kid: Option<String>,
+    key: DecodingKey,
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredKey {
+    kid: String,
+    secret: Vec<u8>,
+    // Rows from before sealing hold the plain secret until the next rotation
+    sealed: bool,
+    retired_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Clone)]
+// Seals stored signing keys with AES-256-GCM, under a key derived from the
+// configured secret and with the key id as associated data. A database dump
+// alone then can't be used to forge tokens.
+struct KeySealer(LessSafeKey);
+
+impl KeySealer {
+    fn new(secret: &[u8]) -> Self {
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, b"rustrest signing keys").extract(secret);
+        let key = prk
+            .expand(&[b"aes-256-gcm"], &aead::AES_256_GCM)
+            .expect("an AES key is well within HKDF's output limit");
+        Self(LessSafeKey::new(UnboundKey::from(key)))
+    }
+
+    // The random nonce followed by the encrypted secret and its tag
+    fn seal(&self, kid: &str, secret: &[u8]) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let mut sealed = secret.to_vec();
+        self.0
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(kid.as_bytes()), &mut sealed)
+            .expect("signing keys are far below the AES-GCM size limit");
+        [nonce.as_slice(), &sealed].concat()
+    }
+
+    // None if the key was sealed under another configured secret or altered
+    fn open(&self, kid: &str, sealed: &[u8]) -> Option<Vec<u8>> {
+        let (nonce, ciphertext) = sealed.split_at_checked(NONCE_LEN)?;
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+        let mut buffer = ciphertext.to_vec();
+        let secret = self.0.open_in_place(nonce, Aad::from(kid.as_bytes()), &mut buffer).ok()?;
+        Some(secret.to_vec())
+    }
+}
+
+struct KeyRing {
+    signing_kid: Option<String>,
+    signing_key: EncodingKey,
+    verifying: Vec<VerifyingKey>,
+}
+
+// Signs and verifies tokens. Starts out with the configured secret only; once
+// keys have been rotated into the `signing_keys` table, `reload_keys` switches
+// to those and tokens carry the `kid` of the key that signed them.
 pub struct JwtAuth {
-    encoding_key: EncodingKey,
-    decoding_key: DecodingKey,
+    secret: Vec<u8>,
+    sealer: KeySealer,
+    keys: RwLock<KeyRing>,
 }
 
 impl JwtAuth {
     pub fn new(secret: &[u8]) -> Self {
         Self {
-            encoding_key: EncodingKey::from_secret(secret),
-            decoding_key: DecodingKey::from_secret(secret),
+            secret: secret.to_vec(),
+            sealer: KeySealer::new(secret),
+            keys: RwLock::new(KeyRing {
+                signing_kid: None,
+                signing_key: EncodingKey::from_secret(secret),
+                verifying: vec![VerifyingKey { kid: None, key: DecodingKey::from_secret(secret) }],
+            }),
+        }
+    }
+
+    // Loads the signing keys from the database, returns the number of usable keys.
+    // Without any stored keys the configured secret stays in use.
+    pub async fn reload_keys(&self, pool: &Pool<Postgres>) -> Result<usize, sqlx::Error> {
+        let grace = Duration::minutes(TOKEN_LIFETIME_MINUTES);
+        let rows: Vec<StoredKey> = sqlx::query_as(
+            "SELECT kid, secret, sealed, retired_at FROM signing_keys \
+             WHERE retired_at IS NULL OR retired_at > $1 ORDER BY created_at DESC",
+        )
+        .bind(Utc::now() - grace)
+        .fetch_all(traced(pool))
+        .await?;
+        let rows: Vec<StoredKey> = rows.into_iter().filter_map(|key| self.unseal(key)).collect();
+
+        let Some(signing) = rows.iter().find(|k| k.retired_at.is_none()) else {
+            return Ok(self.keys.read().unwrap().verifying.len());
+        };
+
+        let mut verifying: Vec<VerifyingKey> = rows
+            .iter()
+            .map(|k| VerifyingKey {
+                kid: Some(k.kid.clone()),
+                key: DecodingKey::from_secret(&k.secret),
+            })
+            .collect();
+
+        // The configured secret counts as retired when the first stored key was created
+        let first_rotation = sqlx::query_scalar::<_, Option<DateTime<Utc>>>("SELECT min(created_at) FROM signing_keys")
+            .fetch_one(traced(pool))
+            .await?;
+        if first_rotation.is_some_and(|at| at > Utc::now() - grace) {
+            verifying.push(VerifyingKey { kid: None, key: DecodingKey::from_secret(&self.secret) });
         }
+
+        let count = verifying.len();
+        *self.keys.write().unwrap() = KeyRing {
+            signing_kid: Some(signing.kid.clone()),
+            signing_key: EncodingKey::from_secret(&signing.secret),
+            verifying,
+        };
+        Ok(count)
+    }
+
+    // The stored key with its plain secret, None if it can't be opened
+    fn unseal(&self, key: StoredKey) -> Option<StoredKey> {
+        if !key.sealed {
+            return Some(key);
+        }
+        let Some(secret) = self.sealer.open(&key.kid, &key.secret) else {
+            tracing::error!(kid = %key.kid, "Signing key can't be opened with the configured JWT_SECRET, skipping it");
+            return None;
+        };
+        Some(StoredKey { secret, sealed: false, ..key })
     }
 
     pub fn create_token(&self, claims: &Claims) -> Result<String, AppError> {
-        encode(&Header::default(), claims, &self.encoding_key)
+        let keys = self.keys.read().unwrap();
+        let header = Header {
+            kid: keys.signing_kid.clone(),
+            ..Header::default()
+        };
+        encode(&header, claims, &keys.signing_key)
             .map_err(|_| AppError::TokenCreation)
     }
 
+    // Signs and verifies a short-lived probe token, proves the keys are loaded and usable
+    pub fn self_check(&self) -> Result<(), AppError> {
+        let claims = Claims::new("self-check".to_string(), Vec::new(), Duration::seconds(30));
+        let token = self.create_token(&claims)?;
+        self.verify_token(&token).map(|_| ())
+    }
+
     pub fn verify_token(&self, token: &str) -> Result<Claims, AppError> {
         // Create a validation object with default settings
         let mut validation = Validation::default();
         validation.validate_exp = true; // Verify expiration time
         validation.leeway = 0; // No leeway for exp verification (default)
         
+        // Find the key that signed the token
+        let kid = decode_header(token)
+            .map_err(|_| AppError::InvalidToken)
+            .inspect_err(record_verification_failure)?
+            .kid;
+        let keys = self.keys.read().unwrap();
+        let key = keys
+            .verifying
+            .iter()
+            .find(|k| k.kid == kid)
+            .ok_or(AppError::InvalidToken)
+            .inspect_err(record_verification_failure)?;
+
         // Decode and verify the token
-        match decode::<Claims>(token, &self.decoding_key, &validation) {
+        match decode::<Claims>(token, &key.key, &validation) {
             Ok(token_data) => {
                 // Token is valid, return claims
                 Ok(token_data.claims)
             }
             Err(e) => {
-                // Log the error for debugging
-                tracing::error!("Token validation error: {:?}", e);
-                
                 // Map jsonwebtoken errors to AppError
-                match e.kind() {
-                    jsonwebtoken::errors::ErrorKind::ExpiredSignature => Err(AppError::TokenExpired),
-                    jsonwebtoken::errors::ErrorKind::InvalidToken => Err(AppError::InvalidToken),
-                    jsonwebtoken::errors::ErrorKind::InvalidSignature => Err(AppError::InvalidToken),
-                    _ => Err(AppError::InvalidToken),
+                let error = match e.kind() {
+                    jsonwebtoken::errors::ErrorKind::ExpiredSignature => AppError::TokenExpired,
+                    jsonwebtoken::errors::ErrorKind::InvalidToken => AppError::InvalidToken,
+                    jsonwebtoken::errors::ErrorKind::InvalidSignature => AppError::InvalidToken,
+                    _ => AppError::InvalidToken,
+                };
+                record_verification_failure(&error);
+                Err(error)
+            }
+        }
+    }
+}
+
+// Only the reason is logged, never the token or the decoder's error details
+fn record_verification_failure(error: &AppError) {
+    let reason = match error {
+        AppError::TokenExpired => "expired",
+        _ => "invalid",
+    };
+    tracing::warn!(reason, "Token validation failed");
+    counter!("auth_token_verification_failures_total", "reason" => reason).increment(1);
+}
+
+// Retires the current signing key and stores a fresh random one, sealed with
+// `jwt_auth`'s secret, and seals any keys stored before sealing. Returns the
+// new key id; servers pick it up on their next key refresh.
+pub async fn rotate_signing_key(jwt_auth: &JwtAuth, pool: &Pool<Postgres>) -> Result<String, sqlx::Error> {
+    let kid = Uuid::new_v4().simple().to_string();
+    let mut secret = vec![0u8; 64];
+    OsRng.fill_bytes(&mut secret);
+
+    let mut tx = pool.begin().await?;
+    let unsealed: Vec<(String, Vec<u8>)> = sqlx::query_as("SELECT kid, secret FROM signing_keys WHERE NOT sealed FOR UPDATE")
+        .fetch_all(traced(&mut *tx))
+        .await?;
+    for (old_kid, old_secret) in unsealed {
+        sqlx::query("UPDATE signing_keys SET secret = $2, sealed = TRUE WHERE kid = $1")
+            .bind(&old_kid)
+            .bind(jwt_auth.sealer.seal(&old_kid, &old_secret))
+            .execute(traced(&mut *tx))
+            .await?;
+    }
+    sqlx::query("UPDATE signing_keys SET retired_at = NOW() WHERE retired_at IS NULL")
+        .execute(traced(&mut *tx))
+        .await?;
+    sqlx::query("INSERT INTO signing_keys (kid, secret, sealed) VALUES ($1, $2, TRUE)")
+        .bind(&kid)
+        .bind(jwt_auth.sealer.seal(&kid, &secret))
+        .execute(traced(&mut *tx))
+        .await?;
+    tx.commit().await?;
+
+    Ok(kid)
+}
+
+// Reloads the signing keys every `interval` so rotations reach running servers
+pub async fn refresh_keys(
+    jwt_auth: Arc<JwtAuth>,
+    pool: Pool<Postgres>,
+    interval: std::time::Duration,
+    shutdown: impl Future<Output = ()>,
+) {
+    tokio::pin!(shutdown);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = ticker.tick() => {
+                if let Err(e) = jwt_auth.reload_keys(&pool).await {
+                    tracing::warn!("Failed to reload signing keys: {:?}", e);
                 }
             }
         }
@@ -87,26 +306,29 @@ impl JwtAuth {
 // Extractor for protected routes
 impl<S> FromRequestParts<S> for Claims
 where
+    Arc<JwtAuth>: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = AppError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Already verified by auth_middleware
+        if let Some(claims) = parts.extensions.get::<Claims>() {
+            return Ok(claims.clone());
+        }
+
         // Extract the token from the Authorization header
         let TypedHeader(Authorization(bearer)) = parts
             .extract::<TypedHeader<Authorization<Bearer>>>()
             .await
             .map_err(|_| AppError::MissingToken)?;
 
-        // Get our auth service from extensions
-        let jwt_auth = parts
-            .extensions
-            .get::<Arc<JwtAuth>>()
-            .ok_or(AppError::MissingAuthService)?;
-            
+        // Get our auth service from the application state
+        let jwt_auth = Arc::<JwtAuth>::from_ref(state);
+
         // Verify the token
         let claims = jwt_auth.verify_token(bearer.token())?;
 
         Ok(claims)
     }
-}
\ No newline at end of file
+}
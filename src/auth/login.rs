@@ -1,12 +1,14 @@
 use std::sync::Arc;
-use axum::{extract::{State, Json, Extension}, http::StatusCode};
+use axum::{extract::{State, Json}, http::StatusCode};
 use chrono::Duration;
+use metrics::counter;
 use serde::{Deserialize, Serialize};
 use sqlx::Pool;
 use sqlx::Postgres;
 
+use crate::audit::{AuditEvent, Auditor};
 use crate::services::error::AppError;
-use crate::auth::jwt::{Claims, JwtAuth};
+use crate::auth::jwt::{Claims, JwtAuth, TOKEN_LIFETIME_MINUTES};
 use crate::models::user::User;
 
 #[derive(Deserialize)]
@@ -26,22 +28,31 @@ pub struct LoginResponse {
 
 pub async fn login(
     State(jwt_auth): State<Arc<JwtAuth>>,
-    Extension(pool): Extension<Pool<Postgres>>,
+    State(pool): State<Pool<Postgres>>,
+    auditor: Auditor,
     Json(payload): Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>, AppError> {
     // Authentication against database
-    let user = User::find_by_credentials(
-        &payload.username,
-        payload.password,
-        &pool,
-    )
-    .await?;
+    let user = match User::find_by_credentials(&payload.username, payload.password, &pool).await {
+        Ok(user) => user,
+        Err(e) => {
+            counter!("auth_logins_total", "outcome" => "failure").increment(1);
+            auditor
+                .record(AuditEvent::new("login.failed").failed().detail("username", &payload.username))
+                .await;
+            return Err(e);
+        }
+    };
+    counter!("auth_logins_total", "outcome" => "success").increment(1);
+    auditor
+        .record(AuditEvent::new("login.succeeded").actor(user.id.to_string()).resource(format!("user:{}", user.id)))
+        .await;
 
     // Create token with appropriate roles
-    let expiration = Duration::minutes(15);
+    let expiration = Duration::minutes(TOKEN_LIFETIME_MINUTES);
     let claims = Claims::new(
         user.id.to_string(),
-        vec!["user".to_string()], // Default role - in real app, fetch from database
+        user.roles(&pool).await?,
         expiration,
     );
 
@@ -65,7 +76,8 @@ pub struct RegisterRequest {
 }
 
 pub async fn register(
-    Extension(pool): Extension<Pool<Postgres>>,
+    State(pool): State<Pool<Postgres>>,
+    auditor: Auditor,
     Json(payload): Json<RegisterRequest>,
 ) -> Result<(StatusCode, Json<User>), AppError> {
     // Create the new user
@@ -76,6 +88,9 @@ pub async fn register(
     };
 
     let user = User::create(new_user, payload.password, &pool).await?;
+    auditor
+        .record(AuditEvent::new("user.registered").resource(format!("user:{}", user.id)).change(None, Some(&user)))
+        .await;
 
     // Return the created user (without password)
     Ok((StatusCode::CREATED, Json(user)))
@@ -2,6 +2,7 @@ pub mod rbac;
 pub mod login;
 pub mod jwt;
 pub mod password;
+pub mod revocation;
 
 pub use login::{login, register};
 pub use password::{hash_password, verify_password};
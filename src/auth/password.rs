@@ -3,6 +3,7 @@ use argon2::password_hash::SaltString;
 use argon2::password_hash::rand_core::OsRng;
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 
+#[tracing::instrument(name = "password.hash", skip_all)]
 pub fn hash_password(password: String) -> String {
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
@@ -12,6 +13,7 @@ pub fn hash_password(password: String) -> String {
         .to_string()
 }
 
+#[tracing::instrument(name = "password.verify", skip_all)]
 pub fn verify_password(
     stored_hash: &PasswordHash<'_>,
     password: String,
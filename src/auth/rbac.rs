@@ -1,9 +1,10 @@
 use axum::{
     extract::Request,
     middleware::Next,
-    response::{IntoResponse, Response},
+    response::IntoResponse,
 };
 use std::fmt;
+use std::str::FromStr;
 
 use crate::services::error::AppError;
 use crate::auth::jwt::Claims;
@@ -35,6 +36,20 @@ impl From<&str> for Role {
     }
 }
 
+// Strict parsing for role names supplied by administrators
+impl FromStr for Role {
+    type Err = AppError;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role.to_lowercase().as_str() {
+            "user" => Ok(Role::User),
+            "editor" => Ok(Role::Editor),
+            "admin" => Ok(Role::Admin),
+            _ => Err(AppError::ValidationError(format!("Unknown role: {}", role))),
+        }
+    }
+}
+
 // Simple function to check if a user has a required role
 pub fn has_role(claims: &Claims, required_role: &Role) -> bool {
     claims.roles
@@ -49,6 +64,14 @@ pub fn has_any_role(claims: &Claims, required_roles: &[Role]) -> bool {
         .any(|required| has_role(claims, required))
 }
 
+// The same check inside a handler, for routes that only restrict some methods
+pub fn ensure_role(claims: &Claims, required_role: Role) -> Result<(), AppError> {
+    if !has_role(claims, &required_role) {
+        return Err(AppError::Forbidden(format!("Requires {} role", required_role)));
+    }
+    Ok(())
+}
+
 // Middleware for role-based authorization
 pub async fn require_role(required_role: Role, request: Request, next: Next) -> impl IntoResponse {
     if let Some(claims) = request.extensions().get::<Claims>() {
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::auth::jwt::Claims;
+use crate::db::traced;
+
+// Revokes a token before it expires. The entry is only needed until the token's
+// own expiry, after which `purge_expired` can remove it.
+pub async fn revoke(claims: &Claims, pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+    let expires_at = DateTime::<Utc>::from_timestamp(claims.exp, 0).unwrap_or_else(Utc::now);
+    sqlx::query("INSERT INTO revoked_tokens (jti, expires_at) VALUES ($1, $2) ON CONFLICT (jti) DO NOTHING")
+        .bind(&claims.jti)
+        .bind(expires_at)
+        .execute(traced(pool))
+        .await?;
+    Ok(())
+}
+
+pub async fn is_revoked(jti: &str, pool: &Pool<Postgres>) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM revoked_tokens WHERE jti = $1)")
+        .bind(jti)
+        .fetch_one(traced(pool))
+        .await
+}
+
+// Removes revocations of tokens that have expired anyway, returns the number removed
+pub async fn purge_expired(pool: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < NOW()")
+        .execute(traced(pool))
+        .await?;
+    Ok(result.rows_affected())
+}
+
+// Entries the cache holds at most, the expired ones are dropped first
+const CACHE_CAPACITY: usize = 100_000;
+
+// Remembers revocation checks by `jti` for a short while, so a client
+// sending the same token again doesn't cost a query per request. A
+// revocation takes up to the TTL to reach a server that checked the token
+// shortly before; tokens found revoked stay rejected without another query.
+pub struct RevocationCache {
+    ttl: Duration,
+    checked: Mutex<HashMap<String, (bool, Instant)>>,
+}
+
+impl RevocationCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, checked: Mutex::new(HashMap::new()) }
+    }
+
+    pub async fn is_revoked(&self, jti: &str, pool: &Pool<Postgres>) -> Result<bool, sqlx::Error> {
+        if let Some((revoked, checked_at)) = self.checked.lock().unwrap().get(jti)
+            && (*revoked || checked_at.elapsed() < self.ttl)
+        {
+            return Ok(*revoked);
+        }
+
+        let revoked = is_revoked(jti, pool).await?;
+        if revoked || !self.ttl.is_zero() {
+            let mut checked = self.checked.lock().unwrap();
+            if checked.len() >= CACHE_CAPACITY {
+                checked.retain(|_, (revoked, checked_at)| *revoked || checked_at.elapsed() < self.ttl);
+                if checked.len() >= CACHE_CAPACITY {
+                    checked.clear();
+                }
+            }
+            checked.insert(jti.to_string(), (revoked, Instant::now()));
+        }
+        Ok(revoked)
+    }
+}
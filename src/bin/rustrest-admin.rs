@@ -0,0 +1,75 @@
+use anyhow::Context;
+use clap::Parser;
+use dotenvy::dotenv;
+use serde_json::Value;
+use sqlx::postgres::PgPoolOptions;
+use std::env;
+use std::io::BufRead;
+
+use rustrest::admin::{self, AdminCommand};
+use rustrest::auth::JwtAuth;
+
+/// Administrative tasks for users, roles and signing keys
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Print the result as JSON, for scripting
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: AdminCommand,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+
+    let mut cli = Cli::parse();
+
+    // Keep passwords out of the shell history and process list
+    if let Some(password @ None) = cli.command.password_mut() {
+        eprintln!("Password:");
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        *password = Some(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+
+    let url = env::var("DATABASE_URL").context("DATABASE_URL must be set")?;
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&url)
+        .await?;
+
+    let jwt_secret = env::var("JWT_SECRET").context("JWT_SECRET must be set")?;
+    let jwt_auth = JwtAuth::new(jwt_secret.as_bytes());
+    jwt_auth.reload_keys(&pool).await?;
+
+    let result = admin::execute(cli.command, &pool, &jwt_auth).await;
+    pool.close().await;
+
+    match result {
+        Ok(output) if cli.json => println!("{}", output),
+        Ok(output) => print_human(&output),
+        Err(e) if cli.json => {
+            println!("{}", serde_json::json!({ "error": e.to_string() }));
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+fn print_human(output: &Value) {
+    match output.as_object() {
+        Some(fields) => {
+            for (key, value) in fields {
+                match value {
+                    Value::String(s) => println!("{}: {}", key, s),
+                    other => println!("{}: {}", key, other),
+                }
+            }
+        }
+        None => println!("{}", output),
+    }
+}
@@ -0,0 +1,204 @@
+use futures_core::future::BoxFuture;
+use futures_core::stream::{BoxStream, Stream};
+use serde::Serialize;
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::postgres::{PgQueryResult, PgRow, PgStatement, PgTypeInfo};
+use sqlx::{Describe, Either, Execute, Executor, Pool, Postgres};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tracing::{Instrument, Span};
+
+// Migrations from ./migrations, embedded in the binary at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+// How the schema in the database relates to the migrations this binary knows about
+#[derive(Debug, Default, Serialize)]
+pub struct MigrationState {
+    pub applied: Vec<i64>,
+    // Embedded but not yet applied
+    pub pending: Vec<i64>,
+    // Applied, but unknown to this binary: the schema is ahead of it
+    pub unknown: Vec<i64>,
+    // Applied with a checksum that differs from the embedded migration
+    pub modified: Vec<i64>,
+}
+
+impl MigrationState {
+    pub fn is_current(&self) -> bool {
+        self.pending.is_empty() && self.unknown.is_empty() && self.modified.is_empty()
+    }
+}
+
+// Compares the `_sqlx_migrations` bookkeeping table with the embedded migrations.
+// Read-only: a database that was never migrated simply reports everything as pending.
+pub async fn migration_state(pool: &Pool<Postgres>) -> Result<MigrationState, sqlx::Error> {
+    let applied: Vec<(i64, Vec<u8>)> = match sqlx::query_as(
+        "SELECT version, checksum FROM _sqlx_migrations WHERE success ORDER BY version",
+    )
+    .fetch_all(traced(pool))
+    .await
+    {
+        Ok(rows) => rows,
+        // undefined_table: migrations have never been run
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("42P01") => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    let embedded: Vec<_> = MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .collect();
+
+    let mut state = MigrationState::default();
+    for (version, checksum) in &applied {
+        state.applied.push(*version);
+        match embedded.iter().find(|m| m.version == *version) {
+            Some(m) if m.checksum.as_ref() != checksum.as_slice() => state.modified.push(*version),
+            Some(_) => {}
+            None => state.unknown.push(*version),
+        }
+    }
+    state.pending = embedded
+        .iter()
+        .map(|m| m.version)
+        .filter(|v| !state.applied.contains(v))
+        .collect();
+
+    Ok(state)
+}
+
+// Applies all pending migrations. Safe with several replicas starting at
+// once, sqlx serializes migration runs with an advisory lock.
+pub async fn run_migrations(pool: &Pool<Postgres>) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+// Reverts applied migrations down to (not including) `target`.
+// Without a target only the most recent migration is reverted.
+pub async fn revert_migrations(pool: &Pool<Postgres>, target: Option<i64>) -> anyhow::Result<Vec<i64>> {
+    let state = migration_state(pool).await?;
+    let target = match target {
+        Some(target) => target,
+        None => match state.applied.as_slice() {
+            [] => return Ok(Vec::new()),
+            [.., previous, _] => *previous,
+            [_] => 0,
+        },
+    };
+    if let Some(version) = state.unknown.iter().find(|v| **v > target) {
+        anyhow::bail!("cannot revert migration {}, it is not known to this binary", version);
+    }
+
+    MIGRATOR.undo(pool, target).await?;
+    Ok(state.applied.into_iter().filter(|v| *v > target).collect())
+}
+
+// Refuses to run against a schema that is newer than this binary
+pub async fn ensure_compatible(pool: &Pool<Postgres>) -> anyhow::Result<MigrationState> {
+    let state = migration_state(pool).await?;
+    if !state.unknown.is_empty() {
+        anyhow::bail!(
+            "database schema is ahead of this binary, unknown migrations applied: {:?}",
+            state.unknown
+        );
+    }
+    if !state.modified.is_empty() {
+        anyhow::bail!(
+            "applied migrations differ from the ones embedded in this binary: {:?}",
+            state.modified
+        );
+    }
+    Ok(state)
+}
+
+// Span for a single query, exported to OpenTelemetry as a client span
+pub fn query_span(statement: &str) -> Span {
+    let operation = statement
+        .split_whitespace()
+        .next()
+        .unwrap_or("QUERY")
+        .to_uppercase();
+    tracing::info_span!(
+        "db.query",
+        otel.name = %operation,
+        otel.kind = "client",
+        db.system = "postgresql",
+        db.statement = statement,
+    )
+}
+
+// Executor that runs every query inside its own `query_span`. Wraps a pool,
+// connection or transaction: use `traced(pool)` or `traced(&mut *tx)` wherever
+// a query would otherwise take the executor directly.
+#[derive(Debug)]
+pub struct Traced<E>(E);
+
+pub fn traced<'c, E: Executor<'c, Database = Postgres>>(executor: E) -> Traced<E> {
+    Traced(executor)
+}
+
+// Keeps the span entered while the result stream is polled, and open until it is dropped
+struct InstrumentedStream<'e, T> {
+    inner: BoxStream<'e, T>,
+    span: Span,
+}
+
+impl<T> Stream for InstrumentedStream<'_, T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let this = &mut *self;
+        let _enter = this.span.enter();
+        this.inner.as_mut().poll_next(cx)
+    }
+}
+
+impl<'c, X> Executor<'c> for Traced<X>
+where
+    X: Executor<'c, Database = Postgres>,
+{
+    type Database = Postgres;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<PgQueryResult, PgRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + Execute<'q, Postgres>,
+    {
+        let span = query_span(query.sql());
+        let inner = span.in_scope(|| self.0.fetch_many(query));
+        Box::pin(InstrumentedStream { inner, span })
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<PgRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + Execute<'q, Postgres>,
+    {
+        let span = query_span(query.sql());
+        Box::pin(self.0.fetch_optional(query).instrument(span))
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [PgTypeInfo],
+    ) -> BoxFuture<'e, Result<PgStatement<'q>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        self.0.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<Postgres>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        self.0.describe(sql)
+    }
+}
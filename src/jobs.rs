@@ -0,0 +1,132 @@
+use anyhow::Context;
+use metrics::counter;
+use serde_json::json;
+use sqlx::{PgConnection, Pool, Postgres};
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::audit::{AuditEvent, Auditor};
+use crate::models::job::Job;
+use crate::models::post::{Post, PostTransition};
+use crate::services::error::AppError;
+
+// Job kinds, with `{"post_id": ...}` as payload
+pub const PUBLISH_POST: &str = "post.publish";
+pub const UNPUBLISH_POST: &str = "post.unpublish";
+
+// Actor of the audit events recorded by jobs
+pub const SCHEDULER: &str = "scheduler";
+
+// A job still running after this long is assumed lost with its worker
+const STALE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+// Delay before the next attempt of a failed job: 30s, 1m, 2m, ... up to an hour
+pub fn backoff(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 7) as u32;
+    Duration::from_secs(30 * 2u64.pow(exponent)).min(Duration::from_secs(60 * 60))
+}
+
+// Enqueues the next scheduled step of the post, if it has one, on the
+// transaction that changed the post. Jobs for outdated times are harmless,
+// they find nothing to do.
+pub async fn schedule_post(post: &Post, conn: &mut PgConnection) -> Result<(), AppError> {
+    let Some((transition, at)) = post.next_scheduled() else {
+        return Ok(());
+    };
+    let kind = match transition {
+        PostTransition::Publish => PUBLISH_POST,
+        _ => UNPUBLISH_POST,
+    };
+    Job::enqueue(kind, json!({ "post_id": post.id }), at, conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+// Claims and runs due jobs. Every replica runs one; the claim query makes sure
+// each job is picked up by a single worker.
+#[derive(Clone)]
+pub struct JobRunner {
+    pool: Pool<Postgres>,
+    worker: String,
+}
+
+impl JobRunner {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool, worker: format!("worker-{}", Uuid::new_v4()) }
+    }
+
+    // Runs jobs until none are due, returns how many ran
+    pub async fn run_due(&self) -> usize {
+        let mut ran = 0;
+        loop {
+            let job = match Job::claim(&self.worker, STALE_AFTER, &self.pool).await {
+                Ok(Some(job)) => job,
+                Ok(None) => return ran,
+                Err(e) => {
+                    error!("Failed to claim a job: {}", e);
+                    return ran;
+                }
+            };
+            ran += 1;
+
+            let result = match self.execute(&job).await {
+                Ok(()) => {
+                    counter!("jobs_total", "kind" => job.kind.clone(), "outcome" => "success").increment(1);
+                    job.succeed(&self.pool).await
+                }
+                Err(e) => {
+                    let error = format!("{:#}", e);
+                    counter!("jobs_total", "kind" => job.kind.clone(), "outcome" => "failure").increment(1);
+                    if job.attempts >= job.max_attempts {
+                        error!(job_id = job.id, kind = %job.kind, attempts = job.attempts, "Job failed, giving up: {}", error);
+                    } else {
+                        warn!(job_id = job.id, kind = %job.kind, attempts = job.attempts, "Job failed, will retry: {}", error);
+                    }
+                    job.fail(&error, backoff(job.attempts), &self.pool).await
+                }
+            };
+            if let Err(e) = result {
+                error!(job_id = job.id, "Failed to store the job result: {}", e);
+            }
+        }
+    }
+
+    async fn execute(&self, job: &Job) -> anyhow::Result<()> {
+        match job.kind.as_str() {
+            PUBLISH_POST | UNPUBLISH_POST => {
+                let post_id = job.payload["post_id"].as_i64().and_then(|id| i32::try_from(id).ok());
+                let post_id = post_id.context("payload has no post_id")?;
+                let (transition, action) = match job.kind.as_str() {
+                    PUBLISH_POST => (PostTransition::Publish, "post.published"),
+                    _ => (PostTransition::Archive, "post.unpublished"),
+                };
+
+                if Post::run_scheduled(post_id, transition, &self.pool).await?.is_none() {
+                    return Ok(());
+                }
+                info!(job_id = job.id, post_id, "Scheduled {} done", action);
+                Auditor::system(self.pool.clone(), SCHEDULER)
+                    .record(AuditEvent::new(action).resource(format!("post:{}", post_id)).detail("job_id", job.id))
+                    .await;
+                Ok(())
+            }
+            kind => anyhow::bail!("unknown job kind: {}", kind),
+        }
+    }
+}
+
+// Polls for due jobs until shutdown
+pub async fn run(runner: JobRunner, interval: Duration, shutdown: impl Future<Output = ()>) {
+    tokio::pin!(shutdown);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = ticker.tick() => {
+                runner.run_due().await;
+            }
+        }
+    }
+}
@@ -1,7 +1,22 @@
+pub mod admin;
+pub mod audit;
+pub mod app;
+pub mod config;
+pub mod db;
+pub mod jobs;
+pub mod markdown;
 pub mod models;
 pub mod services;
 pub mod auth;
 pub mod middleware;
+pub mod redact;
+pub mod shutdown;
+pub mod slug;
+pub mod telemetry;
+pub mod tls;
+
+pub use app::{app, AppState};
+pub use config::Config;
 
 // Ensure models are accessible
 pub use models::post::Post;
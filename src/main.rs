@@ -1,68 +1,212 @@
-use axum::routing::{get, post};
-use axum::{middleware, Extension, Router};
+use anyhow::Context;
+use clap::{Parser, Subcommand};
 use dotenvy::dotenv;
-use rustrest::services::posts::{get_post, get_posts};
 use sqlx::postgres::PgPoolOptions;
+use sqlx::{Pool, Postgres};
 use std::env;
+use std::future::IntoFuture;
 use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::Arc;
 use tokio::net::TcpListener;
-use tower_http::trace::TraceLayer;
-use tracing::Level;
+use tracing::{error, info, warn};
 
-use rustrest::auth;
-use rustrest::auth::jwt::{JwtAuth};
-use rustrest::middleware::{audit_log, auth_middleware, security_headers};
+use rustrest::db;
+use rustrest::services::metrics;
+use rustrest::shutdown::shutdown_signal;
+use rustrest::telemetry;
+use rustrest::tls::{self, ServerTls, TlsListener};
+use rustrest::config::LogConfig;
+use rustrest::{app, AppState, Config};
+
+#[derive(Parser)]
+#[command(version, about = "Blog REST API")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the API server (the default)
+    Serve,
+    /// Manage the database schema with the embedded migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply all pending migrations
+    Up,
+    /// List applied and pending migrations
+    Status,
+    /// Revert the latest migration, or every migration after --target
+    Revert {
+        #[arg(long)]
+        target: Option<i64>,
+    },
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
-        .init();
-
     dotenv().ok();
 
-    let url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let max_connections = env::var("MAX_DB_CONNECTIONS")
-        .expect("MAX_DB_CONNECTIONS must be set")
-        .parse()?;
+    match Cli::parse().command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            let config = Config::from_env()?;
+            let telemetry = telemetry::init(&config.log, config.otel.as_ref())?;
+            let result = serve(config).await;
+            telemetry.shutdown();
+            result
+        }
+        Command::Migrate { action } => {
+            let telemetry = telemetry::init(&LogConfig::from_env()?, None)?;
+            let result = migrate(action).await;
+            telemetry.shutdown();
+            result
+        }
+    }
+}
+
+async fn serve(config: Config) -> anyhow::Result<()> {
     let pool = PgPoolOptions::new()
-        .max_connections(max_connections)
-        .connect(&url)
+        .max_connections(config.max_db_connections)
+        .connect(&config.database_url)
         .await?;
 
-    // JWT Authentication
-    let jwt_secret = env::var("JWT_SECRET")
-        .expect("JWT_SECRET must be set")
-        .into_bytes();
-    let jwt_auth = Arc::new(JwtAuth::new(&jwt_secret));
-
-    // API routes
-    let api_routes = Router::new()
-        // Public routes
-        .route("/login", post(auth::login))
-        .route("/register", post(auth::register))
-
-        // Protected routes
-        .route("/posts", get(get_posts))
-        .route("/posts/{id}", get(get_post))
-
-        // Apply authentication middleware to all routes
-        .layer(middleware::from_fn_with_state(Arc::clone(&jwt_auth), auth_middleware))
-        .layer(middleware::from_fn(audit_log))
-        .layer(middleware::from_fn(security_headers)) // Security headers
-        .layer(TraceLayer::new_for_http()) // Request tracing
-        .layer(Extension(Arc::clone(&jwt_auth))) // JWT auth
-        .layer(Extension(pool))
-        .with_state(jwt_auth).into_make_service_with_connect_info::<SocketAddr>();
-
-    let bind_host = env::var("BIND_HOST").expect("BIND_HOST must be set");
+    if config.run_migrations {
+        db::run_migrations(&pool).await?;
+    }
+    let migrations = db::ensure_compatible(&pool).await?;
+    if !migrations.pending.is_empty() {
+        warn!(pending = ?migrations.pending, "Database has pending migrations, run `migrate up` or set RUN_MIGRATIONS=true");
+    }
+
+    let bind_host = config.bind_host.clone();
+    let shutdown_timeout = config.shutdown_timeout;
+    let pre_stop_delay = config.shutdown_pre_stop_delay;
+    let tls_config = config.tls.clone();
+    let recorder = metrics::install_recorder()?;
+    let state = AppState::new(pool.clone(), config, recorder);
+    state.jwt_auth.reload_keys(&pool).await?;
+    state.spawn_background_tasks();
+    let lifecycle = state.lifecycle.clone();
+    let router = app(state);
+
     let addr: SocketAddr = bind_host.parse()?;
     let listener = TcpListener::bind(addr).await?;
 
-    println!("Server is running on {}", bind_host);
+    // Flip to draining on SIGINT/SIGTERM; axum stops accepting connections once
+    // the pre-stop delay is over, or right away on a second signal
+    tokio::spawn({
+        let lifecycle = lifecycle.clone();
+        async move {
+            shutdown_signal().await;
+            tokio::select! {
+                _ = lifecycle.shutdown_after(pre_stop_delay) => {}
+                _ = shutdown_signal() => lifecycle.begin_shutdown(),
+            }
+        }
+    });
+
+    let server: Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>> = match tls_config {
+        Some(tls_config) => {
+            if let Some(redirect_bind) = &tls_config.redirect_bind {
+                let redirect_listener = TcpListener::bind(redirect_bind.parse::<SocketAddr>()?).await?;
+                println!("Redirecting HTTP on {} to HTTPS", redirect_bind);
+                let redirect = axum::serve(redirect_listener, tls::redirect_app(addr.port()))
+                    .with_graceful_shutdown(lifecycle.shutdown_requested());
+                lifecycle.spawn(async move {
+                    if let Err(e) = redirect.await {
+                        error!("HTTP redirect listener failed: {}", e);
+                    }
+                });
+            }
+
+            let server_tls = ServerTls::load(tls_config)?;
+            lifecycle.spawn(tls::watch(Arc::clone(&server_tls), lifecycle.shutdown_requested()));
+            println!("Server is running on https://{}", bind_host);
+            let listener = TlsListener::new(listener, server_tls)?;
+            Box::pin(
+                axum::serve(listener, tls::make_service(router))
+                    .with_graceful_shutdown(lifecycle.shutdown_requested())
+                    .into_future(),
+            )
+        }
+        None => {
+            println!("Server is running on {}", bind_host);
+            Box::pin(
+                axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
+                    .with_graceful_shutdown(lifecycle.shutdown_requested())
+                    .into_future(),
+            )
+        }
+    };
+
+    // In-flight requests get `shutdown_timeout` to complete once draining starts
+    let drain_deadline = {
+        let shutdown_requested = lifecycle.shutdown_requested();
+        async move {
+            shutdown_requested.await;
+            tokio::time::sleep(shutdown_timeout).await;
+        }
+    };
+
+    tokio::select! {
+        result = server => result?,
+        _ = drain_deadline => warn!("Shutdown timeout elapsed, dropping remaining connections"),
+    }
+
+    lifecycle.wait_for_tasks(shutdown_timeout).await;
+    pool.close().await;
+    info!("Shutdown complete");
+
+    Ok(())
+}
+
+async fn migrate(action: MigrateAction) -> anyhow::Result<()> {
+    // Only the database is needed here, not the full server configuration
+    let url = env::var("DATABASE_URL").context("DATABASE_URL must be set")?;
+    let pool: Pool<Postgres> = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&url)
+        .await?;
 
-    axum::serve(listener, api_routes).await?;
+    match action {
+        MigrateAction::Up => {
+            db::run_migrations(&pool).await?;
+            println!("Database is up to date");
+        }
+        MigrateAction::Status => {
+            let state = db::migration_state(&pool).await?;
+            for migration in db::MIGRATOR.iter().filter(|m| !m.migration_type.is_down_migration()) {
+                let status = if state.modified.contains(&migration.version) {
+                    "modified"
+                } else if state.applied.contains(&migration.version) {
+                    "applied"
+                } else {
+                    "pending"
+                };
+                println!("{:<16}{:<10}{}", migration.version, status, migration.description);
+            }
+            for version in &state.unknown {
+                println!("{:<16}{:<10}(not known to this binary)", version, "unknown");
+            }
+        }
+        MigrateAction::Revert { target } => {
+            let reverted = db::revert_migrations(&pool, target).await?;
+            if reverted.is_empty() {
+                println!("Nothing to revert");
+            }
+            for version in reverted {
+                println!("Reverted {}", version);
+            }
+        }
+    }
 
+    pool.close().await;
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,85 @@
+use ammonia::Builder;
+use pulldown_cmark::{html, Event, Options, Parser, TagEnd};
+use std::sync::OnceLock;
+
+// Characters kept for the excerpt, cut back to a whole word
+const EXCERPT_LEN: usize = 200;
+const WORDS_PER_MINUTE: usize = 200;
+// Stored with each rendering. Bump it when the options or the allow-list
+// change, then `rustrest-admin rerender-posts` brings old posts up to date.
+pub const RENDERER_VERSION: i32 = 1;
+
+// What a post body renders to. Bodies are CommonMark with the GitHub
+// extensions for tables, strikethrough and task lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rendered {
+    // Safe to embed as is: only allow-listed tags and attributes survive,
+    // so no scripts, event handlers or `javascript:` links
+    pub html: String,
+    // The start of the text without any markup
+    pub excerpt: String,
+    pub reading_minutes: i32,
+}
+
+fn options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS | Options::ENABLE_GFM
+}
+
+// Ammonia's allow-list plus the checkboxes of task lists
+fn sanitizer() -> &'static Builder<'static> {
+    static SANITIZER: OnceLock<Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = Builder::default();
+        builder
+            .add_tags(["input"])
+            .add_tag_attributes("input", ["checked", "disabled"])
+            .add_tag_attribute_values("input", "type", ["checkbox"]);
+        builder
+    })
+}
+
+pub fn render(markdown: &str) -> Rendered {
+    let mut unsafe_html = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options()));
+
+    let text = plain_text(markdown);
+    let words = text.split_whitespace().count();
+    Rendered {
+        html: sanitizer().clean(&unsafe_html).to_string(),
+        excerpt: excerpt(&text),
+        reading_minutes: words.div_ceil(WORDS_PER_MINUTE).max(1) as i32,
+    }
+}
+
+// The text a reader sees, blocks separated by spaces; raw HTML is dropped
+fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(markdown, options()) {
+        match event {
+            Event::Text(part) | Event::Code(part) => text.push_str(&part),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::BlockQuote(_)
+                | TagEnd::CodeBlock
+                | TagEnd::Item
+                | TagEnd::TableCell,
+            ) => text.push(' '),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn excerpt(text: &str) -> String {
+    if text.chars().count() <= EXCERPT_LEN {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(EXCERPT_LEN).collect();
+    let cut = match cut.rfind(' ') {
+        Some(space) => &cut[..space],
+        None => &cut,
+    };
+    format!("{}…", cut.trim_end_matches(|c: char| c.is_ascii_punctuation()))
+}
@@ -1,54 +1,52 @@
 use axum::{
-    extract::{ConnectInfo, Request},
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    Extension,
     middleware::Next,
     response::Response,
 };
+use http::StatusCode;
+use serde_json::json;
+use std::sync::Arc;
 use std::time::Instant;
-use tracing::{info, warn};
-use uuid::Uuid;
+use tracing::warn;
 
-// Audit logging middleware for security-relevant events
+use crate::audit::{AuditScope, DenialLog, ANONYMOUS};
+use crate::middleware::RequestId;
+use crate::redact::redactor;
+use crate::models::audit_record::{NewAuditRecord, OUTCOME_FAILURE};
+
+// Request-level audit trail. Domain actions are recorded by the handlers
+// themselves (see `crate::audit`); this logs failed requests and queues
+// rejections (401/403) that no handler recorded as `access.denied`.
 pub async fn audit_log(
+    State(denials): State<Arc<DenialLog>>,
     ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
-    request: Request,
+    Extension(request_id): Extension<RequestId>,
+    mut request: Request,
     next: Next,
 ) -> Response {
     let start = Instant::now();
     let method = request.method().clone();
     let uri = request.uri().clone();
-    let request_id = Uuid::new_v4();
-    
-    // Extract user information if available
-    let user_id = request
+    let route = request
         .extensions()
-        .get::<crate::auth::jwt::Claims>()
-        .map(|claims| claims.sub.clone())
-        .unwrap_or_else(|| "anonymous".to_string());
-    
-    // Extract sensitive paths to log with more detail
-    let is_sensitive_path = uri.path().contains("/login") || 
-                           uri.path().contains("/register") || 
-                           uri.path().contains("/password");
-                           
-    if is_sensitive_path {
-        info!(
-            target: "AUDIT",
-            request_id = %request_id,
-            remote_addr = %addr,
-            method = %method,
-            uri = %uri,
-            user_id = %user_id,
-            "Sensitive operation initiated"
-        );
-    }
-    
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    // Filled in by auth_middleware and the handler
+    let scope = AuditScope::default();
+    request.extensions_mut().insert(scope.clone());
+
     // Process the request
     let response = next.run(request).await;
-    
+
     // Get response status for the log
     let status = response.status();
     let duration = start.elapsed();
-    
+    let user_id = scope.actor().unwrap_or(ANONYMOUS);
+    let redactor = redactor();
+
     // Log authentication failures and other suspicious activity
     if status.is_client_error() || status.is_server_error() {
         warn!(
@@ -56,25 +54,27 @@ pub async fn audit_log(
             request_id = %request_id,
             remote_addr = %addr,
             method = %method,
-            uri = %uri,
-            user_id = %user_id,
+            uri = %redactor.text(&uri.to_string()),
+            user_id = %redactor.user(user_id),
             status = %status.as_u16(),
             duration_ms = %duration.as_millis(),
             "Request failed"
         );
-    } else if is_sensitive_path {
-        info!(
-            target: "AUDIT",
-            request_id = %request_id,
-            remote_addr = %addr,
-            method = %method,
-            uri = %uri,
-            user_id = %user_id,
-            status = %status.as_u16(),
-            duration_ms = %duration.as_millis(),
-            "Sensitive operation completed"
-        );
     }
-    
+
+    let denied = status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN;
+    if denied && !scope.is_recorded() {
+        let event = NewAuditRecord {
+            actor: user_id.to_string(),
+            ip: Some(addr.ip().to_string()),
+            action: "access.denied".to_string(),
+            resource: Some(format!("{} {}", method, route)),
+            outcome: OUTCOME_FAILURE,
+            request_id: Some(request_id.to_string()),
+            details: json!({ "status": status.as_u16() }),
+        };
+        denials.record(event);
+    }
+
     response
-}
\ No newline at end of file
+}
@@ -2,19 +2,27 @@ use std::sync::Arc;
 use axum::extract::{Request, State};
 use axum::middleware::Next;
 use axum::response::Response;
-use http::{header, StatusCode};
-use tracing::{error, info};
+use http::header;
+use chrono::Duration;
+use tracing::{error, info, warn};
+use metrics::counter;
+use sqlx::{Pool, Postgres};
+use crate::audit::AuditScope;
+use crate::auth::revocation::RevocationCache;
+use crate::redact::redactor;
 use crate::auth::JwtAuth;
+use crate::auth::jwt::{Claims, TOKEN_LIFETIME_MINUTES};
+use crate::models::user::User;
+use crate::tls::{ClientCertificate, TlsSession};
+use crate::services::error::AppError;
 
 pub async fn auth_middleware(
     State(jwt_auth): State<Arc<JwtAuth>>,
+    State(revocations): State<Arc<RevocationCache>>,
+    State(pool): State<Pool<Postgres>>,
     mut request: Request,
     next: Next
-) -> Response {
-    if request.uri().path() == "/login" || request.uri().path() == "/register" {
-        return next.run(request).await;
-    }
-
+) -> Result<Response, AppError> {
     let auth_header = request
         .headers()
         .get(header::AUTHORIZATION)
@@ -27,24 +35,74 @@ pub async fn auth_middleware(
 
             match jwt_auth.verify_token(token) {
                 Ok(claims) => {
-                    info!("Authentication successful for user: {}", claims.sub);
+                    match revocations.is_revoked(&claims.jti, &pool).await {
+                        Ok(false) => {}
+                        Ok(true) => {
+                            counter!("auth_token_verification_failures_total", "reason" => "revoked").increment(1);
+                            return Err(AppError::Unauthorized("Token has been revoked".to_string()));
+                        }
+                        Err(e) => {
+                            error!("Revocation check failed: {:?}", e);
+                            return Err(AppError::InternalServerError);
+                        }
+                    }
+
+                    info!("Authentication successful for user: {}", redactor().user(&claims.sub));
+                    if let Some(scope) = request.extensions().get::<AuditScope>() {
+                        scope.set_actor(&claims.sub);
+                    }
                     request.extensions_mut().insert(claims);
-                    next.run(request).await
+                    Ok(next.run(request).await)
                 }
                 Err(e) => {
                     error!("Token verification failed: {:?}", e);
-                    Response::builder()
-                        .status(StatusCode::UNAUTHORIZED)
-                        .body("Invalid token".into())
-                        .unwrap()
+                    Err(AppError::Unauthorized("Invalid token".to_string()))
                 }
             }
         }
         _ => {
-            Response::builder()
-                .status(StatusCode::UNAUTHORIZED)
-                .body("Missing or invalid Authorization header".into())
-                .unwrap()
+            let certificate = request
+                .extensions()
+                .get::<TlsSession>()
+                .and_then(|session| session.client_certificate.clone());
+            let Some(certificate) = certificate else {
+                return Err(AppError::Unauthorized("Missing or invalid Authorization header".to_string()));
+            };
+
+            match certificate_claims(&certificate, &pool).await {
+                Ok(Some(claims)) => {
+                    info!("Client certificate authentication successful for user: {}", redactor().user(&claims.sub));
+                    if let Some(scope) = request.extensions().get::<AuditScope>() {
+                        scope.set_actor(&claims.sub);
+                    }
+                    request.extensions_mut().insert(claims);
+                    Ok(next.run(request).await)
+                }
+                Ok(None) => {
+                    counter!("auth_token_verification_failures_total", "reason" => "unknown_certificate").increment(1);
+                    warn!(subject = %certificate.subject, "Client certificate does not belong to a user");
+                    Err(AppError::Unauthorized("Unknown client certificate".to_string()))
+                }
+                Err(e) => {
+                    error!("Client certificate lookup failed: {:?}", e);
+                    Err(AppError::InternalServerError)
+                }
+            }
         }
     }
 }
+
+// mTLS: the certificate was verified during the handshake, its common name is
+// the username. Requests signed in this way get the user's current roles.
+async fn certificate_claims(certificate: &ClientCertificate, pool: &Pool<Postgres>) -> Result<Option<Claims>, AppError> {
+    let Some(username) = &certificate.common_name else {
+        return Ok(None);
+    };
+    let user = match User::find_by_username(username, pool).await {
+        Ok(user) => user,
+        Err(AppError::NotFound(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let roles = user.roles(pool).await?;
+    Ok(Some(Claims::new(user.id.to_string(), roles, Duration::minutes(TOKEN_LIFETIME_MINUTES))))
+}
@@ -0,0 +1,77 @@
+use http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::warn;
+
+use crate::config::CorsConfig;
+use crate::middleware::request_id::REQUEST_ID_HEADER;
+
+// Answers preflight requests itself and adds the CORS response headers. It has
+// to sit outside of authentication, preflights never carry a token.
+pub fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    let allow_origin = if config.allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        let patterns: Vec<OriginPattern> = config.allowed_origins.iter().map(|origin| OriginPattern::new(origin)).collect();
+        AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| patterns.iter().any(|pattern| pattern.matches(origin)))
+        })
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(parse::<Method>(&config.allowed_methods, "method"))
+        .allow_headers(parse::<HeaderName>(&config.allowed_headers, "header"))
+        .allow_credentials(config.allow_credentials)
+        .expose_headers([REQUEST_ID_HEADER, http::header::ETAG])
+        .max_age(config.max_age)
+}
+
+fn parse<T: std::str::FromStr>(values: &[String], kind: &str) -> Vec<T> {
+    values
+        .iter()
+        .filter_map(|value| {
+            let parsed = value.parse().ok();
+            if parsed.is_none() {
+                warn!(value = %value, "Ignoring invalid CORS {}", kind);
+            }
+            parsed
+        })
+        .collect()
+}
+
+// An allowed origin: exact, or `scheme://*.domain[:port]` for any subdomain of
+// `domain` (but not `domain` itself)
+enum OriginPattern {
+    Exact(String),
+    Subdomains { scheme: String, suffix: String },
+}
+
+impl OriginPattern {
+    fn new(origin: &str) -> Self {
+        let origin = origin.trim_end_matches('/').to_lowercase();
+        match origin.split_once("://*.") {
+            Some((scheme, domain)) => OriginPattern::Subdomains {
+                scheme: format!("{}://", scheme),
+                suffix: format!(".{}", domain),
+            },
+            None => OriginPattern::Exact(origin),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_lowercase();
+        match self {
+            OriginPattern::Exact(allowed) => origin == *allowed,
+            OriginPattern::Subdomains { scheme, suffix } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|host| host.strip_suffix(suffix.as_str()))
+                .is_some_and(|subdomain| {
+                    !subdomain.is_empty()
+                        && !subdomain.starts_with('.')
+                        && subdomain.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+                }),
+        }
+    }
+}
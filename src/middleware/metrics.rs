@@ -0,0 +1,48 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use metrics::{counter, gauge, histogram};
+use std::time::Instant;
+
+// Counts a request as in flight until dropped, which also happens when the
+// client goes away and the request future is dropped mid-way
+struct InFlight;
+
+impl InFlight {
+    fn start() -> Self {
+        gauge!("http_requests_in_flight").increment(1);
+        InFlight
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        gauge!("http_requests_in_flight").decrement(1);
+    }
+}
+
+// Request metrics, labelled by the route template (e.g. "/posts/{id}") rather
+// than the raw path so the number of series stays bounded
+pub async fn track_metrics(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let in_flight = InFlight::start();
+    let response = next.run(request).await;
+    drop(in_flight);
+
+    let status_class = format!("{}xx", response.status().as_u16() / 100);
+    counter!("http_requests_total", "method" => method.clone(), "route" => route.clone(), "status" => status_class)
+        .increment(1);
+    histogram!("http_request_duration_seconds", "method" => method, "route" => route)
+        .record(start.elapsed().as_secs_f64());
+
+    response
+}
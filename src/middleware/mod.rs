@@ -1,7 +1,13 @@
+pub mod cors;
+pub mod rate_limit;
+pub mod request_id;
 pub mod security_headers;
 mod audit;
 mod auth_middleware;
+mod metrics;
 
+pub use request_id::{request_id, RequestId};
 pub use security_headers::security_headers;
 pub use audit::audit_log;
-pub use auth_middleware::auth_middleware;
\ No newline at end of file
+pub use auth_middleware::auth_middleware;
+pub use metrics::track_metrics;
\ No newline at end of file
@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Entries are pruned once this many clients are tracked
+const MAX_TRACKED: usize = 10_000;
+
+// Fixed-window limit per client address, kept in memory; good enough for
+// throttling noisy endpoints on a single instance
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    clients: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self { limit, window, clients: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn per_minute(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+
+    // Counts a request, returns false once the client is over the limit
+    pub fn check(&self, client: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() >= MAX_TRACKED {
+            clients.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+        }
+        let (started, count) = clients.entry(client).or_insert((now, 0));
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= self.limit
+    }
+}
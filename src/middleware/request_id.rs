@@ -0,0 +1,68 @@
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::Response,
+};
+use http::{HeaderName, HeaderValue};
+use std::fmt;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Incoming ids longer than this, or with unexpected characters, are replaced
+const MAX_LENGTH: usize = 128;
+
+// Identifies one request across log lines, spans, error bodies and audit records
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    // Accepts the caller's id when it is safe to echo and log, else generates one
+    fn from_header(value: Option<&HeaderValue>) -> Self {
+        value
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid(id))
+            .map(|id| RequestId(id.to_string()))
+            .unwrap_or_else(|| RequestId(Uuid::new_v4().to_string()))
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_LENGTH
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+}
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+// Id of the request being handled by the current task, for code that has no access to the request
+pub fn current() -> Option<RequestId> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
+
+// Takes the id from `X-Request-Id` or generates one, stores it in the request
+// extensions and echoes it in the response. Must run outside the trace layer so
+// the request span can record it.
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let id = RequestId::from_header(request.headers().get(&REQUEST_ID_HEADER));
+    request.extensions_mut().insert(id.clone());
+
+    let mut response = CURRENT.scope(id.clone(), next.run(request)).await;
+
+    if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
@@ -1,57 +1,99 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
 use axum::{
-    extract::Request,
+    extract::{FromRequestParts, Request, State},
+    http::request::Parts,
     middleware::Next,
-    response::IntoResponse,
+    response::Response,
 };
-use http::{HeaderMap, HeaderName, HeaderValue};
+use http::{HeaderName, HeaderValue};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::config::SecurityHeadersConfig;
+
+// Headers for one route group, parsed once from the configuration
+#[derive(Debug)]
+pub struct HeaderPolicy {
+    headers: Vec<(HeaderName, String)>,
+}
+
+impl HeaderPolicy {
+    pub fn for_group(config: &SecurityHeadersConfig, group: &str) -> Arc<Self> {
+        let headers = config
+            .group(group)
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let valid = HeaderValue::try_from(value.replace("{nonce}", "0")).is_ok();
+                match HeaderName::try_from(name.as_str()) {
+                    Ok(name) if valid => Some((name, value)),
+                    _ => {
+                        warn!(header = %name, "Ignoring invalid security header");
+                        None
+                    }
+                }
+            })
+            .collect();
+        Arc::new(Self { headers })
+    }
+}
+
+// Random per-request value for `'nonce-…'` sources in the Content-Security-Policy.
+// Handlers that render inline scripts or styles extract it and put it in the
+// element's `nonce` attribute.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    fn generate() -> Self {
+        let mut bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut bytes);
+        CspNonce(hex::encode(bytes))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for CspNonce {
+    type Rejection = Infallible;
+
+    // Outside of `security_headers` no policy refers to the nonce, any value will do
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<CspNonce>().cloned().unwrap_or_else(CspNonce::generate))
+    }
+}
+
+// Marks a response whose security headers were set by an inner route group
+#[derive(Clone)]
+struct HeadersApplied;
+
+// Sets the security headers of a route group. Layer it on the group's router
+// and once more, with the default policy, around the whole app for responses
+// that match no route; the innermost policy wins.
+pub async fn security_headers(State(policy): State<Arc<HeaderPolicy>>, mut request: Request, next: Next) -> Response {
+    let nonce = match request.extensions().get::<CspNonce>() {
+        Some(nonce) => nonce.clone(),
+        None => {
+            let nonce = CspNonce::generate();
+            request.extensions_mut().insert(nonce.clone());
+            nonce
+        }
+    };
 
-// Enhanced security headers
-pub async fn security_headers(request: Request, next: Next) -> impl IntoResponse {
     let mut response = next.run(request).await;
-    
+    if response.extensions().get::<HeadersApplied>().is_some() {
+        return response;
+    }
+
     let headers = response.headers_mut();
-    
-    // Prevent MIME type sniffing
-    headers.insert(
-        HeaderName::from_static("x-content-type-options"),
-        HeaderValue::from_static("nosniff")
-    );
-    
-    // Prevent clickjacking
-    headers.insert(
-        HeaderName::from_static("x-frame-options"),
-        HeaderValue::from_static("DENY")
-    );
-    
-    // Enable XSS protections
-    headers.insert(
-        HeaderName::from_static("x-xss-protection"),
-        HeaderValue::from_static("1; mode=block")
-    );
-    
-    // Force HTTPS connections
-    headers.insert(
-        HeaderName::from_static("strict-transport-security"),
-        HeaderValue::from_static("max-age=31536000; includeSubDomains; preload")
-    );
-    
-    // Control referrer information
-    headers.insert(
-        HeaderName::from_static("referrer-policy"),
-        HeaderValue::from_static("strict-origin-when-cross-origin")
-    );
-    
-    // Content Security Policy
-    headers.insert(
-        HeaderName::from_static("content-security-policy"),
-        HeaderValue::from_static("default-src 'self'; script-src 'self'; img-src 'self'; style-src 'self'; font-src 'self'; frame-ancestors 'none'; base-uri 'self'; form-action 'self'")
-    );
-    
-    // Permissions Policy
-    headers.insert(
-        HeaderName::from_static("permissions-policy"), 
-        HeaderValue::from_static("camera=(), microphone=(), geolocation=(), interest-cohort=()")
-    );
-    
+    for (name, value) in &policy.headers {
+        // Values were validated up front and the nonce is hex
+        if let Ok(value) = HeaderValue::try_from(value.replace("{nonce}", nonce.as_str())) {
+            headers.insert(name.clone(), value);
+        }
+    }
+    response.extensions_mut().insert(HeadersApplied);
     response
-}
\ No newline at end of file
+}
@@ -0,0 +1,266 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Postgres, QueryBuilder};
+
+use crate::db::traced;
+
+// Serialises appends so every row sees the hash of the row before it
+const CHAIN_LOCK: i64 = 0x6175_6469_7400;
+
+// `prev_hash` of the first row
+const GENESIS: [u8; 32] = [0; 32];
+
+pub const OUTCOME_SUCCESS: &str = "success";
+pub const OUTCOME_FAILURE: &str = "failure";
+
+// A stored audit event
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor: String,
+    pub ip: Option<String>,
+    pub action: String,
+    pub resource: Option<String>,
+    pub outcome: String,
+    pub request_id: Option<String>,
+    pub details: Value,
+    #[serde(skip)]
+    pub prev_hash: Vec<u8>,
+    #[serde(serialize_with = "as_hex")]
+    pub hash: Vec<u8>,
+}
+
+fn as_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(bytes))
+}
+
+// An event to append to the audit log
+#[derive(Debug, Clone)]
+pub struct NewAuditRecord {
+    pub actor: String,
+    pub ip: Option<String>,
+    pub action: String,
+    pub resource: Option<String>,
+    pub outcome: &'static str,
+    pub request_id: Option<String>,
+    pub details: Value,
+}
+
+// Filters for searching the audit log, all optional. Results are newest first;
+// pass the smallest id of a page as `before` to get the next one.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub resource: Option<String>,
+    pub outcome: Option<String>,
+    pub request_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+// Result of checking the hash chain
+#[derive(Debug, Serialize)]
+pub struct ChainVerification {
+    pub events: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broken_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+}
+
+impl ChainVerification {
+    pub fn is_intact(&self) -> bool {
+        self.broken_at.is_none()
+    }
+}
+
+// The hashed contents of a row
+struct Chained<'a> {
+    occurred_at: DateTime<Utc>,
+    actor: &'a str,
+    ip: Option<&'a str>,
+    action: &'a str,
+    resource: Option<&'a str>,
+    outcome: &'a str,
+    request_id: Option<&'a str>,
+    details: &'a Value,
+}
+
+impl Chained<'_> {
+    // Every field is length-prefixed so values cannot run into each other
+    fn hash(&self, prev_hash: &[u8]) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update(prev_hash);
+        let occurred_at = self.occurred_at.timestamp_micros().to_string();
+        let details = self.details.to_string();
+        for field in [
+            Some(occurred_at.as_str()),
+            Some(self.actor),
+            self.ip,
+            Some(self.action),
+            self.resource,
+            Some(self.outcome),
+            self.request_id,
+            Some(details.as_str()),
+        ] {
+            match field {
+                Some(value) => {
+                    hasher.update((value.len() as u64).to_be_bytes());
+                    hasher.update(value.as_bytes());
+                }
+                None => hasher.update(u64::MAX.to_be_bytes()),
+            }
+        }
+        hasher.finalize().to_vec()
+    }
+}
+
+impl AuditRecord {
+    fn expected_hash(&self) -> Vec<u8> {
+        Chained {
+            occurred_at: self.occurred_at,
+            actor: &self.actor,
+            ip: self.ip.as_deref(),
+            action: &self.action,
+            resource: self.resource.as_deref(),
+            outcome: &self.outcome,
+            request_id: self.request_id.as_deref(),
+            details: &self.details,
+        }
+        .hash(&self.prev_hash)
+    }
+
+    // Appends an event, chained to the current last row
+    pub async fn append(event: NewAuditRecord, pool: &Pool<Postgres>) -> Result<AuditRecord, sqlx::Error> {
+        let mut stored = Self::append_all(vec![event], pool).await?;
+        Ok(stored.remove(0))
+    }
+
+    // Appends the events in order, in one transaction that takes the chain lock once
+    pub async fn append_all(events: Vec<NewAuditRecord>, pool: &Pool<Postgres>) -> Result<Vec<AuditRecord>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(CHAIN_LOCK)
+            .execute(traced(&mut *tx))
+            .await?;
+        let mut prev_hash = sqlx::query_scalar::<_, Vec<u8>>("SELECT hash FROM audit_events ORDER BY id DESC LIMIT 1")
+            .fetch_optional(traced(&mut *tx))
+            .await?
+            .unwrap_or_else(|| GENESIS.to_vec());
+
+        let mut stored = Vec::with_capacity(events.len());
+        for event in events {
+            // Postgres stores microseconds, hash exactly what will be read back
+            let occurred_at = DateTime::from_timestamp_micros(Utc::now().timestamp_micros()).unwrap_or_default();
+            let hash = Chained {
+                occurred_at,
+                actor: &event.actor,
+                ip: event.ip.as_deref(),
+                action: &event.action,
+                resource: event.resource.as_deref(),
+                outcome: event.outcome,
+                request_id: event.request_id.as_deref(),
+                details: &event.details,
+            }
+            .hash(&prev_hash);
+
+            let record = sqlx::query_as::<_, AuditRecord>(
+                "INSERT INTO audit_events \
+                 (occurred_at, actor, ip, action, resource, outcome, request_id, details, prev_hash, hash) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING *",
+            )
+            .bind(occurred_at)
+            .bind(&event.actor)
+            .bind(&event.ip)
+            .bind(&event.action)
+            .bind(&event.resource)
+            .bind(event.outcome)
+            .bind(&event.request_id)
+            .bind(&event.details)
+            .bind(&prev_hash)
+            .bind(&hash)
+            .fetch_one(traced(&mut *tx))
+            .await?;
+            prev_hash = hash;
+            stored.push(record);
+        }
+        tx.commit().await?;
+
+        Ok(stored)
+    }
+
+    pub async fn search(filter: &AuditFilter, pool: &Pool<Postgres>) -> Result<Vec<AuditRecord>, sqlx::Error> {
+        let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM audit_events WHERE TRUE");
+        for (column, value) in [
+            ("actor", &filter.actor),
+            ("action", &filter.action),
+            ("resource", &filter.resource),
+            ("outcome", &filter.outcome),
+            ("request_id", &filter.request_id),
+        ] {
+            if let Some(value) = value {
+                query.push(format!(" AND {} = ", column)).push_bind(value);
+            }
+        }
+        if let Some(from) = filter.from {
+            query.push(" AND occurred_at >= ").push_bind(from);
+        }
+        if let Some(to) = filter.to {
+            query.push(" AND occurred_at < ").push_bind(to);
+        }
+        if let Some(before) = filter.before {
+            query.push(" AND id < ").push_bind(before);
+        }
+        query
+            .push(" ORDER BY id DESC LIMIT ")
+            .push_bind(filter.limit.unwrap_or(100).clamp(1, 1000));
+
+        query.build_query_as().fetch_all(traced(pool)).await
+    }
+
+    // Walks the whole log in id order and reports the first row whose contents
+    // don't match its hash or that doesn't link to the row before it. Removing
+    // rows from the end can't be detected from the chain alone.
+    pub async fn verify_chain(pool: &Pool<Postgres>) -> Result<ChainVerification, sqlx::Error> {
+        const BATCH: i64 = 1000;
+        let mut prev_hash = GENESIS.to_vec();
+        let mut last_id = 0;
+        let mut events = 0;
+
+        loop {
+            let batch = sqlx::query_as::<_, AuditRecord>(
+                "SELECT * FROM audit_events WHERE id > $1 ORDER BY id LIMIT $2",
+            )
+            .bind(last_id)
+            .bind(BATCH)
+            .fetch_all(traced(pool))
+            .await?;
+
+            for event in &batch {
+                let reason = if event.prev_hash != prev_hash {
+                    Some("does not link to the previous event")
+                } else if event.expected_hash() != event.hash {
+                    Some("contents do not match the stored hash")
+                } else {
+                    None
+                };
+                if reason.is_some() {
+                    return Ok(ChainVerification { events, broken_at: Some(event.id), reason });
+                }
+                events += 1;
+                prev_hash.clone_from(&event.hash);
+                last_id = event.id;
+            }
+
+            if (batch.len() as i64) < BATCH {
+                return Ok(ChainVerification { events, broken_at: None, reason: None });
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+
+use crate::db::traced;
+use crate::services::error::AppError;
+use crate::slug::slugify;
+
+// A node of the category tree. Filtering by a category includes its subcategories.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Category {
+    pub id: i32,
+    pub parent_id: Option<i32>,
+    pub name: String,
+    pub slug: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// Without a slug one is derived from the name
+#[derive(Debug, Deserialize)]
+pub struct NewCategory {
+    pub name: String,
+    pub slug: Option<String>,
+    pub parent_id: Option<i32>,
+}
+
+impl NewCategory {
+    fn validate(&self) -> Result<String, AppError> {
+        if self.name.trim().is_empty() {
+            return Err(AppError::ValidationError("Name must not be empty".to_string()));
+        }
+        let slug = slugify(self.slug.as_deref().unwrap_or(&self.name));
+        if slug.is_empty() {
+            return Err(AppError::ValidationError("Slug must contain letters or digits".to_string()));
+        }
+        Ok(slug)
+    }
+}
+
+fn db_error(e: sqlx::Error) -> AppError {
+    if let sqlx::Error::Database(ref dbe) = e {
+        if dbe.is_unique_violation() {
+            return AppError::Conflict("A category with this slug already exists".to_string());
+        }
+        if dbe.is_foreign_key_violation() {
+            return AppError::ValidationError("Parent category not found".to_string());
+        }
+    }
+    AppError::DatabaseError(e.to_string())
+}
+
+impl Category {
+    pub async fn create(category: NewCategory, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        let slug = category.validate()?;
+        sqlx::query_as::<_, Category>("INSERT INTO categories (parent_id, name, slug) VALUES ($1, $2, $3) RETURNING *")
+            .bind(category.parent_id)
+            .bind(category.name.trim())
+            .bind(slug)
+            .fetch_one(traced(pool))
+            .await
+            .map_err(db_error)
+    }
+
+    pub async fn find(id: i32, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        sqlx::query_as::<_, Category>("SELECT * FROM categories WHERE id = $1")
+            .bind(id)
+            .fetch_optional(traced(pool))
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| AppError::NotFound("Category not found".to_string()))
+    }
+
+    // The whole tree, parents before their children
+    pub async fn list(pool: &Pool<Postgres>) -> Result<Vec<Self>, AppError> {
+        sqlx::query_as::<_, Category>(
+            "WITH RECURSIVE tree AS ( \
+                 SELECT categories.*, ARRAY[name] AS path FROM categories WHERE parent_id IS NULL \
+                 UNION ALL \
+                 SELECT categories.*, tree.path || categories.name FROM categories \
+                 JOIN tree ON categories.parent_id = tree.id \
+             ) \
+             SELECT id, parent_id, name, slug, created_at FROM tree ORDER BY path",
+        )
+        .fetch_all(traced(pool))
+        .await
+        .map_err(db_error)
+    }
+
+    // Renames or moves the category. Moving it below itself or one of its
+    // subcategories would cut the subtree off the tree, so that is refused.
+    pub async fn update(&self, category: NewCategory, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        let slug = category.validate()?;
+        if let Some(parent_id) = category.parent_id {
+            let cycle: bool = sqlx::query_scalar(
+                "WITH RECURSIVE subtree AS ( \
+                     SELECT id FROM categories WHERE id = $1 \
+                     UNION ALL \
+                     SELECT categories.id FROM categories JOIN subtree ON categories.parent_id = subtree.id \
+                 ) \
+                 SELECT EXISTS (SELECT 1 FROM subtree WHERE id = $2)",
+            )
+            .bind(self.id)
+            .bind(parent_id)
+            .fetch_one(traced(pool))
+            .await
+            .map_err(db_error)?;
+            if cycle {
+                return Err(AppError::ValidationError("A category can't be moved below itself".to_string()));
+            }
+        }
+
+        sqlx::query_as::<_, Category>("UPDATE categories SET parent_id = $2, name = $3, slug = $4 WHERE id = $1 RETURNING *")
+            .bind(self.id)
+            .bind(category.parent_id)
+            .bind(category.name.trim())
+            .bind(slug)
+            .fetch_optional(traced(pool))
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| AppError::NotFound("Category not found".to_string()))
+    }
+
+    // Only leaf categories can be deleted, their posts become uncategorised
+    pub async fn delete(&self, pool: &Pool<Postgres>) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM categories WHERE id = $1")
+            .bind(self.id)
+            .execute(traced(pool))
+            .await
+            .map_err(|e| match e {
+                // The subcategories' reference to this one
+                sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                    AppError::Conflict("Category has subcategories".to_string())
+                }
+                e => db_error(e),
+            })?;
+        Ok(())
+    }
+}
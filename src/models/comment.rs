@@ -0,0 +1,346 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+
+use crate::db::traced;
+use crate::models::post::Viewer;
+use crate::services::error::AppError;
+
+const MAX_BODY_LEN: usize = 10_000;
+// Deeper pages are clamped, so the offset can't overflow
+const MAX_PAGE: i64 = 100_000;
+
+// New comments wait for an editor; hidden ones are only seen by editors and their author
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentStatus {
+    Pending,
+    Approved,
+    Hidden,
+}
+
+impl CommentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommentStatus::Pending => "pending",
+            CommentStatus::Approved => "approved",
+            CommentStatus::Hidden => "hidden",
+        }
+    }
+}
+
+impl TryFrom<String> for CommentStatus {
+    type Error = AppError;
+
+    fn try_from(status: String) -> Result<Self, Self::Error> {
+        match status.as_str() {
+            "pending" => Ok(CommentStatus::Pending),
+            "approved" => Ok(CommentStatus::Approved),
+            "hidden" => Ok(CommentStatus::Hidden),
+            _ => Err(AppError::ValidationError(format!("Unknown comment status: {}", status))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Comment {
+    pub id: i64,
+    pub post_id: i32,
+    pub parent_id: Option<i64>,
+    pub root_id: Option<i64>,
+    pub author_id: Option<i32>,
+    pub body: String,
+    #[sqlx(try_from = "String")]
+    pub status: CommentStatus,
+    pub created_at: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewComment {
+    pub body: String,
+    // Set for replies
+    pub parent_id: Option<i64>,
+}
+
+fn validate_body(body: &str) -> Result<(), AppError> {
+    if body.trim().is_empty() || body.chars().count() > MAX_BODY_LEN {
+        return Err(AppError::ValidationError(format!("Comments must be 1 to {} characters", MAX_BODY_LEN)));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentView {
+    // Threads: top-level comments with their replies below them
+    #[default]
+    Nested,
+    // All comments oldest first, replies carry their parent_id
+    Flat,
+}
+
+// `?view=flat&page=2&per_page=50`; nested pages count top-level comments
+#[derive(Debug, Default, Deserialize)]
+pub struct CommentQuery {
+    #[serde(default)]
+    pub view: CommentView,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+impl CommentQuery {
+    fn page(&self) -> (i64, i64) {
+        (self.page.unwrap_or(1).clamp(1, MAX_PAGE), self.per_page.unwrap_or(50).clamp(1, 200))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommentThread {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub replies: Vec<CommentThread>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum CommentList {
+    Flat(Vec<Comment>),
+    Nested(Vec<CommentThread>),
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommentPage {
+    pub comments: CommentList,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+// Arranges replies below their parents. Replies whose parent is not in
+// `replies` or `roots`, because the viewer can't see it, are left out.
+fn threads(roots: Vec<Comment>, replies: Vec<Comment>) -> Vec<CommentThread> {
+    let mut children: HashMap<i64, Vec<Comment>> = HashMap::new();
+    for reply in replies {
+        if let Some(parent_id) = reply.parent_id {
+            children.entry(parent_id).or_default().push(reply);
+        }
+    }
+    fn build(comment: Comment, children: &mut HashMap<i64, Vec<Comment>>) -> CommentThread {
+        let replies = children.remove(&comment.id).unwrap_or_default();
+        let replies = replies.into_iter().map(|reply| build(reply, children)).collect();
+        CommentThread { comment, replies }
+    }
+    roots.into_iter().map(|root| build(root, &mut children)).collect()
+}
+
+impl Comment {
+    pub fn is_visible_to(&self, viewer: Viewer) -> bool {
+        self.status == CommentStatus::Approved || self.author_id == Some(viewer.user_id) || viewer.is_editor
+    }
+
+    pub async fn create(post_id: i32, author_id: i32, comment: NewComment, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        validate_body(&comment.body)?;
+        let root_id = match comment.parent_id {
+            Some(parent_id) => {
+                let parent = Comment::find(parent_id, pool).await?;
+                if parent.post_id != post_id || parent.deleted_at.is_some() {
+                    return Err(AppError::ValidationError("Replies must be to a comment on the same post".to_string()));
+                }
+                Some(parent.root_id.unwrap_or(parent.id))
+            }
+            None => None,
+        };
+
+        sqlx::query_as::<_, Comment>(
+            "INSERT INTO comments (post_id, parent_id, root_id, author_id, body) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+        )
+        .bind(post_id)
+        .bind(comment.parent_id)
+        .bind(root_id)
+        .bind(author_id)
+        .bind(comment.body)
+        .fetch_one(traced(pool))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    pub async fn find(id: i64, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        sqlx::query_as::<_, Comment>("SELECT * FROM comments WHERE id = $1")
+            .bind(id)
+            .fetch_optional(traced(pool))
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .ok_or_else(|| AppError::NotFound("Comment not found".to_string()))
+    }
+
+    // A changed approved comment goes back to the moderation queue
+    pub async fn edit(&self, body: &str, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        validate_body(body)?;
+        sqlx::query_as::<_, Comment>(
+            "UPDATE comments SET body = $2, edited_at = NOW(), \
+             status = CASE WHEN status = 'approved' THEN 'pending' ELSE status END \
+             WHERE id = $1 AND deleted_at IS NULL RETURNING *",
+        )
+        .bind(self.id)
+        .bind(body)
+        .fetch_optional(traced(pool))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("Comment not found".to_string()))
+    }
+
+    // Clears the body but keeps the comment, its replies stay in the thread
+    pub async fn delete(&self, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        sqlx::query_as::<_, Comment>(
+            "UPDATE comments SET body = '', deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL RETURNING *",
+        )
+        .bind(self.id)
+        .fetch_optional(traced(pool))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("Comment not found".to_string()))
+    }
+
+    // Deleted comments are out of moderation
+    pub async fn moderate(&self, status: CommentStatus, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        sqlx::query_as::<_, Comment>("UPDATE comments SET status = $2 WHERE id = $1 AND deleted_at IS NULL RETURNING *")
+            .bind(self.id)
+            .bind(status.as_str())
+            .fetch_optional(traced(pool))
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .ok_or_else(|| AppError::NotFound("Comment not found".to_string()))
+    }
+
+    // The post's comments `viewer` may see, a page of them
+    pub async fn for_post(
+        post_id: i32,
+        viewer: Viewer,
+        query: &CommentQuery,
+        pool: &Pool<Postgres>,
+    ) -> Result<CommentPage, AppError> {
+        let (page, per_page) = query.page();
+        let db_error = |e: sqlx::Error| AppError::DatabaseError(e.to_string());
+        // Flat pages count all comments, nested ones only the top-level comments
+        let roots_only = query.view == CommentView::Nested;
+        let visible = "post_id = $1 AND (status = 'approved' OR author_id = $2 OR $3) AND ($4 = FALSE OR parent_id IS NULL)";
+
+        let count = format!("SELECT COUNT(*) FROM comments WHERE {}", visible);
+        let select = format!("SELECT * FROM comments WHERE {} ORDER BY created_at, id LIMIT $5 OFFSET $6", visible);
+
+        let total: i64 = sqlx::query_scalar(&count)
+            .bind(post_id)
+            .bind(viewer.user_id)
+            .bind(viewer.is_editor)
+            .bind(roots_only)
+            .fetch_one(traced(pool))
+            .await
+            .map_err(db_error)?;
+        let comments = sqlx::query_as::<_, Comment>(&select)
+        .bind(post_id)
+        .bind(viewer.user_id)
+        .bind(viewer.is_editor)
+        .bind(roots_only)
+        .bind(per_page)
+        .bind((page - 1) * per_page)
+        .fetch_all(traced(pool))
+        .await
+        .map_err(db_error)?;
+
+        let comments = match query.view {
+            CommentView::Flat => CommentList::Flat(comments),
+            CommentView::Nested => {
+                let root_ids: Vec<i64> = comments.iter().map(|comment| comment.id).collect();
+                let replies = sqlx::query_as::<_, Comment>(
+                    "SELECT * FROM comments WHERE root_id = ANY($1) \
+                     AND (status = 'approved' OR author_id = $2 OR $3) ORDER BY created_at, id",
+                )
+                .bind(&root_ids)
+                .bind(viewer.user_id)
+                .bind(viewer.is_editor)
+                .fetch_all(traced(pool))
+                .await
+                .map_err(db_error)?;
+                CommentList::Nested(threads(comments, replies))
+            }
+        };
+        Ok(CommentPage { comments, total, page, per_page })
+    }
+
+    // The moderation queue: pending comments of all posts, oldest first
+    pub async fn pending(query: &CommentQuery, pool: &Pool<Postgres>) -> Result<CommentPage, AppError> {
+        let (page, per_page) = query.page();
+        let db_error = |e: sqlx::Error| AppError::DatabaseError(e.to_string());
+
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM comments WHERE status = 'pending' AND deleted_at IS NULL")
+            .fetch_one(traced(pool))
+            .await
+            .map_err(db_error)?;
+        let comments = sqlx::query_as::<_, Comment>(
+            "SELECT * FROM comments WHERE status = 'pending' AND deleted_at IS NULL \
+             ORDER BY created_at, id LIMIT $1 OFFSET $2",
+        )
+        .bind(per_page)
+        .bind((page - 1) * per_page)
+        .fetch_all(traced(pool))
+        .await
+        .map_err(db_error)?;
+        Ok(CommentPage { comments: CommentList::Flat(comments), total, page, per_page })
+    }
+}
+
+// A user an editor barred from commenting
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct CommentBan {
+    pub user_id: i32,
+    pub banned_by: Option<i32>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl CommentBan {
+    pub async fn is_banned(user_id: i32, pool: &Pool<Postgres>) -> Result<bool, AppError> {
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM comment_bans WHERE user_id = $1)")
+            .bind(user_id)
+            .fetch_one(traced(pool))
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    // Bans the user and hides their comments still waiting for approval.
+    // Banning again keeps the first ban.
+    pub async fn ban(user_id: i32, banned_by: i32, reason: Option<&str>, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        let db_error = |e: sqlx::Error| AppError::DatabaseError(e.to_string());
+        let mut tx = pool.begin().await.map_err(db_error)?;
+        let ban = sqlx::query_as::<_, CommentBan>(
+            "INSERT INTO comment_bans (user_id, banned_by, reason) VALUES ($1, $2, $3) \
+             ON CONFLICT (user_id) DO UPDATE SET user_id = comment_bans.user_id RETURNING *",
+        )
+        .bind(user_id)
+        .bind(banned_by)
+        .bind(reason)
+        .fetch_one(traced(&mut *tx))
+        .await
+        .map_err(db_error)?;
+        sqlx::query("UPDATE comments SET status = 'hidden' WHERE author_id = $1 AND status = 'pending'")
+            .bind(user_id)
+            .execute(traced(&mut *tx))
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(ban)
+    }
+
+    // False if the user wasn't banned
+    pub async fn lift(user_id: i32, pool: &Pool<Postgres>) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM comment_bans WHERE user_id = $1")
+            .bind(user_id)
+            .execute(traced(pool))
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        Ok(result.rows_affected() > 0)
+    }
+}
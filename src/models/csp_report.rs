@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+
+use crate::db::traced;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CspReport {
+    pub id: i64,
+    pub received_at: DateTime<Utc>,
+    pub document_uri: Option<String>,
+    pub blocked_uri: Option<String>,
+    pub effective_directive: Option<String>,
+    pub source_file: Option<String>,
+    pub line_number: Option<i64>,
+    pub disposition: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub report: Value,
+}
+
+// A violation as sent by the browser, with the fields we index pulled out
+#[derive(Debug)]
+pub struct NewCspReport {
+    pub document_uri: Option<String>,
+    pub blocked_uri: Option<String>,
+    pub effective_directive: Option<String>,
+    pub source_file: Option<String>,
+    pub line_number: Option<i64>,
+    pub disposition: Option<String>,
+    pub report: Value,
+}
+
+impl NewCspReport {
+    // `application/csp-report` (the `report-uri` directive): `{"csp-report": {"document-uri": ...}}`
+    pub fn from_report_uri(payload: Value) -> Option<Self> {
+        let report = payload.get("csp-report")?.clone();
+        let field = |name: &str| report.get(name).and_then(Value::as_str).map(str::to_string);
+        Some(Self {
+            document_uri: field("document-uri"),
+            blocked_uri: field("blocked-uri"),
+            effective_directive: field("effective-directive").or_else(|| field("violated-directive")),
+            source_file: field("source-file"),
+            line_number: report.get("line-number").and_then(Value::as_i64),
+            disposition: field("disposition"),
+            report,
+        })
+    }
+
+    // `application/reports+json` (the Reporting API, `report-to`): a list of
+    // reports of any type, only `csp-violation` ones are kept
+    pub fn from_reporting_api(payload: Value) -> Vec<Self> {
+        let Value::Array(reports) = payload else {
+            return Vec::new();
+        };
+        reports
+            .into_iter()
+            .filter(|report| report.get("type").and_then(Value::as_str) == Some("csp-violation"))
+            .filter_map(|report| {
+                let body = report.get("body")?;
+                let field = |name: &str| body.get(name).and_then(Value::as_str).map(str::to_string);
+                Some(Self {
+                    document_uri: field("documentURL"),
+                    blocked_uri: field("blockedURL"),
+                    effective_directive: field("effectiveDirective"),
+                    source_file: field("sourceFile"),
+                    line_number: body.get("lineNumber").and_then(Value::as_i64),
+                    disposition: field("disposition"),
+                    report: report.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl CspReport {
+    pub async fn insert(
+        reports: Vec<NewCspReport>,
+        user_agent: Option<&str>,
+        ip: Option<&str>,
+        pool: &Pool<Postgres>,
+    ) -> Result<(), sqlx::Error> {
+        for report in reports {
+            sqlx::query(
+                "INSERT INTO csp_reports \
+                 (document_uri, blocked_uri, effective_directive, source_file, line_number, disposition, user_agent, ip, report) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            )
+            .bind(report.document_uri)
+            .bind(report.blocked_uri)
+            .bind(report.effective_directive)
+            .bind(report.source_file)
+            .bind(report.line_number)
+            .bind(report.disposition)
+            .bind(user_agent)
+            .bind(ip)
+            .bind(report.report)
+            .execute(traced(pool))
+            .await?;
+        }
+        Ok(())
+    }
+
+    // Newest first, `before` is the smallest id of the previous page
+    pub async fn list(before: Option<i64>, limit: i64, pool: &Pool<Postgres>) -> Result<Vec<CspReport>, sqlx::Error> {
+        sqlx::query_as::<_, CspReport>(
+            "SELECT * FROM csp_reports WHERE $1::BIGINT IS NULL OR id < $1 ORDER BY id DESC LIMIT $2",
+        )
+        .bind(before)
+        .bind(limit.clamp(1, 1000))
+        .fetch_all(traced(pool))
+        .await
+    }
+}
@@ -0,0 +1,144 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Executor, Pool, Postgres};
+use std::time::Duration;
+
+use crate::db::traced;
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_SUCCEEDED: &str = "succeeded";
+pub const STATUS_FAILED: &str = "failed";
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Job {
+    pub id: i64,
+    pub kind: String,
+    pub payload: Value,
+    pub status: String,
+    pub run_at: DateTime<Utc>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub last_error: Option<String>,
+    pub locked_by: Option<String>,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct JobFilter {
+    pub status: Option<String>,
+    pub kind: Option<String>,
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl Job {
+    // Takes a pool, or the transaction that makes the job necessary so that
+    // the job is only there if the change is
+    pub async fn enqueue<'c>(
+        kind: &str,
+        payload: Value,
+        run_at: DateTime<Utc>,
+        executor: impl Executor<'c, Database = Postgres>,
+    ) -> Result<Job, sqlx::Error> {
+        sqlx::query_as::<_, Job>("INSERT INTO jobs (kind, payload, run_at) VALUES ($1, $2, $3) RETURNING *")
+            .bind(kind)
+            .bind(payload)
+            .bind(run_at)
+            .fetch_one(traced(executor))
+            .await
+    }
+
+    // Takes the next due job for `worker`. Rows locked by another worker's claim
+    // are skipped rather than waited for. A job still `running` after
+    // `stale_after` belonged to a worker that died and is claimed again, or
+    // fails for good when that was its last attempt.
+    pub async fn claim(worker: &str, stale_after: Duration, pool: &Pool<Postgres>) -> Result<Option<Job>, sqlx::Error> {
+        sqlx::query_as::<_, Job>(
+            "WITH exhausted AS ( \
+                 UPDATE jobs SET status = 'failed', finished_at = NOW(), locked_by = NULL, locked_at = NULL, \
+                 last_error = 'worker stopped during the last attempt' \
+                 WHERE id IN ( \
+                     SELECT id FROM jobs \
+                     WHERE status = 'running' AND locked_at < NOW() - make_interval(secs => $2) \
+                       AND attempts >= max_attempts \
+                     FOR UPDATE SKIP LOCKED \
+                 ) \
+             ) \
+             UPDATE jobs SET status = 'running', locked_by = $1, locked_at = NOW(), attempts = attempts + 1 \
+             WHERE id = ( \
+                 SELECT id FROM jobs \
+                 WHERE (status = 'pending' AND run_at <= NOW()) \
+                    OR (status = 'running' AND locked_at < NOW() - make_interval(secs => $2) \
+                        AND attempts < max_attempts) \
+                 ORDER BY run_at, id \
+                 LIMIT 1 \
+                 FOR UPDATE SKIP LOCKED \
+             ) \
+             RETURNING *",
+        )
+        .bind(worker)
+        .bind(stale_after.as_secs_f64())
+        .fetch_optional(traced(pool))
+        .await
+    }
+
+    pub async fn succeed(&self, pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE jobs SET status = 'succeeded', finished_at = NOW(), last_error = NULL, locked_by = NULL, locked_at = NULL \
+             WHERE id = $1",
+        )
+        .bind(self.id)
+        .execute(traced(pool))
+        .await?;
+        Ok(())
+    }
+
+    // Schedules another attempt after `retry_in`, or gives up once the attempts are used
+    pub async fn fail(&self, error: &str, retry_in: Duration, pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE jobs SET last_error = $2, locked_by = NULL, locked_at = NULL, \
+             status = CASE WHEN attempts >= max_attempts THEN 'failed' ELSE 'pending' END, \
+             finished_at = CASE WHEN attempts >= max_attempts THEN NOW() END, \
+             run_at = CASE WHEN attempts >= max_attempts THEN run_at ELSE NOW() + make_interval(secs => $3) END \
+             WHERE id = $1",
+        )
+        .bind(self.id)
+        .bind(error)
+        .bind(retry_in.as_secs_f64())
+        .execute(traced(pool))
+        .await?;
+        Ok(())
+    }
+
+    // Gives a failed job a fresh set of attempts, returns None unless it had failed
+    pub async fn retry(id: i64, pool: &Pool<Postgres>) -> Result<Option<Job>, sqlx::Error> {
+        sqlx::query_as::<_, Job>(
+            "UPDATE jobs SET status = 'pending', attempts = 0, run_at = NOW(), finished_at = NULL \
+             WHERE id = $1 AND status = 'failed' RETURNING *",
+        )
+        .bind(id)
+        .fetch_optional(traced(pool))
+        .await
+    }
+
+    // Newest first, `before` is the smallest id of the previous page
+    pub async fn list(filter: &JobFilter, pool: &Pool<Postgres>) -> Result<Vec<Job>, sqlx::Error> {
+        sqlx::query_as::<_, Job>(
+            "SELECT * FROM jobs \
+             WHERE ($1::TEXT IS NULL OR status = $1) \
+             AND ($2::TEXT IS NULL OR kind = $2) \
+             AND ($3::BIGINT IS NULL OR id < $3) \
+             ORDER BY id DESC LIMIT $4",
+        )
+        .bind(&filter.status)
+        .bind(&filter.kind)
+        .bind(filter.before)
+        .bind(filter.limit.unwrap_or(100).clamp(1, 1000))
+        .fetch_all(traced(pool))
+        .await
+    }
+}
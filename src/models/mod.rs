@@ -1,2 +1,12 @@
+pub mod audit_record;
+pub mod category;
+pub mod comment;
+pub mod csp_report;
+pub mod job;
 pub mod post;
+pub mod post_revision;
+pub mod post_search;
+pub mod post_slug;
+pub mod reaction;
+pub mod tag;
 pub mod user;
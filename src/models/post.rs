@@ -1,9 +1,842 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgArguments, PgRow, PgTypeInfo, PgValueRef};
+use sqlx::query::Map;
+use sqlx::{Connection, PgConnection, Pool, Postgres};
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Deserialize, Serialize)]
+use crate::db::traced;
+use crate::jobs;
+use crate::markdown::{self, Rendered};
+use crate::models::post_revision::PostRevision;
+use crate::models::post_slug;
+use crate::models::post_search::ensure_language;
+use crate::services::error::AppError;
+
+// Deeper pages are clamped, so the offset can't overflow
+const MAX_PAGE: i64 = 100_000;
+const RERENDER_BATCH: i64 = 500;
+const SLUG_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostStatus {
+    Draft,
+    InReview,
+    Scheduled,
+    Published,
+    Archived,
+}
+
+impl PostStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostStatus::Draft => "draft",
+            PostStatus::InReview => "in_review",
+            PostStatus::Scheduled => "scheduled",
+            PostStatus::Published => "published",
+            PostStatus::Archived => "archived",
+        }
+    }
+}
+
+impl fmt::Display for PostStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PostStatus {
+    type Err = AppError;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "draft" => Ok(PostStatus::Draft),
+            "in_review" => Ok(PostStatus::InReview),
+            "scheduled" => Ok(PostStatus::Scheduled),
+            "published" => Ok(PostStatus::Published),
+            "archived" => Ok(PostStatus::Archived),
+            _ => Err(AppError::ValidationError(format!("Unknown post status: {}", status))),
+        }
+    }
+}
+
+impl TryFrom<String> for PostStatus {
+    type Error = AppError;
+
+    fn try_from(status: String) -> Result<Self, Self::Error> {
+        status.parse()
+    }
+}
+
+// Stored as TEXT, decoded for the `"status: PostStatus"` overrides of `query_as!`
+impl sqlx::Type<Postgres> for PostStatus {
+    fn type_info() -> PgTypeInfo {
+        <String as sqlx::Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <String as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Postgres> for PostStatus {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(<&str as sqlx::Decode<Postgres>>::decode(value)?.parse()?)
+    }
+}
+
+// Whether readers may comment on a post
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentsSetting {
+    // New comments welcome
+    Open,
+    // Existing comments stay visible, no new ones
+    Closed,
+    // No comments shown or taken
+    Disabled,
+}
+
+impl CommentsSetting {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommentsSetting::Open => "open",
+            CommentsSetting::Closed => "closed",
+            CommentsSetting::Disabled => "disabled",
+        }
+    }
+}
+
+impl TryFrom<String> for CommentsSetting {
+    type Error = AppError;
+
+    fn try_from(setting: String) -> Result<Self, Self::Error> {
+        match setting.as_str() {
+            "open" => Ok(CommentsSetting::Open),
+            "closed" => Ok(CommentsSetting::Closed),
+            "disabled" => Ok(CommentsSetting::Disabled),
+            _ => Err(AppError::ValidationError(format!("Unknown comments setting: {}", setting))),
+        }
+    }
+}
+
+// Stored as TEXT, like the status
+impl sqlx::Type<Postgres> for CommentsSetting {
+    fn type_info() -> PgTypeInfo {
+        <String as sqlx::Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <String as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Postgres> for CommentsSetting {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(<String as sqlx::Decode<Postgres>>::decode(value)?.try_into()?)
+    }
+}
+
+// The steps of the review workflow:
+//
+//     draft --submit--> in_review --approve--> published --archive--> archived
+//       ^                 |    |                   ^
+//       +-----reject------+    +--schedule--> scheduled
+//                                           (until publish_at)
+//
+// Authors submit their own posts, editors approve or reject them. Approved
+// posts with a future `publish_at` are scheduled and published by a job;
+// a job also archives published posts once `unpublish_at` has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostTransition {
+    Submit,
+    Approve,
+    Reject,
+    Schedule,
+    Publish,
+    Archive,
+}
+
+impl PostTransition {
+    pub fn from(&self) -> PostStatus {
+        match self {
+            PostTransition::Submit => PostStatus::Draft,
+            PostTransition::Approve | PostTransition::Reject | PostTransition::Schedule => PostStatus::InReview,
+            PostTransition::Publish => PostStatus::Scheduled,
+            PostTransition::Archive => PostStatus::Published,
+        }
+    }
+
+    pub fn to(&self) -> PostStatus {
+        match self {
+            PostTransition::Submit => PostStatus::InReview,
+            PostTransition::Reject => PostStatus::Draft,
+            PostTransition::Approve | PostTransition::Publish => PostStatus::Published,
+            PostTransition::Schedule => PostStatus::Scheduled,
+            PostTransition::Archive => PostStatus::Archived,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Post {
     pub(crate) id: i32,
     pub(crate) user_id: Option<i32>,
     pub(crate) title: String,
     pub(crate) body: String,
-}
\ No newline at end of file
+    pub(crate) status: PostStatus,
+    pub(crate) published_at: Option<DateTime<Utc>>,
+    pub(crate) publish_at: Option<DateTime<Utc>>,
+    pub(crate) unpublish_at: Option<DateTime<Utc>>,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) updated_at: DateTime<Utc>,
+    // Incremented by every change to the row
+    pub(crate) version: i32,
+    // Text search configuration the post is indexed with
+    pub(crate) language: String,
+    pub(crate) category_id: Option<i32>,
+    pub(crate) comments: CommentsSetting,
+    // `body` is Markdown; these are rendered from it whenever it changes
+    pub(crate) body_html: Option<String>,
+    pub(crate) excerpt: Option<String>,
+    pub(crate) reading_minutes: Option<i32>,
+    // Unique, generated from the title unless `custom_slug`
+    pub(crate) slug: String,
+    pub(crate) custom_slug: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewPost {
+    pub title: String,
+    pub body: String,
+    // Search language, 'english' for new posts and unchanged on edits if not given
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+impl NewPost {
+    async fn validate(&self, pool: &Pool<Postgres>) -> Result<(), AppError> {
+        if self.title.trim().is_empty() {
+            return Err(AppError::ValidationError("Title must not be empty".to_string()));
+        }
+        if let Some(language) = &self.language {
+            ensure_language(language, pool).await?;
+        }
+        Ok(())
+    }
+}
+
+// Narrows `Post::list`: `?status=in_review&tag=rust&category=databases&page=2&per_page=50`
+#[derive(Debug, Default, Deserialize)]
+pub struct PostFilter {
+    pub status: Option<PostStatus>,
+    pub tag: Option<String>,
+    // Category slug, includes the subcategories
+    pub category: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+impl PostFilter {
+    fn page(&self) -> (i64, i64) {
+        (self.page.unwrap_or(1).clamp(1, MAX_PAGE), self.per_page.unwrap_or(50).clamp(1, 200))
+    }
+}
+
+// Embargo and expiry, set by editors
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct PostSchedule {
+    pub publish_at: Option<DateTime<Utc>>,
+    pub unpublish_at: Option<DateTime<Utc>>,
+}
+
+// Who is looking, decides which posts are visible
+#[derive(Debug, Clone, Copy)]
+pub struct Viewer {
+    pub user_id: i32,
+    pub is_editor: bool,
+}
+
+impl Post {
+    pub fn is_owned_by(&self, user_id: i32) -> bool {
+        self.user_id == Some(user_id)
+    }
+
+    // Published posts are public, authors see all of theirs and editors
+    // everything that left the draft stage
+    pub fn is_visible_to(&self, viewer: Viewer) -> bool {
+        self.status == PostStatus::Published
+            || self.is_owned_by(viewer.user_id)
+            || (viewer.is_editor && self.status != PostStatus::Draft)
+    }
+
+    // Why a write conditioned on this version of the post matched no row:
+    // someone saved in between, else `otherwise`
+    async fn refusal(&self, otherwise: AppError, pool: &Pool<Postgres>) -> AppError {
+        match Post::find(self.id, pool).await {
+            Ok(current) if current.version != self.version => {
+                AppError::PreconditionFailed("Post was changed by someone else".to_string())
+            }
+            Ok(_) => otherwise,
+            Err(e) => e,
+        }
+    }
+
+    // The new post's content is its first revision
+    pub async fn create(user_id: i32, post: NewPost, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        post.validate(pool).await?;
+
+        let rendered = markdown::render(&post.body);
+        let mut tx = pool.begin().await.map_err(db_error)?;
+        let created = with_generated_slug(&post.title, None, &mut tx, |slug| {
+            sqlx::query_as!(
+                Post,
+                r#"INSERT INTO posts (user_id, title, body, language, body_html, excerpt, reading_minutes, slug, render_version)
+                   VALUES ($1, $2, $3, COALESCE($4, 'english'), $5, $6, $7, $8, $9)
+                   RETURNING id, user_id, title, body, status AS "status: PostStatus", published_at,
+                             publish_at, unpublish_at, created_at, updated_at, version, language,
+                             category_id, comments AS "comments: CommentsSetting", body_html, excerpt,
+                             reading_minutes, slug, custom_slug"#,
+                user_id,
+                post.title,
+                post.body,
+                post.language,
+                rendered.html,
+                rendered.excerpt,
+                rendered.reading_minutes,
+                slug,
+                markdown::RENDERER_VERSION,
+            )
+        })
+        .await?
+        .ok_or(AppError::InternalServerError)?;
+        PostRevision::record(created.id, &created.title, &created.body, user_id, None, &mut tx)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(created)
+    }
+
+    pub async fn find(id: i32, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        sqlx::query_as!(
+            Post,
+            r#"SELECT id, user_id, title, body, status AS "status: PostStatus", published_at,
+                      publish_at, unpublish_at, created_at, updated_at, version, language,
+                      category_id, comments AS "comments: CommentsSetting", body_html, excerpt,
+                      reading_minutes, slug, custom_slug
+               FROM posts WHERE id = $1"#,
+            id,
+        )
+        .fetch_optional(traced(pool))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or(AppError::NotFound("Post not found".to_string()))
+    }
+
+    // A page of the posts `viewer` may see, narrowed by `filter`
+    pub async fn list(viewer: Viewer, filter: &PostFilter, pool: &Pool<Postgres>) -> Result<Vec<Self>, AppError> {
+        let (page, per_page) = filter.page();
+        sqlx::query_as!(
+            Post,
+            r#"SELECT id, user_id, title, body, status AS "status: PostStatus", published_at,
+                      publish_at, unpublish_at, created_at, updated_at, version, language,
+                      category_id, comments AS "comments: CommentsSetting", body_html, excerpt,
+                      reading_minutes, slug, custom_slug
+               FROM posts
+               WHERE (status = 'published' OR user_id = $1 OR ($2 AND status <> 'draft'))
+               AND ($3::TEXT IS NULL OR status = $3)
+               AND ($4::TEXT IS NULL OR id IN (
+                   SELECT post_id FROM post_tags JOIN tags ON tags.id = post_tags.tag_id WHERE tags.name = $4
+               ))
+               AND ($5::TEXT IS NULL OR category_id IN (
+                   WITH RECURSIVE subtree AS (
+                       SELECT id FROM categories WHERE slug = $5
+                       UNION ALL
+                       SELECT categories.id FROM categories JOIN subtree ON categories.parent_id = subtree.id
+                   )
+                   SELECT id FROM subtree
+               ))
+               ORDER BY id LIMIT $6 OFFSET $7"#,
+            viewer.user_id,
+            viewer.is_editor,
+            filter.status.map(|status| status.as_str()),
+            filter.tag.as_deref().map(|tag| tag.trim().to_lowercase()),
+            filter.category,
+            per_page,
+            (page - 1) * per_page,
+        )
+        .fetch_all(traced(pool))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    // Title and body can change unless the post is in review or archived.
+    // Editors' edits of approved posts stay live; when anyone else edits a
+    // published or scheduled post it goes back to review, so no text goes
+    // out unapproved. Every edit is kept as a new revision by `author`. The
+    // edit applies to this version of the post only; if someone saved in
+    // between it fails with 412.
+    pub async fn update(&self, author: Viewer, post: NewPost, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        post.validate(pool).await?;
+        self.save(author, &post.title, &post.body, post.language.as_deref(), None, pool).await
+    }
+
+    // Brings back the content of an earlier revision, as a new revision
+    pub async fn restore(&self, author: Viewer, revision: &PostRevision, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        self.save(author, &revision.title, &revision.body, None, Some(revision.revision), pool).await
+    }
+
+    async fn save(
+        &self,
+        author: Viewer,
+        title: &str,
+        body: &str,
+        language: Option<&str>,
+        restored_from: Option<i32>,
+        pool: &Pool<Postgres>,
+    ) -> Result<Self, AppError> {
+        // An unchanged body keeps its rendering
+        let rendered = (body != self.body).then(|| markdown::render(body));
+        let rendered = rendered.as_ref();
+
+        let mut tx = pool.begin().await.map_err(db_error)?;
+        let update = |slug: Option<String>| {
+            sqlx::query_as!(
+                Post,
+                r#"UPDATE posts SET title = $2, body = $3, language = COALESCE($5, language), updated_at = NOW(),
+                   body_html = COALESCE($6, body_html), excerpt = COALESCE($7, excerpt),
+                   reading_minutes = COALESCE($8, reading_minutes), slug = COALESCE($9, slug),
+                   render_version = COALESCE($10, render_version), version = version + 1,
+                   status = CASE WHEN $11 AND status IN ('published', 'scheduled') THEN 'in_review' ELSE status END
+                   WHERE id = $1 AND status NOT IN ('in_review', 'archived') AND version = $4
+                   RETURNING id, user_id, title, body, status AS "status: PostStatus", published_at,
+                             publish_at, unpublish_at, created_at, updated_at, version, language,
+                             category_id, comments AS "comments: CommentsSetting", body_html, excerpt,
+                             reading_minutes, slug, custom_slug"#,
+                self.id,
+                title,
+                body,
+                self.version,
+                language,
+                rendered.map(|rendered| rendered.html.as_str()),
+                rendered.map(|rendered| rendered.excerpt.as_str()),
+                rendered.map(|rendered| rendered.reading_minutes),
+                slug,
+                rendered.map(|_| markdown::RENDERER_VERSION),
+                !author.is_editor,
+            )
+        };
+        // A new title means a new slug, unless the author picked one
+        let post = if title != self.title && !self.custom_slug {
+            with_generated_slug(title, Some(self.id), &mut tx, |slug| update(Some(slug))).await?
+        } else {
+            update(None).fetch_optional(traced(&mut *tx)).await.map_err(db_error)?
+        };
+        let Some(post) = post else {
+            drop(tx);
+            return Err(self.refusal(AppError::Conflict("Posts in review or archived can't be edited".to_string()), pool).await);
+        };
+        post_slug::record_move(self.id, &self.slug, &post.slug, &mut tx)
+            .await
+            .map_err(db_error)?;
+        PostRevision::record(self.id, title, body, author.user_id, restored_from, &mut tx)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(post)
+    }
+
+    pub async fn find_by_slug(slug: &str, pool: &Pool<Postgres>) -> Result<Option<Self>, AppError> {
+        sqlx::query_as!(
+            Post,
+            r#"SELECT id, user_id, title, body, status AS "status: PostStatus", published_at,
+                      publish_at, unpublish_at, created_at, updated_at, version, language,
+                      category_id, comments AS "comments: CommentsSetting", body_html, excerpt,
+                      reading_minutes, slug, custom_slug
+               FROM posts WHERE slug = $1"#,
+            slug,
+        )
+        .fetch_optional(traced(pool))
+        .await
+        .map_err(db_error)
+    }
+
+    // Gives the post the author's own slug, or with None one generated from
+    // the title again. The previous slug keeps redirecting here. Like title
+    // and body, the slug is fixed while the post is in review or archived.
+    pub async fn set_slug(&self, slug: Option<&str>, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        let mut tx = pool.begin().await.map_err(db_error)?;
+        let update = |new_slug: String| {
+            sqlx::query_as!(
+                Post,
+                r#"UPDATE posts SET slug = $2, custom_slug = $3, updated_at = NOW(), version = version + 1
+                   WHERE id = $1 AND status NOT IN ('in_review', 'archived') AND version = $4
+                   RETURNING id, user_id, title, body, status AS "status: PostStatus", published_at,
+                             publish_at, unpublish_at, created_at, updated_at, version, language,
+                             category_id, comments AS "comments: CommentsSetting", body_html, excerpt,
+                             reading_minutes, slug, custom_slug"#,
+                self.id,
+                new_slug,
+                slug.is_some(),
+                self.version,
+            )
+        };
+        let post = match slug {
+            Some(slug) => {
+                let custom = post_slug::custom(slug, self.id, &mut tx).await?;
+                update(custom).fetch_optional(traced(&mut *tx)).await.map_err(db_error)?
+            }
+            None => with_generated_slug(&self.title, Some(self.id), &mut tx, update).await?,
+        };
+        let Some(post) = post else {
+            drop(tx);
+            return Err(self.refusal(AppError::Conflict("Posts in review or archived can't be changed".to_string()), pool).await);
+        };
+        post_slug::record_move(self.id, &self.slug, &post.slug, &mut tx)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(post)
+    }
+
+    // Files the post under a category, or none. Unlike title and body this can
+    // change in any status.
+    pub async fn set_category(&self, category_id: Option<i32>, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        let post = sqlx::query_as!(
+            Post,
+            r#"UPDATE posts SET category_id = $2, updated_at = NOW(), version = version + 1
+               WHERE id = $1 AND version = $3
+               RETURNING id, user_id, title, body, status AS "status: PostStatus", published_at,
+                         publish_at, unpublish_at, created_at, updated_at, version, language,
+                         category_id, comments AS "comments: CommentsSetting", body_html, excerpt,
+                         reading_minutes, slug, custom_slug"#,
+            self.id,
+            category_id,
+            self.version,
+        )
+        .fetch_optional(traced(pool))
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(dbe) if dbe.is_foreign_key_violation() => {
+                AppError::ValidationError("Category not found".to_string())
+            }
+            e => AppError::DatabaseError(e.to_string()),
+        })?;
+        match post {
+            Some(post) => Ok(post),
+            None => Err(self.refusal(AppError::NotFound("Post not found".to_string()), pool).await),
+        }
+    }
+
+    pub async fn set_comments(&self, setting: CommentsSetting, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        let post = sqlx::query_as!(
+            Post,
+            r#"UPDATE posts SET comments = $2, updated_at = NOW(), version = version + 1
+               WHERE id = $1 AND version = $3
+               RETURNING id, user_id, title, body, status AS "status: PostStatus", published_at,
+                         publish_at, unpublish_at, created_at, updated_at, version, language,
+                         category_id, comments AS "comments: CommentsSetting", body_html, excerpt,
+                         reading_minutes, slug, custom_slug"#,
+            self.id,
+            setting.as_str(),
+            self.version,
+        )
+        .fetch_optional(traced(pool))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        match post {
+            Some(post) => Ok(post),
+            None => Err(self.refusal(AppError::NotFound("Post not found".to_string()), pool).await),
+        }
+    }
+
+    // Moves the post along the workflow. The status is checked in the same
+    // statement, so of two concurrent transitions only one succeeds.
+    pub async fn transition(&self, transition: PostTransition, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        transition_query(self.id, transition)
+            .fetch_optional(traced(pool))
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .ok_or_else(|| transition_conflict(transition))
+    }
+
+    // Sets the embargo and expiry. Published posts keep their publication time,
+    // only `unpublish_at` can change; a scheduled post whose embargo is lifted
+    // is published right away. The job for the next step is enqueued with the change.
+    pub async fn set_schedule(&self, schedule: PostSchedule, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        let publish_at = match self.status {
+            PostStatus::InReview | PostStatus::Scheduled => schedule.publish_at,
+            PostStatus::Published if schedule.publish_at.is_none() => self.publish_at,
+            PostStatus::Published => return Err(AppError::Conflict("Post is already published".to_string())),
+            status => return Err(AppError::Conflict(format!("A {} post can't be scheduled", status))),
+        };
+        if let (Some(publish_at), Some(unpublish_at)) = (publish_at, schedule.unpublish_at)
+            && unpublish_at <= publish_at
+        {
+            return Err(AppError::ValidationError("unpublish_at must be after publish_at".to_string()));
+        }
+
+        let mut tx = pool.begin().await.map_err(db_error)?;
+        let mut post = sqlx::query_as!(
+            Post,
+            r#"UPDATE posts SET publish_at = $3, unpublish_at = $4, updated_at = NOW(), version = version + 1
+               WHERE id = $1 AND status = $2
+               RETURNING id, user_id, title, body, status AS "status: PostStatus", published_at,
+                         publish_at, unpublish_at, created_at, updated_at, version, language,
+                         category_id, comments AS "comments: CommentsSetting", body_html, excerpt,
+                         reading_minutes, slug, custom_slug"#,
+            self.id,
+            self.status.as_str(),
+            publish_at,
+            schedule.unpublish_at,
+        )
+        .fetch_optional(traced(&mut *tx))
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| AppError::Conflict("Post changed, try again".to_string()))?;
+
+        if post.status == PostStatus::Scheduled && is_due(post.publish_at) {
+            post = transition_query(self.id, PostTransition::Publish)
+                .fetch_optional(traced(&mut *tx))
+                .await
+                .map_err(db_error)?
+                .ok_or_else(|| transition_conflict(PostTransition::Publish))?;
+        }
+        jobs::schedule_post(&post, &mut tx).await?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(post)
+    }
+
+    // When the scheduler has to act on the post next: publishing a scheduled
+    // post, archiving an expiring one
+    pub fn next_scheduled(&self) -> Option<(PostTransition, DateTime<Utc>)> {
+        match self.status {
+            PostStatus::Scheduled => self.publish_at.map(|at| (PostTransition::Publish, at)),
+            PostStatus::Published => self.unpublish_at.map(|at| (PostTransition::Archive, at)),
+            _ => None,
+        }
+    }
+
+    // Runs a scheduled transition once its time has come. Jobs for times that
+    // were changed since, or posts that moved on, are a no-op: None.
+    pub async fn run_scheduled(id: i32, transition: PostTransition, pool: &Pool<Postgres>) -> Result<Option<Self>, AppError> {
+        let post = match Post::find(id, pool).await {
+            Ok(post) => post,
+            Err(AppError::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let at = match transition {
+            PostTransition::Publish => post.publish_at,
+            PostTransition::Archive => post.unpublish_at,
+            _ => return Ok(None),
+        };
+        if post.status != transition.from() || (transition == PostTransition::Archive && at.is_none()) || !is_due(at) {
+            return Ok(None);
+        }
+        // A published post may have its expiry coming up, that job goes in with the change
+        let mut tx = pool.begin().await.map_err(db_error)?;
+        let Some(post) = transition_query(id, transition)
+            .fetch_optional(traced(&mut *tx))
+            .await
+            .map_err(db_error)?
+        else {
+            return Ok(None);
+        };
+        jobs::schedule_post(&post, &mut tx).await?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(Some(post))
+    }
+
+    // An editor's approval or rejection of a submitted post, stored together
+    // with the transition it causes
+    pub async fn review(
+        &self,
+        reviewer_id: i32,
+        decision: ReviewDecision,
+        comment: Option<&str>,
+        pool: &Pool<Postgres>,
+    ) -> Result<(Self, PostReview), AppError> {
+        let transition = match decision {
+            ReviewDecision::Approved if self.publish_at.is_some_and(|at| at > Utc::now()) => PostTransition::Schedule,
+            ReviewDecision::Approved => PostTransition::Approve,
+            ReviewDecision::Rejected => PostTransition::Reject,
+        };
+
+        let mut tx = pool.begin().await.map_err(db_error)?;
+        let post = transition_query(self.id, transition)
+            .fetch_optional(traced(&mut *tx))
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| transition_conflict(transition))?;
+        let review = sqlx::query_as::<_, PostReview>(
+            "INSERT INTO post_reviews (post_id, reviewer_id, decision, comment) VALUES ($1, $2, $3, $4) RETURNING *",
+        )
+        .bind(self.id)
+        .bind(reviewer_id)
+        .bind(decision.as_str())
+        .bind(comment)
+        .fetch_one(traced(&mut *tx))
+        .await
+        .map_err(db_error)?;
+        jobs::schedule_post(&post, &mut tx).await?;
+        tx.commit().await.map_err(db_error)?;
+        Ok((post, review))
+    }
+}
+
+// Another post got the same slug at the same moment
+fn is_slug_collision(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(dbe) if dbe.constraint() == Some("posts_slug_key"))
+}
+
+// Collisions that retrying didn't get past, or an author's own slug taken
+// just before it was saved
+fn db_error(e: sqlx::Error) -> AppError {
+    if is_slug_collision(&e) {
+        return AppError::Conflict("Slug was just taken by another post, try again".to_string());
+    }
+    AppError::DatabaseError(e.to_string())
+}
+
+// Generated slugs are checked, not reserved, so a post with the same title
+// can take one between the check and the write. `query` for each slug runs
+// in a savepoint that a collision rolls back, to try again with the next
+// free slug rather than fail the whole transaction.
+async fn with_generated_slug<F>(
+    title: &str,
+    post_id: Option<i32>,
+    conn: &mut PgConnection,
+    query: impl Fn(String) -> Map<'static, Postgres, F, PgArguments>,
+) -> Result<Option<Post>, AppError>
+where
+    F: FnMut(PgRow) -> Result<Post, sqlx::Error> + Send,
+{
+    let mut attempt = 1;
+    loop {
+        let slug = post_slug::unique(title, post_id, &mut *conn).await.map_err(db_error)?;
+        let mut savepoint = conn.begin().await.map_err(db_error)?;
+        match query(slug).fetch_optional(traced(&mut *savepoint)).await {
+            Ok(written) => {
+                savepoint.commit().await.map_err(db_error)?;
+                return Ok(written);
+            }
+            Err(e) if is_slug_collision(&e) && attempt < SLUG_ATTEMPTS => {
+                savepoint.rollback().await.map_err(db_error)?;
+                attempt += 1;
+            }
+            Err(e) => return Err(db_error(e)),
+        }
+    }
+}
+
+// Renders the bodies that no or an older renderer produced the HTML for,
+// RERENDER_BATCH posts per statement, and returns how many were updated.
+// The rendering isn't content, so neither `version` nor `updated_at` move.
+pub async fn rerender_outdated(pool: &Pool<Postgres>) -> Result<u64, AppError> {
+    let mut after = 0;
+    let mut rerendered = 0;
+    loop {
+        let batch: Vec<(i32, String)> = sqlx::query_as(
+            "SELECT id, body FROM posts WHERE id > $1 AND render_version IS DISTINCT FROM $2 ORDER BY id LIMIT $3",
+        )
+        .bind(after)
+        .bind(markdown::RENDERER_VERSION)
+        .bind(RERENDER_BATCH)
+        .fetch_all(traced(pool))
+        .await
+        .map_err(db_error)?;
+        let Some((last, _)) = batch.last() else {
+            return Ok(rerendered);
+        };
+        after = *last;
+
+        let (ids, bodies): (Vec<i32>, Vec<String>) = batch.into_iter().unzip();
+        let rendered: Vec<Rendered> = bodies.iter().map(|body| markdown::render(body)).collect();
+        // A body edited meanwhile keeps the rendering it was saved with
+        let result = sqlx::query(
+            "UPDATE posts SET body_html = rendered.html, excerpt = rendered.excerpt, \
+             reading_minutes = rendered.reading_minutes, render_version = $6 \
+             FROM UNNEST($1::INTEGER[], $2::TEXT[], $3::TEXT[], $4::TEXT[], $5::INTEGER[]) \
+                 AS rendered (id, body, html, excerpt, reading_minutes) \
+             WHERE posts.id = rendered.id AND posts.body = rendered.body",
+        )
+        .bind(&ids)
+        .bind(&bodies)
+        .bind(rendered.iter().map(|r| r.html.as_str()).collect::<Vec<_>>())
+        .bind(rendered.iter().map(|r| r.excerpt.as_str()).collect::<Vec<_>>())
+        .bind(rendered.iter().map(|r| r.reading_minutes).collect::<Vec<_>>())
+        .bind(markdown::RENDERER_VERSION)
+        .execute(traced(pool))
+        .await
+        .map_err(db_error)?;
+        rerendered += result.rows_affected();
+    }
+}
+
+// A missing time counts as due, so a scheduled post without one goes live
+fn is_due(at: Option<DateTime<Utc>>) -> bool {
+    at.is_none_or(|at| at <= Utc::now())
+}
+
+fn transition_query(
+    id: i32,
+    transition: PostTransition,
+) -> Map<'static, Postgres, impl FnMut(PgRow) -> Result<Post, sqlx::Error> + Send, PgArguments> {
+    sqlx::query_as!(
+        Post,
+        r#"UPDATE posts SET status = $3, updated_at = NOW(), version = version + 1,
+           published_at = CASE WHEN $3 = 'published' THEN COALESCE(published_at, NOW()) ELSE published_at END
+           WHERE id = $1 AND status = $2
+           RETURNING id, user_id, title, body, status AS "status: PostStatus", published_at,
+                     publish_at, unpublish_at, created_at, updated_at, version, language,
+                     category_id, comments AS "comments: CommentsSetting", body_html, excerpt,
+                     reading_minutes, slug, custom_slug"#,
+        id,
+        transition.from().as_str(),
+        transition.to().as_str(),
+    )
+}
+
+fn transition_conflict(transition: PostTransition) -> AppError {
+    AppError::Conflict(format!("Post is not {}", transition.from()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewDecision {
+    Approved,
+    Rejected,
+}
+
+impl ReviewDecision {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReviewDecision::Approved => "approved",
+            ReviewDecision::Rejected => "rejected",
+        }
+    }
+}
+
+// An editor's decision on a submitted post
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PostReview {
+    pub id: i64,
+    pub post_id: i32,
+    pub reviewer_id: Option<i32>,
+    pub decision: String,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PostReview {
+    // Oldest first
+    pub async fn for_post(post_id: i32, pool: &Pool<Postgres>) -> Result<Vec<Self>, AppError> {
+        sqlx::query_as::<_, PostReview>("SELECT * FROM post_reviews WHERE post_id = $1 ORDER BY id")
+            .bind(post_id)
+            .fetch_all(traced(pool))
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+}
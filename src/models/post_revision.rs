@@ -0,0 +1,106 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use sqlx::{PgConnection, Pool, Postgres};
+
+use crate::db::traced;
+use crate::services::error::AppError;
+
+// A saved version of a post's title and body. Revisions are numbered per
+// post, starting at 1 when the post is created.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PostRevision {
+    pub id: i64,
+    pub post_id: i32,
+    pub revision: i32,
+    pub title: String,
+    pub body: String,
+    pub author_id: Option<i32>,
+    pub restored_from: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+// What changed between two revisions, the body as a unified diff
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionDiff {
+    pub from: i32,
+    pub to: i32,
+    pub title_before: String,
+    pub title_after: String,
+    pub diff: String,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+impl PostRevision {
+    // Stores the post's content as its next revision. Runs on the connection of
+    // the statement that changed the post, whose row lock keeps the numbers
+    // from colliding.
+    pub(crate) async fn record(
+        post_id: i32,
+        title: &str,
+        body: &str,
+        author_id: i32,
+        restored_from: Option<i32>,
+        conn: &mut PgConnection,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as::<_, PostRevision>(
+            "INSERT INTO post_revisions (post_id, revision, title, body, author_id, restored_from) \
+             SELECT $1, COALESCE(MAX(revision), 0) + 1, $2, $3, $4, $5 FROM post_revisions WHERE post_id = $1 \
+             RETURNING *",
+        )
+        .bind(post_id)
+        .bind(title)
+        .bind(body)
+        .bind(author_id)
+        .bind(restored_from)
+        .fetch_one(traced(conn))
+        .await
+    }
+
+    // Newest first
+    pub async fn for_post(post_id: i32, pool: &Pool<Postgres>) -> Result<Vec<Self>, AppError> {
+        sqlx::query_as::<_, PostRevision>("SELECT * FROM post_revisions WHERE post_id = $1 ORDER BY revision DESC")
+            .bind(post_id)
+            .fetch_all(traced(pool))
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
+    pub async fn find(post_id: i32, revision: i32, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        sqlx::query_as::<_, PostRevision>("SELECT * FROM post_revisions WHERE post_id = $1 AND revision = $2")
+            .bind(post_id)
+            .bind(revision)
+            .fetch_optional(traced(pool))
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .ok_or_else(|| AppError::NotFound(format!("Revision {} not found", revision)))
+    }
+
+    // Line diff of the bodies, from this revision to `other`
+    pub fn diff(&self, other: &PostRevision) -> RevisionDiff {
+        let diff = TextDiff::from_lines(&self.body, &other.body);
+        let (mut additions, mut deletions) = (0, 0);
+        for change in diff.iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Insert => additions += 1,
+                ChangeTag::Delete => deletions += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+        let unified = diff
+            .unified_diff()
+            .header(&format!("revision {}", self.revision), &format!("revision {}", other.revision))
+            .to_string();
+
+        RevisionDiff {
+            from: self.revision,
+            to: other.revision,
+            title_before: self.title.clone(),
+            title_after: other.title.clone(),
+            diff: unified,
+            additions,
+            deletions,
+        }
+    }
+}
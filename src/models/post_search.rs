@@ -0,0 +1,208 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres, QueryBuilder};
+
+use crate::db::traced;
+use crate::models::post::{PostStatus, Viewer};
+use crate::services::error::AppError;
+
+const HIGHLIGHT: &str = "StartSel=<mark>, StopSel=</mark>, HighlightAll=true";
+const SNIPPET: &str = "StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=30, MinWords=10, FragmentDelimiter=\" … \"";
+// Deeper pages are clamped, so the offset can't overflow
+const MAX_PAGE: i64 = 100_000;
+
+// `column` with the characters HTML treats specially escaped, so that the
+// only markup in a headline is the <mark> around the matches
+fn html_escaped(column: &str) -> String {
+    format!(
+        "replace(replace(replace(replace(replace({}, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '\"', '&quot;'), '''', '&#39;')",
+        column
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    // Text search configuration for the query, defaults to SEARCH_LANGUAGE
+    pub lang: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+// A matching post, best first. `title` and `snippet` are HTML: the post's
+// text escaped, with the matches wrapped in <mark>.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SearchHit {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    #[sqlx(try_from = "String")]
+    pub status: PostStatus,
+    pub published_at: Option<DateTime<Utc>>,
+    pub title: String,
+    pub snippet: String,
+    pub rank: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct TitleSuggestion {
+    pub id: i32,
+    pub title: String,
+}
+
+// A part of the search text: `word`, `prefix*`, `"a phrase"` or `-excluded`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Word(String),
+    Prefix(String),
+    Phrase(String),
+    Exclude(String),
+}
+
+// Splits the search text into terms, all of which must match. An unclosed
+// quote runs to the end of the text.
+pub fn parse_query(q: &str) -> Vec<Term> {
+    let mut terms = Vec::new();
+    let mut rest = q.trim();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let (phrase, after) = quoted.split_once('"').unwrap_or((quoted, ""));
+            if !phrase.trim().is_empty() {
+                terms.push(Term::Phrase(phrase.trim().to_string()));
+            }
+            rest = after.trim_start();
+            continue;
+        }
+
+        let (word, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        rest = after.trim_start();
+        if let Some(excluded) = word.strip_prefix('-').filter(|w| !w.is_empty()) {
+            terms.push(Term::Exclude(excluded.to_string()));
+        } else if let Some(prefix) = word.strip_suffix('*') {
+            // to_tsquery has its own syntax, only letters and digits go in
+            let prefix: String = prefix.chars().filter(|c| c.is_alphanumeric()).collect();
+            if !prefix.is_empty() {
+                terms.push(Term::Prefix(prefix));
+            }
+        } else {
+            terms.push(Term::Word(word.to_string()));
+        }
+    }
+    terms
+}
+
+// Appends the tsquery matching all `terms`, parsed with `language`
+fn push_tsquery(builder: &mut QueryBuilder<'_, Postgres>, terms: &[Term], language: &str) {
+    for (i, term) in terms.iter().enumerate() {
+        if i > 0 {
+            builder.push(" && ");
+        }
+        let (function, text) = match term {
+            Term::Word(word) => ("plainto_tsquery(", word.clone()),
+            Term::Prefix(prefix) => ("to_tsquery(", format!("{}:*", prefix)),
+            Term::Phrase(phrase) => ("phraseto_tsquery(", phrase.clone()),
+            Term::Exclude(word) => ("!! plainto_tsquery(", word.clone()),
+        };
+        builder.push(function).push_bind(language.to_string()).push("::REGCONFIG, ").push_bind(text).push(")");
+    }
+}
+
+// Same rule as `Post::list`
+fn push_visible(builder: &mut QueryBuilder<'_, Postgres>, viewer: Viewer) {
+    builder
+        .push(" AND (status = 'published' OR user_id = ")
+        .push_bind(viewer.user_id)
+        .push(" OR (")
+        .push_bind(viewer.is_editor)
+        .push(" AND status <> 'draft'))");
+}
+
+// Errors unless Postgres has a text search configuration of that name
+pub async fn ensure_language(language: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_ts_config WHERE cfgname = $1)")
+        .bind(language)
+        .fetch_one(traced(pool))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if !exists {
+        return Err(AppError::ValidationError(format!("Unknown search language: {}", language)));
+    }
+    Ok(())
+}
+
+// Full-text search over the posts `viewer` may see, ranked by how well
+// and how densely they match, title matches first
+pub async fn search(
+    query: &SearchQuery,
+    language: &str,
+    viewer: Viewer,
+    pool: &Pool<Postgres>,
+) -> Result<SearchResults, AppError> {
+    let page = query.page.unwrap_or(1).clamp(1, MAX_PAGE);
+    let per_page = query.per_page.unwrap_or(20).clamp(1, 100);
+    let terms = parse_query(&query.q);
+    if terms.is_empty() {
+        return Err(AppError::ValidationError("Search text must not be empty".to_string()));
+    }
+    ensure_language(language, pool).await?;
+    let db_error = |e: sqlx::Error| AppError::DatabaseError(e.to_string());
+
+    let mut count = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM posts WHERE search_vector @@ (");
+    push_tsquery(&mut count, &terms, language);
+    count.push(")");
+    push_visible(&mut count, viewer);
+    let total: i64 = count.build_query_scalar().fetch_one(traced(pool)).await.map_err(db_error)?;
+
+    let mut select = QueryBuilder::<Postgres>::new("WITH query AS (SELECT ");
+    push_tsquery(&mut select, &terms, language);
+    select
+        .push(" AS q) SELECT id, user_id, status, published_at, ts_headline(")
+        .push_bind(language.to_string())
+        .push(format!("::REGCONFIG, {}, query.q, ", html_escaped("title")))
+        .push_bind(HIGHLIGHT)
+        .push(") AS title, ts_headline(")
+        .push_bind(language.to_string())
+        .push(format!("::REGCONFIG, {}, query.q, ", html_escaped("body")))
+        .push_bind(SNIPPET)
+        .push(") AS snippet, ts_rank_cd(search_vector, query.q) AS rank FROM posts, query WHERE search_vector @@ query.q");
+    push_visible(&mut select, viewer);
+    select
+        .push(" ORDER BY rank DESC, id DESC LIMIT ")
+        .push_bind(per_page)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * per_page);
+    let hits = select.build_query_as::<SearchHit>().fetch_all(traced(pool)).await.map_err(db_error)?;
+
+    Ok(SearchResults { hits, total, page, per_page })
+}
+
+// Titles resembling what the user typed so far, by trigram word similarity,
+// so partly typed or misspelt words still match
+pub async fn suggest_titles(
+    prefix: &str,
+    limit: i64,
+    viewer: Viewer,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<TitleSuggestion>, AppError> {
+    let mut builder = QueryBuilder::<Postgres>::new("SELECT id, title FROM posts WHERE ");
+    builder.push_bind(prefix.to_string()).push(" <% title");
+    push_visible(&mut builder, viewer);
+    builder
+        .push(" ORDER BY word_similarity(")
+        .push_bind(prefix.to_string())
+        .push(", title) DESC, id DESC LIMIT ")
+        .push_bind(limit.clamp(1, 50));
+    builder
+        .build_query_as::<TitleSuggestion>()
+        .fetch_all(traced(pool))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))
+}
+
@@ -0,0 +1,100 @@
+use sqlx::{PgConnection, Pool, Postgres};
+
+use crate::db::traced;
+use crate::services::error::AppError;
+use crate::slug::slugify;
+
+// Slugs for titles without a single letter or digit
+const FALLBACK: &str = "post";
+
+// Whether a post other than `post_id` uses or used `slug`
+async fn is_taken(slug: &str, post_id: Option<i32>, conn: &mut PgConnection) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM posts WHERE slug = $1 AND id IS DISTINCT FROM $2) \
+         OR EXISTS (SELECT 1 FROM post_slug_redirects WHERE slug = $1 AND post_id IS DISTINCT FROM $2)",
+    )
+    .bind(slug)
+    .bind(post_id)
+    .fetch_one(traced(conn))
+    .await
+}
+
+// The lowest n from 2 up for which no post other than `post_id` uses or
+// used "`base`-n". Worked out by the database from the prefix indexes, so
+// only the suffixes' gap comes back rather than every "`base`-%" slug.
+async fn free_suffix(base: &str, post_id: Option<i32>, conn: &mut PgConnection) -> Result<i64, sqlx::Error> {
+    // Slugs consist of [a-z0-9-] only, nothing LIKE would treat specially
+    sqlx::query_scalar(
+        "WITH suffixes AS ( \
+             SELECT substring(slug FROM char_length($1) + 2) AS suffix FROM posts \
+             WHERE slug LIKE $1 || '-%' AND id IS DISTINCT FROM $2 \
+             UNION \
+             SELECT substring(slug FROM char_length($1) + 2) FROM post_slug_redirects \
+             WHERE slug LIKE $1 || '-%' AND post_id IS DISTINCT FROM $2 \
+         ), used AS ( \
+             SELECT CASE WHEN suffix ~ '^[1-9][0-9]{0,17}$' THEN suffix::BIGINT END AS n FROM suffixes \
+         ) \
+         SELECT MIN(n + 1) FROM (SELECT 1::BIGINT AS n UNION SELECT n FROM used WHERE n IS NOT NULL) ends \
+         WHERE NOT EXISTS (SELECT 1 FROM used WHERE used.n = ends.n + 1)",
+    )
+    .bind(base)
+    .bind(post_id)
+    .fetch_one(traced(conn))
+    .await
+}
+
+// A slug for `title` no other post uses or used: "my-title", else "my-title-2",
+// "my-title-3" and so on. A post may get back one of its own earlier slugs.
+pub(crate) async fn unique(title: &str, post_id: Option<i32>, conn: &mut PgConnection) -> Result<String, sqlx::Error> {
+    let base = Some(slugify(title)).filter(|slug| !slug.is_empty()).unwrap_or_else(|| FALLBACK.to_string());
+    if !is_taken(&base, post_id, &mut *conn).await? {
+        return Ok(base);
+    }
+    let suffix = free_suffix(&base, post_id, conn).await?;
+    Ok(format!("{}-{}", base, suffix))
+}
+
+// A slug chosen by the author, normalized the same way generated ones are.
+// Unlike a generated slug it is refused when taken.
+pub(crate) async fn custom(slug: &str, post_id: i32, conn: &mut PgConnection) -> Result<String, AppError> {
+    let slug = slugify(slug);
+    if slug.is_empty() {
+        return Err(AppError::ValidationError("Slug must contain letters or digits".to_string()));
+    }
+    let taken = is_taken(&slug, Some(post_id), conn)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if taken {
+        return Err(AppError::Conflict("Slug is already taken".to_string()));
+    }
+    Ok(slug)
+}
+
+// Keeps `old` leading to the post after it moved to `new`
+pub(crate) async fn record_move(post_id: i32, old: &str, new: &str, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    if old == new {
+        return Ok(());
+    }
+    sqlx::query("DELETE FROM post_slug_redirects WHERE slug = $1")
+        .bind(new)
+        .execute(traced(&mut *conn))
+        .await?;
+    sqlx::query(
+        "INSERT INTO post_slug_redirects (slug, post_id) VALUES ($1, $2) \
+         ON CONFLICT (slug) DO UPDATE SET post_id = EXCLUDED.post_id, created_at = NOW()",
+    )
+    .bind(old)
+    .bind(post_id)
+    .execute(traced(conn))
+    .await?;
+    Ok(())
+}
+
+// The post an earlier slug belonged to
+pub async fn redirect(slug: &str, pool: &Pool<Postgres>) -> Result<Option<i32>, AppError> {
+    sqlx::query_scalar("SELECT post_id FROM post_slug_redirects WHERE slug = $1")
+        .bind(slug)
+        .fetch_optional(traced(pool))
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))
+}
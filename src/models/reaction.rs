@@ -0,0 +1,162 @@
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::db::traced;
+use crate::models::post::{CommentsSetting, Post, PostStatus};
+use crate::services::error::AppError;
+
+// What a post's readers did with it, as seen by one of them
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReactionSummary {
+    // Count per reaction, reactions nobody left are missing
+    pub reactions: BTreeMap<String, i64>,
+    // The caller's own reactions
+    pub my_reactions: Vec<String>,
+    pub bookmarked: bool,
+}
+
+// A post in responses, with its reactions
+#[derive(Debug, Clone, Serialize)]
+pub struct PostWithReactions {
+    #[serde(flatten)]
+    pub post: Post,
+    #[serde(flatten)]
+    pub summary: ReactionSummary,
+}
+
+#[derive(sqlx::FromRow)]
+struct ReactionCount {
+    post_id: i32,
+    reaction: String,
+    count: i32,
+}
+
+#[derive(sqlx::FromRow)]
+struct OwnReaction {
+    post_id: i32,
+    reaction: String,
+}
+
+fn db_error(e: sqlx::Error) -> AppError {
+    AppError::DatabaseError(e.to_string())
+}
+
+// Adds `reaction` to `post_id` for `user_id`; reacting twice is a no-op.
+// The counters follow through a trigger.
+pub async fn add(post_id: i32, user_id: i32, reaction: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+    sqlx::query("INSERT INTO post_reactions (post_id, user_id, reaction) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING")
+        .bind(post_id)
+        .bind(user_id)
+        .bind(reaction)
+        .execute(traced(pool))
+        .await
+        .map_err(db_error)?;
+    Ok(())
+}
+
+// Removing a reaction that isn't there is a no-op as well
+pub async fn remove(post_id: i32, user_id: i32, reaction: &str, pool: &Pool<Postgres>) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM post_reactions WHERE post_id = $1 AND user_id = $2 AND reaction = $3")
+        .bind(post_id)
+        .bind(user_id)
+        .bind(reaction)
+        .execute(traced(pool))
+        .await
+        .map_err(db_error)?;
+    Ok(())
+}
+
+pub async fn bookmark(post_id: i32, user_id: i32, pool: &Pool<Postgres>) -> Result<(), AppError> {
+    sqlx::query("INSERT INTO bookmarks (user_id, post_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(user_id)
+        .bind(post_id)
+        .execute(traced(pool))
+        .await
+        .map_err(db_error)?;
+    Ok(())
+}
+
+pub async fn unbookmark(post_id: i32, user_id: i32, pool: &Pool<Postgres>) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM bookmarks WHERE user_id = $1 AND post_id = $2")
+        .bind(user_id)
+        .bind(post_id)
+        .execute(traced(pool))
+        .await
+        .map_err(db_error)?;
+    Ok(())
+}
+
+// The posts `user_id` bookmarked and can still see, most recently bookmarked first
+pub async fn bookmarked_posts(user_id: i32, is_editor: bool, pool: &Pool<Postgres>) -> Result<Vec<Post>, AppError> {
+    sqlx::query_as!(
+        Post,
+        r#"SELECT posts.id, posts.user_id, posts.title, posts.body, posts.status AS "status: PostStatus", posts.published_at,
+                  posts.publish_at, posts.unpublish_at, posts.created_at, posts.updated_at, posts.version, posts.language,
+                  posts.category_id, posts.comments AS "comments: CommentsSetting", posts.body_html, posts.excerpt,
+                  posts.reading_minutes, posts.slug, posts.custom_slug
+           FROM bookmarks JOIN posts ON posts.id = bookmarks.post_id
+           WHERE bookmarks.user_id = $1
+           AND (posts.status = 'published' OR posts.user_id = $1 OR ($2 AND posts.status <> 'draft'))
+           ORDER BY bookmarks.created_at DESC, posts.id DESC"#,
+        user_id,
+        is_editor,
+    )
+    .fetch_all(traced(pool))
+    .await
+    .map_err(db_error)
+}
+
+// Attaches the reaction counts and `user_id`'s own reactions and bookmarks.
+// Three index lookups, however many posts there are.
+pub async fn with_reactions(posts: Vec<Post>, user_id: i32, pool: &Pool<Postgres>) -> Result<Vec<PostWithReactions>, AppError> {
+    if posts.is_empty() {
+        return Ok(Vec::new());
+    }
+    let ids: Vec<i32> = posts.iter().map(|post| post.id).collect();
+
+    let counts = sqlx::query_as::<_, ReactionCount>(
+        "SELECT post_id, reaction, count FROM post_reaction_counts WHERE post_id = ANY($1)",
+    )
+    .bind(&ids)
+    .fetch_all(traced(pool))
+    .await
+    .map_err(db_error)?;
+    let own = sqlx::query_as::<_, OwnReaction>(
+        "SELECT post_id, reaction FROM post_reactions WHERE user_id = $1 AND post_id = ANY($2) ORDER BY created_at",
+    )
+    .bind(user_id)
+    .bind(&ids)
+    .fetch_all(traced(pool))
+    .await
+    .map_err(db_error)?;
+    let bookmarked: Vec<i32> = sqlx::query_scalar("SELECT post_id FROM bookmarks WHERE user_id = $1 AND post_id = ANY($2)")
+        .bind(user_id)
+        .bind(&ids)
+        .fetch_all(traced(pool))
+        .await
+        .map_err(db_error)?;
+
+    let mut summaries: HashMap<i32, ReactionSummary> = HashMap::new();
+    for count in counts {
+        summaries.entry(count.post_id).or_default().reactions.insert(count.reaction, count.count.into());
+    }
+    for reaction in own {
+        summaries.entry(reaction.post_id).or_default().my_reactions.push(reaction.reaction);
+    }
+    for post_id in bookmarked {
+        summaries.entry(post_id).or_default().bookmarked = true;
+    }
+    Ok(posts
+        .into_iter()
+        .map(|post| {
+            let summary = summaries.remove(&post.id).unwrap_or_default();
+            PostWithReactions { post, summary }
+        })
+        .collect())
+}
+
+pub async fn post_with_reactions(post: Post, user_id: i32, pool: &Pool<Postgres>) -> Result<PostWithReactions, AppError> {
+    let mut posts = with_reactions(vec![post], user_id, pool).await?;
+    Ok(posts.remove(0))
+}
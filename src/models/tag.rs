@@ -0,0 +1,171 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+
+use crate::db::traced;
+use crate::services::error::AppError;
+
+const MAX_NAME_LEN: usize = 50;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// A tag with the number of published posts carrying it, for tag clouds
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct TagCount {
+    pub id: i32,
+    pub name: String,
+    pub post_count: i64,
+}
+
+// Tags are compared and stored trimmed and lowercase, so "Rust" and "rust " are one tag
+pub fn normalize(name: &str) -> Result<String, AppError> {
+    let name = name.trim().to_lowercase();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN || name.contains(',') {
+        return Err(AppError::ValidationError(format!(
+            "Tag names must be 1 to {} characters without commas",
+            MAX_NAME_LEN
+        )));
+    }
+    Ok(name)
+}
+
+fn db_error(e: sqlx::Error) -> AppError {
+    if let sqlx::Error::Database(ref dbe) = e
+        && dbe.is_unique_violation()
+    {
+        return AppError::Conflict("A tag with this name already exists".to_string());
+    }
+    AppError::DatabaseError(e.to_string())
+}
+
+impl Tag {
+    pub async fn create(name: &str, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        sqlx::query_as::<_, Tag>("INSERT INTO tags (name) VALUES ($1) RETURNING *")
+            .bind(normalize(name)?)
+            .fetch_one(traced(pool))
+            .await
+            .map_err(db_error)
+    }
+
+    pub async fn find(id: i32, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        sqlx::query_as::<_, Tag>("SELECT * FROM tags WHERE id = $1")
+            .bind(id)
+            .fetch_optional(traced(pool))
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| AppError::NotFound("Tag not found".to_string()))
+    }
+
+    // All tags, the most used first
+    pub async fn counts(pool: &Pool<Postgres>) -> Result<Vec<TagCount>, AppError> {
+        sqlx::query_as::<_, TagCount>(
+            "SELECT tags.id, tags.name, COUNT(posts.id) AS post_count FROM tags \
+             LEFT JOIN post_tags ON post_tags.tag_id = tags.id \
+             LEFT JOIN posts ON posts.id = post_tags.post_id AND posts.status = 'published' \
+             GROUP BY tags.id ORDER BY post_count DESC, tags.name",
+        )
+        .fetch_all(traced(pool))
+        .await
+        .map_err(db_error)
+    }
+
+    // Removes the tag from all posts
+    pub async fn delete(&self, pool: &Pool<Postgres>) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM tags WHERE id = $1")
+            .bind(self.id)
+            .execute(traced(pool))
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    // Fails with a conflict if the new name is taken; merge the two instead
+    pub async fn rename(&self, name: &str, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        sqlx::query_as::<_, Tag>("UPDATE tags SET name = $2 WHERE id = $1 RETURNING *")
+            .bind(self.id)
+            .bind(normalize(name)?)
+            .fetch_optional(traced(pool))
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| AppError::NotFound("Tag not found".to_string()))
+    }
+
+    // Moves this tag's posts to `into` and deletes this tag. Returns how many
+    // posts gained the other tag; posts that had both keep one.
+    pub async fn merge(&self, into: &Tag, pool: &Pool<Postgres>) -> Result<u64, AppError> {
+        if self.id == into.id {
+            return Err(AppError::ValidationError("A tag can't be merged into itself".to_string()));
+        }
+        let mut tx = pool.begin().await.map_err(db_error)?;
+        let moved = sqlx::query(
+            "INSERT INTO post_tags (post_id, tag_id) SELECT post_id, $2 FROM post_tags WHERE tag_id = $1 \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(self.id)
+        .bind(into.id)
+        .execute(traced(&mut *tx))
+        .await
+        .map_err(db_error)?
+        .rows_affected();
+        let deleted = sqlx::query("DELETE FROM tags WHERE id = $1")
+            .bind(self.id)
+            .execute(traced(&mut *tx))
+            .await
+            .map_err(db_error)?
+            .rows_affected();
+        if deleted == 0 {
+            return Err(AppError::NotFound("Tag not found".to_string()));
+        }
+        tx.commit().await.map_err(db_error)?;
+        Ok(moved)
+    }
+
+    // Alphabetical
+    pub async fn for_post(post_id: i32, pool: &Pool<Postgres>) -> Result<Vec<Self>, AppError> {
+        sqlx::query_as::<_, Tag>(
+            "SELECT tags.* FROM tags JOIN post_tags ON post_tags.tag_id = tags.id \
+             WHERE post_tags.post_id = $1 ORDER BY tags.name",
+        )
+        .bind(post_id)
+        .fetch_all(traced(pool))
+        .await
+        .map_err(db_error)
+    }
+
+    // Replaces the post's tags, creating the tags that don't exist yet
+    pub async fn set_for_post(post_id: i32, names: &[String], pool: &Pool<Postgres>) -> Result<Vec<Self>, AppError> {
+        let mut names = names.iter().map(|name| normalize(name)).collect::<Result<Vec<_>, _>>()?;
+        names.sort();
+        names.dedup();
+
+        let mut tx = pool.begin().await.map_err(db_error)?;
+        sqlx::query("INSERT INTO tags (name) SELECT UNNEST($1::TEXT[]) ON CONFLICT (name) DO NOTHING")
+            .bind(&names)
+            .execute(traced(&mut *tx))
+            .await
+            .map_err(db_error)?;
+        sqlx::query("DELETE FROM post_tags WHERE post_id = $1")
+            .bind(post_id)
+            .execute(traced(&mut *tx))
+            .await
+            .map_err(db_error)?;
+        let tags = sqlx::query_as::<_, Tag>(
+            "WITH added AS ( \
+                 INSERT INTO post_tags (post_id, tag_id) SELECT $1, id FROM tags WHERE name = ANY($2) RETURNING tag_id \
+             ) \
+             SELECT tags.* FROM tags JOIN added ON added.tag_id = tags.id ORDER BY tags.name",
+        )
+        .bind(post_id)
+        .bind(&names)
+        .fetch_all(traced(&mut *tx))
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(tags)
+    }
+}
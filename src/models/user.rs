@@ -1,6 +1,8 @@
 use argon2::PasswordHash;
 use crate::auth::{hash_password, verify_password};
+use crate::auth::rbac::Role;
 use crate::services::error::AppError;
+use crate::db::traced;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Postgres, Row, postgres::PgRow};
@@ -20,10 +22,7 @@ impl<'c> sqlx::FromRow<'c, PgRow> for User {
         let email: String = row.try_get("email")?;
 
         // Handle created_at which might be missing or in a different format
-        let created_at: Option<DateTime<Utc>> = match row.try_get("created_at") {
-            Ok(dt) => Some(dt),
-            Err(_) => None,
-        };
+        let created_at: Option<DateTime<Utc>> = row.try_get("created_at").ok();
 
         Ok(User {
             id,
@@ -87,21 +86,20 @@ impl User {
             .bind(&new_user.username)
             .bind(&new_user.email)
             .bind(hash_password(new_user.password))
-            .fetch_one(pool)
+            .fetch_one(traced(pool))
             .await;
 
         match user {
             Ok(user) => Ok(user),
             Err(e) => {
                 // Handle constraint violations specifically
-                if let sqlx::Error::Database(ref dbe) = e {
-                    if let Some(constraint) = dbe.constraint() {
-                        if constraint.contains("username") || constraint.contains("email") {
-                            return Err(AppError::ValidationError(
-                                "Username or email already exists".to_string(),
-                            ));
-                        }
-                    }
+                if let sqlx::Error::Database(ref dbe) = e
+                    && let Some(constraint) = dbe.constraint()
+                    && (constraint.contains("username") || constraint.contains("email"))
+                {
+                    return Err(AppError::ValidationError(
+                        "Username or email already exists".to_string(),
+                    ));
                 }
 
                 // Other database errors become internal errors
@@ -120,7 +118,7 @@ impl User {
             "SELECT id, username, email, created_at FROM users WHERE username = $1",
         )
         .bind(username)
-        .fetch_optional(pool)
+        .fetch_optional(traced(pool))
         .await
         .map_err(|_| AppError::InternalServerError)?
         .ok_or(AppError::AuthenticationFailed)?;
@@ -130,7 +128,7 @@ impl User {
             "SELECT password_hash FROM users WHERE username = $1",
         )
         .bind(username)
-        .fetch_one(pool)
+        .fetch_one(traced(pool))
         .await
         .map_err(|_| AppError::InternalServerError)?
         .unwrap_or_default();
@@ -142,11 +140,76 @@ impl User {
             "SELECT id, username, email, created_at FROM users WHERE id = $1",
         )
         .bind(id)
-        .fetch_optional(pool)
+        .fetch_optional(traced(pool))
         .await
         .map_err(|_| AppError::InternalServerError)?
         .ok_or(AppError::NotFound("User not found".to_string()))?;
 
         Ok(user)
     }
+
+    pub async fn find_by_username(username: &str, pool: &Pool<Postgres>) -> Result<Self, AppError> {
+        sqlx::query_as::<_, User>(
+            "SELECT id, username, email, created_at FROM users WHERE username = $1",
+        )
+        .bind(username)
+        .fetch_optional(traced(pool))
+        .await
+        .map_err(|_| AppError::InternalServerError)?
+        .ok_or(AppError::NotFound("User not found".to_string()))
+    }
+
+    pub async fn set_password(&self, password: String, pool: &Pool<Postgres>) -> Result<(), AppError> {
+        if !is_strong_password(&password) {
+            return Err(AppError::ValidationError(
+                "Password must be at least 12 characters".to_string()
+            ));
+        }
+
+        sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+            .bind(hash_password(password))
+            .bind(self.id)
+            .execute(traced(pool))
+            .await
+            .map_err(|_| AppError::InternalServerError)?;
+        Ok(())
+    }
+
+    // Every user has the "user" role, other roles are granted explicitly
+    pub async fn roles(&self, pool: &Pool<Postgres>) -> Result<Vec<String>, AppError> {
+        let mut roles = vec![Role::User.to_string()];
+        let granted = sqlx::query_scalar::<_, String>(
+            "SELECT role FROM user_roles WHERE user_id = $1 ORDER BY role",
+        )
+        .bind(self.id)
+        .fetch_all(traced(pool))
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+        roles.extend(granted.into_iter().filter(|r| *r != Role::User.to_string()));
+        Ok(roles)
+    }
+
+    // Returns false if the user already had the role
+    pub async fn grant_role(&self, role: &Role, pool: &Pool<Postgres>) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "INSERT INTO user_roles (user_id, role) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(self.id)
+        .bind(role.to_string())
+        .execute(traced(pool))
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Returns false if the user did not have the role
+    pub async fn revoke_role(&self, role: &Role, pool: &Pool<Postgres>) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM user_roles WHERE user_id = $1 AND role = $2")
+            .bind(self.id)
+            .bind(role.to_string())
+            .execute(traced(pool))
+            .await
+            .map_err(|_| AppError::InternalServerError)?;
+        Ok(result.rows_affected() > 0)
+    }
 }
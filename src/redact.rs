@@ -0,0 +1,137 @@
+use hmac::{Hmac, Mac};
+use regex::{Captures, Regex};
+use serde_json::Value;
+use sha2::Sha256;
+use std::borrow::Cow;
+use std::io;
+use std::sync::OnceLock;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::config::RedactionConfig;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+// Masks personal data and credentials in log output and audit details, following
+// the configured rules. One instance is installed for the whole process because
+// log lines are written from places that have no access to the application state.
+pub struct Redactor {
+    email: Option<Regex>,
+    credentials: Regex,
+    query_params: Option<Regex>,
+    fields: Vec<String>,
+    pseudonymize_key: Option<Vec<u8>>,
+}
+
+static REDACTOR: OnceLock<Redactor> = OnceLock::new();
+
+// Installs the rules, the first call wins
+pub fn init(config: &RedactionConfig) {
+    let _ = REDACTOR.set(Redactor::new(config));
+}
+
+// The installed rules, or the defaults when `init` was never called
+pub fn redactor() -> &'static Redactor {
+    REDACTOR.get_or_init(|| Redactor::new(&RedactionConfig::default()))
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> Self {
+        let query_params = (!config.query_params.is_empty()).then(|| {
+            let names: Vec<String> = config.query_params.iter().map(|name| regex::escape(name)).collect();
+            Regex::new(&format!(r#"(?i)([?&](?:{})=)[^&\s#"]*"#, names.join("|"))).unwrap()
+        });
+
+        Self {
+            email: config
+                .emails
+                .then(|| Regex::new(r"([A-Za-z0-9._%+-])[A-Za-z0-9._%+-]*@([A-Za-z0-9.-]+\.[A-Za-z]{2,})").unwrap()),
+            // Bearer credentials and anything shaped like a JWT
+            credentials: Regex::new(r"(?i)\bbearer\s+[A-Za-z0-9._~+/=-]+|\beyJ[A-Za-z0-9_-]*\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*")
+                .unwrap(),
+            query_params,
+            fields: config.fields.iter().map(|field| field.to_lowercase()).collect(),
+            pseudonymize_key: config.pseudonymize_key.as_ref().map(|key| key.as_bytes().to_vec()),
+        }
+    }
+
+    // Masks emails, credentials and the configured query parameters in free text
+    pub fn text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        if let Some(query_params) = &self.query_params {
+            text = replace(text, query_params, |caps| format!("{}{}", &caps[1], REDACTED));
+        }
+        text = replace(text, &self.credentials, |_| REDACTED.to_string());
+        if let Some(email) = &self.email {
+            text = replace(text, email, |caps| format!("{}***@{}", &caps[1], &caps[2]));
+        }
+        text
+    }
+
+    // Replaces the values of the configured fields, and masks strings like `text` does
+    pub fn json(&self, value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                for (name, value) in fields.iter_mut() {
+                    if self.fields.contains(&name.to_lowercase()) {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.json(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.json(item)),
+            Value::String(text) => {
+                if let Cow::Owned(redacted) = self.text(text) {
+                    *text = redacted;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // A user id as it may appear in logs: unchanged, or a stable keyed pseudonym
+    pub fn user(&self, user_id: &str) -> String {
+        match &self.pseudonymize_key {
+            Some(key) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+                mac.update(user_id.as_bytes());
+                let digest = mac.finalize().into_bytes();
+                format!("u_{}", hex::encode(&digest[..8]))
+            }
+            None => user_id.to_string(),
+        }
+    }
+}
+
+fn replace<'a>(text: Cow<'a, str>, pattern: &Regex, replacement: impl Fn(&Captures) -> String) -> Cow<'a, str> {
+    if !pattern.is_match(&text) {
+        return text;
+    }
+    Cow::Owned(pattern.replace_all(&text, |caps: &Captures| replacement(caps)).into_owned())
+}
+
+// Wraps a log writer so every formatted line passes through the redactor
+pub struct RedactingWriter<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingWriter<M> {
+    type Writer = Redacting<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        Redacting(self.0.make_writer())
+    }
+}
+
+pub struct Redacting<W>(W);
+
+impl<W: io::Write> io::Write for Redacting<W> {
+    // The fmt layer writes each event with a single call
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let line = String::from_utf8_lossy(buf);
+        self.0.write_all(redactor().text(&line).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
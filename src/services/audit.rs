@@ -0,0 +1,19 @@
+use axum::extract::{Query, State};
+use axum::Json;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+
+use crate::models::audit_record::{AuditRecord, AuditFilter};
+use crate::services::error::AppError;
+
+// Searches the audit log, newest first. `next_before` is set when there may be more pages.
+pub async fn get_audit_events(
+    State(pool): State<Pool<Postgres>>,
+    Query(filter): Query<AuditFilter>,
+) -> Result<Json<Value>, AppError> {
+    let events = AuditRecord::search(&filter, &pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let next_before = events.last().map(|event| event.id);
+    Ok(Json(json!({ "events": events, "next_before": next_before })))
+}
@@ -0,0 +1,73 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::{Pool, Postgres};
+
+use crate::audit::{AuditEvent, Auditor};
+use crate::auth::jwt::Claims;
+use crate::auth::rbac::{ensure_role, Role};
+use crate::models::category::{Category, NewCategory};
+use crate::services::error::AppError;
+
+// The category tree as a flat list, parents before their children
+pub async fn get_categories(State(pool): State<Pool<Postgres>>) -> Result<Json<Vec<Category>>, AppError> {
+    Ok(Json(Category::list(&pool).await?))
+}
+
+pub async fn create_category(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Json(payload): Json<NewCategory>,
+) -> Result<(StatusCode, Json<Category>), AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    let category = Category::create(payload, &pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("category.created")
+                .resource(format!("category:{}", category.id))
+                .change(None, Some(&category)),
+        )
+        .await;
+    Ok((StatusCode::CREATED, Json(category)))
+}
+
+// Renames or moves a category, its subcategories move along
+pub async fn update_category(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+    Json(payload): Json<NewCategory>,
+) -> Result<Json<Category>, AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    let category = Category::find(id, &pool).await?;
+    let updated = category.update(payload, &pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("category.updated")
+                .resource(format!("category:{}", id))
+                .change(Some(&category), Some(&updated)),
+        )
+        .await;
+    Ok(Json(updated))
+}
+
+pub async fn delete_category(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    let category = Category::find(id, &pool).await?;
+    category.delete(&pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("category.deleted")
+                .resource(format!("category:{}", id))
+                .change(Some(&category), None),
+        )
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
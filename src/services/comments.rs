@@ -0,0 +1,266 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use axum_extra::TypedHeader;
+use axum_extra::headers::ETag;
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+
+use crate::audit::{AuditEvent, Auditor};
+use crate::auth::jwt::Claims;
+use crate::auth::rbac::{ensure_role, Role};
+use crate::config::Config;
+use crate::models::comment::{Comment, CommentBan, CommentList, CommentPage, CommentQuery, CommentStatus, NewComment};
+use crate::models::post::{CommentsSetting, Post, PostStatus};
+use crate::services::error::AppError;
+use crate::services::posts::{check_if_match, etag, viewer, visible_post};
+
+#[derive(Debug, Deserialize)]
+pub struct CommentEdit {
+    body: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BanRequest {
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentSettings {
+    comments: CommentsSetting,
+}
+
+// The comment, if the caller may see it and its post
+async fn visible_comment(id: i64, claims: &Claims, pool: &Pool<Postgres>) -> Result<(Comment, Post), AppError> {
+    let viewer = viewer(claims)?;
+    let comment = Comment::find(id, pool).await?;
+    let post = visible_post(comment.post_id, viewer, pool).await?;
+    if !comment.is_visible_to(viewer) || post.comments == CommentsSetting::Disabled {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    }
+    Ok((comment, post))
+}
+
+// Authors change their comments for a while after writing them
+fn check_editable(comment: &Comment, claims: &Claims, window: std::time::Duration) -> Result<(), AppError> {
+    if comment.author_id != Some(claims.user_id()?) {
+        return Err(AppError::Forbidden("Only the author can change a comment".to_string()));
+    }
+    if comment.deleted_at.is_some() {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    }
+    let age = (Utc::now() - comment.created_at).to_std().unwrap_or_default();
+    if age > window {
+        let window = match window.as_secs() {
+            60 => "1 minute".to_string(),
+            secs if secs > 0 && secs % 60 == 0 => format!("{} minutes", secs / 60),
+            1 => "1 second".to_string(),
+            secs => format!("{} seconds", secs),
+        };
+        return Err(AppError::Forbidden(format!("Comments can only be changed within {}", window)));
+    }
+    Ok(())
+}
+
+// `?view=nested` (the default) pages through threads, `?view=flat` through
+// all comments oldest first. Approved comments, plus the caller's own;
+// editors see everything.
+pub async fn get_comments(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path(post_id): Path<i32>,
+    Query(query): Query<CommentQuery>,
+) -> Result<Json<CommentPage>, AppError> {
+    let viewer = viewer(&claims)?;
+    let post = visible_post(post_id, viewer, &pool).await?;
+    if post.comments == CommentsSetting::Disabled {
+        let comments = CommentList::Nested(Vec::new());
+        return Ok(Json(CommentPage { comments, total: 0, page: 1, per_page: 0 }));
+    }
+    Ok(Json(Comment::for_post(post.id, viewer, &query, &pool).await?))
+}
+
+// New comments and replies wait in the moderation queue
+pub async fn create_comment(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(post_id): Path<i32>,
+    Json(payload): Json<NewComment>,
+) -> Result<(StatusCode, Json<Comment>), AppError> {
+    let viewer = viewer(&claims)?;
+    let post = visible_post(post_id, viewer, &pool).await?;
+    if post.status != PostStatus::Published || post.comments != CommentsSetting::Open {
+        return Err(AppError::Conflict("Comments are closed".to_string()));
+    }
+    if CommentBan::is_banned(viewer.user_id, &pool).await? {
+        return Err(AppError::Forbidden("You are banned from commenting".to_string()));
+    }
+    if let Some(parent_id) = payload.parent_id {
+        visible_comment(parent_id, &claims, &pool).await?;
+    }
+
+    let comment = Comment::create(post.id, viewer.user_id, payload, &pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("comment.created")
+                .resource(format!("comment:{}", comment.id))
+                .detail("post_id", post.id),
+        )
+        .await;
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+pub async fn edit_comment(
+    State(pool): State<Pool<Postgres>>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i64>,
+    Json(payload): Json<CommentEdit>,
+) -> Result<Json<Comment>, AppError> {
+    let (comment, _) = visible_comment(id, &claims, &pool).await?;
+    check_editable(&comment, &claims, config.comment_edit_window)?;
+
+    let edited = comment.edit(&payload.body, &pool).await?;
+    auditor
+        .record(AuditEvent::new("comment.edited").resource(format!("comment:{}", id)).change(Some(&comment), Some(&edited)))
+        .await;
+    Ok(Json(edited))
+}
+
+pub async fn delete_comment(
+    State(pool): State<Pool<Postgres>>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let (comment, _) = visible_comment(id, &claims, &pool).await?;
+    check_editable(&comment, &claims, config.comment_edit_window)?;
+
+    comment.delete(&pool).await?;
+    auditor
+        .record(AuditEvent::new("comment.deleted").resource(format!("comment:{}", id)).change(Some(&comment), None))
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Comments waiting for an editor, oldest first
+pub async fn get_moderation_queue(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Query(query): Query<CommentQuery>,
+) -> Result<Json<CommentPage>, AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    Ok(Json(Comment::pending(&query, &pool).await?))
+}
+
+pub async fn approve_comment(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i64>,
+) -> Result<Json<Comment>, AppError> {
+    moderate(pool, claims, auditor, id, CommentStatus::Approved).await
+}
+
+pub async fn hide_comment(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i64>,
+) -> Result<Json<Comment>, AppError> {
+    moderate(pool, claims, auditor, id, CommentStatus::Hidden).await
+}
+
+async fn moderate(
+    pool: Pool<Postgres>,
+    claims: Claims,
+    auditor: Auditor,
+    id: i64,
+    status: CommentStatus,
+) -> Result<Json<Comment>, AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    let comment = Comment::find(id, &pool).await?;
+
+    let moderated = comment.moderate(status, &pool).await?;
+    let action = match status {
+        CommentStatus::Approved => "comment.approved",
+        _ => "comment.hidden",
+    };
+    auditor.record(AuditEvent::new(action).resource(format!("comment:{}", id))).await;
+    Ok(Json(moderated))
+}
+
+// Hides the comment and bars its author from commenting, their other
+// pending comments are hidden too
+pub async fn ban_comment_author(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i64>,
+    Json(payload): Json<BanRequest>,
+) -> Result<Json<CommentBan>, AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    let comment = Comment::find(id, &pool).await?;
+    let author_id = comment
+        .author_id
+        .ok_or_else(|| AppError::ValidationError("The comment's author no longer exists".to_string()))?;
+
+    comment.moderate(CommentStatus::Hidden, &pool).await?;
+    let ban = CommentBan::ban(author_id, claims.user_id()?, payload.reason.as_deref(), &pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("comment.author_banned")
+                .resource(format!("user:{}", author_id))
+                .detail("comment_id", id)
+                .detail("reason", &ban.reason),
+        )
+        .await;
+    Ok(Json(ban))
+}
+
+pub async fn lift_comment_ban(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(user_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    if !CommentBan::lift(user_id, &pool).await? {
+        return Err(AppError::NotFound("User is not banned".to_string()));
+    }
+    auditor.record(AuditEvent::new("comment.ban_lifted").resource(format!("user:{}", user_id))).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// `{"comments": "open" | "closed" | "disabled"}`, by the post's author or an
+// editor. Needs the post's ETag in `If-Match`.
+pub async fn set_comment_settings(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(post_id): Path<i32>,
+    headers: HeaderMap,
+    Json(payload): Json<CommentSettings>,
+) -> Result<(TypedHeader<ETag>, Json<Post>), AppError> {
+    let viewer = viewer(&claims)?;
+    let post = visible_post(post_id, viewer, &pool).await?;
+    if !post.is_owned_by(viewer.user_id) && !viewer.is_editor {
+        return Err(AppError::Forbidden("Only the author or an editor can change comment settings".to_string()));
+    }
+    check_if_match(&post, &headers)?;
+
+    let updated = post.set_comments(payload.comments, &pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("post.comments_changed")
+                .resource(format!("post:{}", post_id))
+                .change(Some(&post.comments), Some(&updated.comments)),
+        )
+        .await;
+    Ok((TypedHeader(etag(&updated)), Json(updated)))
+}
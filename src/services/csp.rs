@@ -0,0 +1,74 @@
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::middleware::rate_limit::RateLimiter;
+use crate::models::csp_report::{CspReport, NewCspReport};
+use crate::services::error::AppError;
+
+// A single report body can carry several reports, the rest are dropped
+const MAX_REPORTS_PER_REQUEST: usize = 20;
+
+// Receives CSP violation reports from browsers, both the `report-uri` format
+// (`application/csp-report`) and the Reporting API (`application/reports+json`)
+pub async fn csp_report(
+    State(pool): State<Pool<Postgres>>,
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, AppError> {
+    if !limiter.check(addr.ip()) {
+        return Err(AppError::TooManyRequests);
+    }
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_lowercase())
+        .unwrap_or_default();
+    let payload: Value = serde_json::from_slice(&body)
+        .map_err(|_| AppError::ValidationError("Report is not valid JSON".to_string()))?;
+
+    let mut reports = match content_type.as_str() {
+        "application/csp-report" => NewCspReport::from_report_uri(payload).into_iter().collect(),
+        "application/reports+json" => NewCspReport::from_reporting_api(payload),
+        // Some browsers send `report-uri` reports as plain JSON
+        "application/json" if payload.is_array() => NewCspReport::from_reporting_api(payload),
+        "application/json" => NewCspReport::from_report_uri(payload).into_iter().collect(),
+        _ => return Err(AppError::ValidationError("Unsupported report content type".to_string())),
+    };
+    reports.truncate(MAX_REPORTS_PER_REQUEST);
+
+    let user_agent = headers.get(header::USER_AGENT).and_then(|value| value.to_str().ok());
+    CspReport::insert(reports, user_agent, Some(&addr.ip().to_string()), &pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct ReportPage {
+    before: Option<i64>,
+    limit: Option<i64>,
+}
+
+// Stored reports for review, newest first
+pub async fn get_csp_reports(
+    State(pool): State<Pool<Postgres>>,
+    Query(page): Query<ReportPage>,
+) -> Result<Json<Value>, AppError> {
+    let reports = CspReport::list(page.before, page.limit.unwrap_or(100), &pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let next_before = reports.last().map(|report| report.id);
+    Ok(Json(json!({ "reports": reports, "next_before": next_before })))
+}
@@ -8,6 +8,8 @@ use serde_json::json;
 use thiserror::Error;
 use tracing::error;
 
+use crate::middleware::request_id;
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Authentication failed")]
@@ -42,9 +44,21 @@ pub enum AppError {
     
     #[error("Forbidden: {0}")]
     Forbidden(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
+    #[error("Precondition required: {0}")]
+    PreconditionRequired(String),
     
     #[error("Database error: {0}")]
     DatabaseError(String),
+
+    #[error("Too many requests")]
+    TooManyRequests,
 }
 
 impl IntoResponse for AppError {
@@ -70,10 +84,14 @@ impl IntoResponse for AppError {
             AppError::ValidationError(msg) => (StatusCode::BAD_REQUEST, msg),
             AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
             AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            AppError::PreconditionFailed(msg) => (StatusCode::PRECONDITION_FAILED, msg),
+            AppError::PreconditionRequired(msg) => (StatusCode::PRECONDITION_REQUIRED, msg),
             AppError::DatabaseError(msg) => {
                 error!("Database error: {}", msg);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
             },
+            AppError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
         };
 
         // Hide internal details from response for security
@@ -83,9 +101,16 @@ impl IntoResponse for AppError {
             error_message
         };
 
-        let body = Json(json!({
-            "error": public_message,
-        }));
+        // Lets a client quote the id that matches the server logs
+        let body = match request_id::current() {
+            Some(request_id) => Json(json!({
+                "error": public_message,
+                "request_id": request_id.as_str(),
+            })),
+            None => Json(json!({
+                "error": public_message,
+            })),
+        };
 
         (status, body).into_response()
     }
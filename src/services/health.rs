@@ -0,0 +1,147 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use axum_extra::headers::authorization::Bearer;
+use axum_extra::headers::Authorization;
+use axum_extra::TypedHeader;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::app::AppState;
+use crate::auth::rbac::{has_role, Role};
+use crate::db;
+use crate::services::error::AppError;
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+pub struct CheckResult {
+    status: &'static str,
+    latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct HealthReport {
+    status: &'static str,
+    checks: BTreeMap<&'static str, CheckResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pool: Option<Value>,
+}
+
+impl HealthReport {
+    fn new(checks: BTreeMap<&'static str, CheckResult>) -> Self {
+        let healthy = checks.values().all(|c| c.status == "ok");
+        Self {
+            status: if healthy { "ok" } else { "fail" },
+            checks,
+            pool: None,
+        }
+    }
+
+    fn status_code(&self) -> StatusCode {
+        if self.status == "ok" {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HealthParams {
+    #[serde(default)]
+    verbose: bool,
+}
+
+// Runs a single check, timing it and turning errors and timeouts into a failed result
+async fn check<F>(future: F) -> CheckResult
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let start = Instant::now();
+    let outcome = match tokio::time::timeout(CHECK_TIMEOUT, future).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err("timed out".to_string()),
+    };
+    CheckResult {
+        status: if outcome.is_ok() { "ok" } else { "fail" },
+        latency_ms: start.elapsed().as_secs_f64() * 1000.0,
+        error: outcome.err(),
+    }
+}
+
+// Liveness probe: the process is up and serving requests
+pub async fn healthz() -> Json<HealthReport> {
+    let mut checks = BTreeMap::new();
+    checks.insert("process", check(async { Ok(()) }).await);
+    Json(HealthReport::new(checks))
+}
+
+// Readiness probe: dependencies are reachable and the schema matches this binary.
+// Fails while the server is draining so load balancers stop routing to it.
+pub async fn readyz(
+    State(state): State<AppState>,
+    Query(params): Query<HealthParams>,
+    bearer: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<(StatusCode, Json<HealthReport>), AppError> {
+    // Pool statistics are only for admins
+    if params.verbose {
+        let TypedHeader(Authorization(bearer)) = bearer.ok_or(AppError::MissingToken)?;
+        let claims = state.jwt_auth.verify_token(bearer.token())?;
+        if !has_role(&claims, &Role::Admin) {
+            return Err(AppError::Forbidden(format!("Requires {} role", Role::Admin)));
+        }
+    }
+
+    let mut checks = BTreeMap::new();
+    checks.insert(
+        "database",
+        check(async {
+            sqlx::query("SELECT 1")
+                .execute(db::traced(&state.pool))
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .await,
+    );
+    checks.insert(
+        "migrations",
+        check(async {
+            let migrations = db::migration_state(&state.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            if migrations.is_current() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "pending: {:?}, unknown: {:?}, modified: {:?}",
+                    migrations.pending, migrations.unknown, migrations.modified
+                ))
+            }
+        })
+        .await,
+    );
+    checks.insert(
+        "signing_keys",
+        check(async { state.jwt_auth.self_check().map_err(|e| e.to_string()) }).await,
+    );
+
+    let mut report = HealthReport::new(checks);
+    if state.lifecycle.is_draining() {
+        report.status = "draining";
+    }
+    if params.verbose {
+        report.pool = Some(json!({
+            "size": state.pool.size(),
+            "idle": state.pool.num_idle(),
+            "max_connections": state.pool.options().get_max_connections(),
+        }));
+    }
+
+    Ok((report.status_code(), Json(report)))
+}
@@ -0,0 +1,42 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+
+use crate::audit::{AuditEvent, Auditor};
+use crate::models::job::{Job, JobFilter, STATUS_FAILED, STATUS_PENDING, STATUS_RUNNING, STATUS_SUCCEEDED};
+use crate::services::error::AppError;
+
+// Background jobs, newest first: `?status=pending` for what is coming up,
+// `?status=failed` for jobs that used up their attempts
+pub async fn get_jobs(
+    State(pool): State<Pool<Postgres>>,
+    Query(filter): Query<JobFilter>,
+) -> Result<Json<Value>, AppError> {
+    if let Some(status) = &filter.status
+        && ![STATUS_PENDING, STATUS_RUNNING, STATUS_SUCCEEDED, STATUS_FAILED].contains(&status.as_str())
+    {
+        return Err(AppError::ValidationError(format!("Unknown job status: {}", status)));
+    }
+    let jobs = Job::list(&filter, &pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let next_before = jobs.last().map(|job| job.id);
+    Ok(Json(json!({ "jobs": jobs, "next_before": next_before })))
+}
+
+// Runs a failed job again, with a fresh set of attempts
+pub async fn retry_job(
+    State(pool): State<Pool<Postgres>>,
+    auditor: Auditor,
+    Path(id): Path<i64>,
+) -> Result<Json<Job>, AppError> {
+    let job = Job::retry(id, &pool)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("No failed job with this id".to_string()))?;
+    auditor
+        .record(AuditEvent::new("job.retried").resource(format!("job:{}", id)).detail("kind", &job.kind))
+        .await;
+    Ok(Json(job))
+}
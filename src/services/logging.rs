@@ -0,0 +1,24 @@
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::services::error::AppError;
+use crate::telemetry;
+
+#[derive(Deserialize)]
+pub struct LogLevelRequest {
+    filter: String,
+}
+
+// Current log filter, in RUST_LOG syntax
+pub async fn get_log_level() -> Result<Json<Value>, AppError> {
+    let filter = telemetry::log_filter().ok_or(AppError::InternalServerError)?;
+    Ok(Json(json!({ "filter": filter })))
+}
+
+// Replaces the log filter of the running process, e.g. `info,sqlx=warn,AUDIT=info`.
+// The change is not persisted, a restart goes back to RUST_LOG.
+pub async fn set_log_level(Json(request): Json<LogLevelRequest>) -> Result<Json<Value>, AppError> {
+    telemetry::set_log_filter(&request.filter)?;
+    get_log_level().await
+}
@@ -0,0 +1,53 @@
+use axum::extract::State;
+use axum::http::{header, HeaderMap};
+use axum::response::IntoResponse;
+use metrics::gauge;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::services::error::AppError;
+
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// Installs the process-wide Prometheus recorder. There can only be one, so
+// the binary installs it at startup and hands the handle to `AppState`.
+pub fn install_recorder() -> anyhow::Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), LATENCY_BUCKETS)?
+        .install_recorder()?;
+    Ok(handle)
+}
+
+// Pool statistics are sampled when Prometheus scrapes
+fn record_pool_metrics(pool: &Pool<Postgres>) {
+    gauge!("db_pool_connections").set(pool.size() as f64);
+    gauge!("db_pool_idle_connections").set(pool.num_idle() as f64);
+    gauge!("db_pool_max_connections").set(pool.options().get_max_connections() as f64);
+}
+
+// Prometheus scrape endpoint; when METRICS_TOKEN is set it must be sent as a bearer token
+pub async fn metrics(
+    State(pool): State<Pool<Postgres>>,
+    State(config): State<Arc<Config>>,
+    State(recorder): State<PrometheusHandle>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(expected) = &config.metrics_token {
+        let provided = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if provided != Some(expected.as_str()) {
+            return Err(AppError::Unauthorized("Invalid metrics token".to_string()));
+        }
+    }
+
+    record_pool_metrics(&pool);
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        recorder.render(),
+    ))
+}
@@ -1,2 +1,12 @@
+pub mod audit;
+pub mod categories;
+pub mod comments;
+pub mod csp;
+pub mod health;
+pub mod jobs;
+pub mod logging;
+pub mod metrics;
 pub mod posts;
+pub mod reactions;
+pub mod tags;
 pub mod error;
\ No newline at end of file
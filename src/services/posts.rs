@@ -1,26 +1,503 @@
-use crate::models::post::Post;
-use axum::http::StatusCode;
-use axum::{Extension, Json};
-use axum::extract::Path;
-use sqlx::{query_as, Pool, Postgres};
+use crate::audit::{AuditEvent, Auditor};
+use crate::auth::jwt::Claims;
+use crate::auth::rbac::{ensure_role, has_role, Role};
+use crate::config::Config;
+use crate::models::post::{NewPost, Post, PostFilter, PostReview, PostSchedule, PostTransition, ReviewDecision, Viewer};
+use crate::models::post_revision::{PostRevision, RevisionDiff};
+use crate::models::post_search::{self, SearchQuery, SearchResults, TitleSuggestion};
+use crate::models::post_slug;
+use crate::models::reaction::{self, PostWithReactions};
+use crate::models::tag::Tag;
+use crate::services::error::AppError;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::response::{IntoResponse, Response};
+use axum_extra::TypedHeader;
+use axum_extra::headers::{ETag, HeaderMapExt, IfMatch, IfNoneMatch};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
 
+#[derive(Debug, Deserialize)]
+pub struct TagList {
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CategoryChoice {
+    category_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SlugChoice {
+    slug: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevisionRange {
+    from: i32,
+    to: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AutocompleteQuery {
+    q: String,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewRequest {
+    comment: Option<String>,
+}
+
+pub(crate) fn viewer(claims: &Claims) -> Result<Viewer, AppError> {
+    Ok(Viewer {
+        user_id: claims.user_id()?,
+        is_editor: has_role(claims, &Role::Editor),
+    })
+}
+
+// Posts the caller cannot see are reported as missing, not forbidden
+pub(crate) async fn visible_post(id: i32, viewer: Viewer, pool: &Pool<Postgres>) -> Result<Post, AppError> {
+    let post = Post::find(id, pool).await?;
+    if !post.is_visible_to(viewer) {
+        return Err(AppError::NotFound("Post not found".to_string()));
+    }
+    Ok(post)
+}
+
+// Strong validator of the post's representation, changes with every save
+pub(crate) fn etag(post: &Post) -> ETag {
+    format!("\"{}\"", post.version).parse().expect("a quoted number is a valid ETag")
+}
+
+// Writes must say which version they were based on, so that of two people
+// editing the same post the second one learns about the first
+pub(crate) fn check_if_match(post: &Post, headers: &HeaderMap) -> Result<(), AppError> {
+    if !headers.contains_key(header::IF_MATCH) {
+        return Err(AppError::PreconditionRequired("If-Match header is required".to_string()));
+    }
+    let if_match = headers
+        .typed_get::<IfMatch>()
+        .ok_or_else(|| AppError::ValidationError("Malformed If-Match header".to_string()))?;
+    if !if_match.precondition_passes(&etag(post)) {
+        return Err(AppError::PreconditionFailed("Post was changed by someone else".to_string()));
+    }
+    Ok(())
+}
+
+// Published posts, plus the caller's own posts in any status; editors also see
+// submitted, scheduled and archived posts. `?status=in_review` is the editors' review queue;
+// `?tag=rust&category=databases` narrow the list, a category includes its subcategories.
+// Each post carries its reaction counts and the caller's reactions and bookmark.
 pub async fn get_posts(
-    Extension(pool): Extension<Pool<Postgres>>,
-) -> Result<Json<Vec<Post>>, StatusCode> {
-    let posts = query_as!(Post, "SELECT id, user_id, title, body FROM posts")
-        .fetch_all(&pool)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
-    Ok(Json(posts))
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Query(filter): Query<PostFilter>,
+) -> Result<Json<Vec<PostWithReactions>>, AppError> {
+    let viewer = viewer(&claims)?;
+    let posts = Post::list(viewer, &filter, &pool).await?;
+    Ok(Json(reaction::with_reactions(posts, viewer.user_id, &pool).await?))
+}
+
+// `?q=rust async* "zero cost" -java&lang=english&page=2&per_page=20`, see `post_search::parse_query`
+pub async fn search_posts(
+    State(pool): State<Pool<Postgres>>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<SearchResults>, AppError> {
+    let language = query.lang.as_deref().unwrap_or(&config.search_language);
+    let results = post_search::search(&query, language, viewer(&claims)?, &pool).await?;
+    Ok(Json(results))
+}
+
+// Title suggestions while typing into the search box
+pub async fn autocomplete_posts(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Query(query): Query<AutocompleteQuery>,
+) -> Result<Json<Vec<TitleSuggestion>>, AppError> {
+    if query.q.trim().is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+    let suggestions = post_search::suggest_titles(query.q.trim(), query.limit.unwrap_or(10), viewer(&claims)?, &pool).await?;
+    Ok(Json(suggestions))
 }
 
+// Answers 304 when `If-None-Match` has the current ETag. The ETag covers the
+// post itself, not its reactions, which change too often to revalidate on.
 pub async fn get_post(
-    Extension(pool): Extension<Pool<Postgres>>, 
-    Path(id): Path<i32>
-) -> Result<Json<Post>, StatusCode>  {
-    let post = query_as!(Post, "SELECT id, user_id, title, body FROM posts WHERE id = $1", id)
-        .fetch_one(&pool)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path(id): Path<i32>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Result<Response, AppError> {
+    let viewer = viewer(&claims)?;
+    let post = visible_post(id, viewer, &pool).await?;
+    post_response(post, viewer, if_none_match, &pool).await
+}
+
+// The post a slug names, like `GET /posts/{id}`. A slug the post had before
+// answers 301 with its current address.
+pub async fn get_post_by_slug(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path(slug): Path<String>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Result<Response, AppError> {
+    let viewer = viewer(&claims)?;
+    if let Some(post) = Post::find_by_slug(&slug, &pool).await?
+        && post.is_visible_to(viewer)
+    {
+        return post_response(post, viewer, if_none_match, &pool).await;
+    }
+    let Some(post_id) = post_slug::redirect(&slug, &pool).await? else {
+        return Err(AppError::NotFound("Post not found".to_string()));
+    };
+    let post = visible_post(post_id, viewer, &pool).await?;
+    let location = format!("/posts/by-slug/{}", post.slug);
+    Ok((StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, location)]).into_response())
+}
+
+async fn post_response(
+    post: Post,
+    viewer: Viewer,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    pool: &Pool<Postgres>,
+) -> Result<Response, AppError> {
+    let etag = etag(&post);
+    if let Some(TypedHeader(if_none_match)) = if_none_match
+        && !if_none_match.precondition_passes(&etag)
+    {
+        return Ok((StatusCode::NOT_MODIFIED, TypedHeader(etag)).into_response());
+    }
+    let post = reaction::post_with_reactions(post, viewer.user_id, pool).await?;
+    Ok((TypedHeader(etag), Json(post)).into_response())
+}
+
+// New posts start out as drafts of the caller
+pub async fn create_post(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Json(payload): Json<NewPost>,
+) -> Result<(StatusCode, TypedHeader<ETag>, Json<Post>), AppError> {
+    let post = Post::create(claims.user_id()?, payload, &pool).await?;
+    auditor
+        .record(AuditEvent::new("post.created").resource(format!("post:{}", post.id)).change(None, Some(&post)))
+        .await;
+    Ok((StatusCode::CREATED, TypedHeader(etag(&post)), Json(post)))
+}
+
+// The author or an editor edits a post that isn't in review or archived; a
+// rejected post is a draft again. An author's edit of an approved post sends
+// it back to review. Needs the post's ETag in `If-Match`.
+pub async fn update_post(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Json(payload): Json<NewPost>,
+) -> Result<(TypedHeader<ETag>, Json<Post>), AppError> {
+    let viewer = viewer(&claims)?;
+    let post = visible_post(id, viewer, &pool).await?;
+    if !post.is_owned_by(viewer.user_id) && !viewer.is_editor {
+        return Err(AppError::Forbidden("Only the author or an editor can edit a post".to_string()));
+    }
+    check_if_match(&post, &headers)?;
+
+    let updated = post.update(viewer, payload, &pool).await?;
+    auditor
+        .record(AuditEvent::new("post.updated").resource(format!("post:{}", id)).change(Some(&post), Some(&updated)))
+        .await;
+    Ok((TypedHeader(etag(&updated)), Json(updated)))
+}
+
+// Hands a draft to the editors
+pub async fn submit_post(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+) -> Result<Json<Post>, AppError> {
+    let viewer = viewer(&claims)?;
+    let post = visible_post(id, viewer, &pool).await?;
+    if !post.is_owned_by(viewer.user_id) {
+        return Err(AppError::Forbidden("Only the author can submit a post".to_string()));
+    }
+
+    let post = post.transition(PostTransition::Submit, &pool).await?;
+    auditor.record(AuditEvent::new("post.submitted").resource(format!("post:{}", id))).await;
+    Ok(Json(post))
+}
+
+pub async fn approve_post(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+    Json(payload): Json<ReviewRequest>,
+) -> Result<Json<Post>, AppError> {
+    review(pool, claims, auditor, id, ReviewDecision::Approved, payload.comment).await
+}
+
+// Sends a submitted post back to its author, the comment says what to change
+pub async fn reject_post(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+    Json(payload): Json<ReviewRequest>,
+) -> Result<Json<Post>, AppError> {
+    let comment = payload.comment.filter(|comment| !comment.trim().is_empty());
+    if comment.is_none() {
+        return Err(AppError::ValidationError("A rejection needs a comment".to_string()));
+    }
+    review(pool, claims, auditor, id, ReviewDecision::Rejected, comment).await
+}
+
+async fn review(
+    pool: Pool<Postgres>,
+    claims: Claims,
+    auditor: Auditor,
+    id: i32,
+    decision: ReviewDecision,
+    comment: Option<String>,
+) -> Result<Json<Post>, AppError> {
+    let viewer = viewer(&claims)?;
+    if !viewer.is_editor {
+        return Err(AppError::Forbidden(format!("Requires {} role", Role::Editor)));
+    }
+    let post = visible_post(id, viewer, &pool).await?;
+
+    let (post, review) = post.review(viewer.user_id, decision, comment.as_deref(), &pool).await?;
+    let action = match decision {
+        ReviewDecision::Approved => "post.approved",
+        ReviewDecision::Rejected => "post.rejected",
+    };
+    auditor
+        .record(
+            AuditEvent::new(action)
+                .resource(format!("post:{}", id))
+                .detail("review_id", review.id)
+                .detail("comment", &review.comment),
+        )
+        .await;
     Ok(Json(post))
-}
\ No newline at end of file
+}
+
+// Sets when a post goes live and when it expires, see `Post::set_schedule`.
+// Approving a post with a future `publish_at` schedules it instead of publishing.
+pub async fn schedule_post(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+    Json(schedule): Json<PostSchedule>,
+) -> Result<Json<Post>, AppError> {
+    let viewer = viewer(&claims)?;
+    if !viewer.is_editor {
+        return Err(AppError::Forbidden(format!("Requires {} role", Role::Editor)));
+    }
+    let post = visible_post(id, viewer, &pool).await?;
+
+    let scheduled = post.set_schedule(schedule, &pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("post.scheduled")
+                .resource(format!("post:{}", id))
+                .change(Some(&post), Some(&scheduled)),
+        )
+        .await;
+    Ok(Json(scheduled))
+}
+
+// Takes a published post offline, by its author or an editor
+pub async fn archive_post(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+) -> Result<Json<Post>, AppError> {
+    let viewer = viewer(&claims)?;
+    let post = visible_post(id, viewer, &pool).await?;
+    if !post.is_owned_by(viewer.user_id) && !viewer.is_editor {
+        return Err(AppError::Forbidden("Only the author or an editor can archive a post".to_string()));
+    }
+
+    let post = post.transition(PostTransition::Archive, &pool).await?;
+    auditor.record(AuditEvent::new("post.archived").resource(format!("post:{}", id))).await;
+    Ok(Json(post))
+}
+
+// Editor decisions on the post, oldest first
+pub async fn get_post_reviews(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<PostReview>>, AppError> {
+    let post = visible_post(id, viewer(&claims)?, &pool).await?;
+    Ok(Json(PostReview::for_post(post.id, &pool).await?))
+}
+
+// The history of a post is for its author and the editors
+async fn post_with_history(id: i32, claims: &Claims, pool: &Pool<Postgres>) -> Result<Post, AppError> {
+    let viewer = viewer(claims)?;
+    let post = visible_post(id, viewer, pool).await?;
+    if !post.is_owned_by(viewer.user_id) && !viewer.is_editor {
+        return Err(AppError::Forbidden("Only the author or an editor can see the revisions".to_string()));
+    }
+    Ok(post)
+}
+
+// Every saved version of the post, newest first
+pub async fn get_post_revisions(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<PostRevision>>, AppError> {
+    let post = post_with_history(id, &claims, &pool).await?;
+    Ok(Json(PostRevision::for_post(post.id, &pool).await?))
+}
+
+pub async fn get_post_revision(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path((id, revision)): Path<(i32, i32)>,
+) -> Result<Json<PostRevision>, AppError> {
+    let post = post_with_history(id, &claims, &pool).await?;
+    Ok(Json(PostRevision::find(post.id, revision, &pool).await?))
+}
+
+// `?from=1&to=3`, either order works
+pub async fn diff_post_revisions(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path(id): Path<i32>,
+    Query(range): Query<RevisionRange>,
+) -> Result<Json<RevisionDiff>, AppError> {
+    let post = post_with_history(id, &claims, &pool).await?;
+    let from = PostRevision::find(post.id, range.from, &pool).await?;
+    let to = PostRevision::find(post.id, range.to, &pool).await?;
+    Ok(Json(from.diff(&to)))
+}
+
+// Puts an earlier revision's content back, recorded as a new revision. The
+// same rules as editing apply, `If-Match` included.
+pub async fn restore_post_revision(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path((id, revision)): Path<(i32, i32)>,
+    headers: HeaderMap,
+) -> Result<(TypedHeader<ETag>, Json<Post>), AppError> {
+    let viewer = viewer(&claims)?;
+    let post = visible_post(id, viewer, &pool).await?;
+    if !post.is_owned_by(viewer.user_id) && !viewer.is_editor {
+        return Err(AppError::Forbidden("Only the author or an editor can restore a revision".to_string()));
+    }
+    check_if_match(&post, &headers)?;
+    let revision = PostRevision::find(post.id, revision, &pool).await?;
+
+    let restored = post.restore(viewer, &revision, &pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("post.restored")
+                .resource(format!("post:{}", id))
+                .detail("revision", revision.revision)
+                .change(Some(&post), Some(&restored)),
+        )
+        .await;
+    Ok((TypedHeader(etag(&restored)), Json(restored)))
+}
+
+// Alphabetical
+pub async fn get_post_tags(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<Tag>>, AppError> {
+    let post = visible_post(id, viewer(&claims)?, &pool).await?;
+    Ok(Json(Tag::for_post(post.id, &pool).await?))
+}
+
+// Editors replace the post's tags with `{"tags": [...]}`, new names become new tags
+pub async fn set_post_tags(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+    Json(payload): Json<TagList>,
+) -> Result<Json<Vec<Tag>>, AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    let post = visible_post(id, viewer(&claims)?, &pool).await?;
+
+    let before = Tag::for_post(post.id, &pool).await?;
+    let tags = Tag::set_for_post(post.id, &payload.tags, &pool).await?;
+    let names = |tags: &[Tag]| tags.iter().map(|tag| tag.name.clone()).collect::<Vec<_>>();
+    auditor
+        .record(
+            AuditEvent::new("post.tagged")
+                .resource(format!("post:{}", id))
+                .change(Some(&names(&before)), Some(&names(&tags))),
+        )
+        .await;
+    Ok(Json(tags))
+}
+
+// Editors file the post under `{"category_id": ...}`, null for none. Needs
+// the post's ETag in `If-Match`.
+pub async fn set_post_category(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Json(payload): Json<CategoryChoice>,
+) -> Result<(TypedHeader<ETag>, Json<Post>), AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    let post = visible_post(id, viewer(&claims)?, &pool).await?;
+    check_if_match(&post, &headers)?;
+
+    let updated = post.set_category(payload.category_id, &pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("post.categorized")
+                .resource(format!("post:{}", id))
+                .change(Some(&post.category_id), Some(&updated.category_id)),
+        )
+        .await;
+    Ok((TypedHeader(etag(&updated)), Json(updated)))
+}
+
+// Authors pick their post's slug with `{"slug": "my-post"}`, or go back to
+// one generated from the title with `{"slug": null}`, unless the post is in
+// review or archived. Needs the post's ETag in `If-Match`.
+pub async fn set_post_slug(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Json(payload): Json<SlugChoice>,
+) -> Result<(TypedHeader<ETag>, Json<Post>), AppError> {
+    let viewer = viewer(&claims)?;
+    let post = visible_post(id, viewer, &pool).await?;
+    if !post.is_owned_by(viewer.user_id) {
+        return Err(AppError::Forbidden("Only the author can change a post's slug".to_string()));
+    }
+    check_if_match(&post, &headers)?;
+
+    let updated = post.set_slug(payload.slug.as_deref(), &pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("post.slug_changed")
+                .resource(format!("post:{}", id))
+                .change(Some(&post.slug), Some(&updated.slug)),
+        )
+        .await;
+    Ok((TypedHeader(etag(&updated)), Json(updated)))
+}
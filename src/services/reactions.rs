@@ -0,0 +1,90 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+
+use crate::auth::jwt::Claims;
+use crate::config::Config;
+use crate::models::post::{Post, PostStatus};
+use crate::models::reaction::{self, PostWithReactions, ReactionSummary};
+use crate::services::error::AppError;
+use crate::services::posts::{viewer, visible_post};
+
+// Reactions and bookmarks are for published posts; taking one back works
+// on any post the caller can still see
+async fn published_post(id: i32, claims: &Claims, pool: &Pool<Postgres>) -> Result<Post, AppError> {
+    let post = visible_post(id, viewer(claims)?, pool).await?;
+    if post.status != PostStatus::Published {
+        return Err(AppError::Conflict("Only published posts can be reacted to or bookmarked".to_string()));
+    }
+    Ok(post)
+}
+
+fn ensure_reaction(reaction: &str, config: &Config) -> Result<(), AppError> {
+    if !config.reactions.iter().any(|allowed| allowed == reaction) {
+        return Err(AppError::ValidationError(format!(
+            "Unknown reaction, use one of {}",
+            config.reactions.join(" ")
+        )));
+    }
+    Ok(())
+}
+
+// `PUT /posts/{id}/reactions/👍`, repeating it changes nothing. Answers
+// with the post's reactions.
+pub async fn put_reaction(
+    State(pool): State<Pool<Postgres>>,
+    State(config): State<Arc<Config>>,
+    claims: Claims,
+    Path((id, reaction)): Path<(i32, String)>,
+) -> Result<Json<ReactionSummary>, AppError> {
+    ensure_reaction(&reaction, &config)?;
+    let post = published_post(id, &claims, &pool).await?;
+    let user_id = claims.user_id()?;
+
+    reaction::add(post.id, user_id, &reaction, &pool).await?;
+    Ok(Json(reaction::post_with_reactions(post, user_id, &pool).await?.summary))
+}
+
+pub async fn delete_reaction(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path((id, reaction)): Path<(i32, String)>,
+) -> Result<Json<ReactionSummary>, AppError> {
+    let post = visible_post(id, viewer(&claims)?, &pool).await?;
+    let user_id = claims.user_id()?;
+
+    reaction::remove(post.id, user_id, &reaction, &pool).await?;
+    Ok(Json(reaction::post_with_reactions(post, user_id, &pool).await?.summary))
+}
+
+pub async fn put_bookmark(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    let post = published_post(id, &claims, &pool).await?;
+    reaction::bookmark(post.id, claims.user_id()?, &pool).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_bookmark(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    let post = visible_post(id, viewer(&claims)?, &pool).await?;
+    reaction::unbookmark(post.id, claims.user_id()?, &pool).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// The caller's bookmarks, newest first; posts they can no longer see are left out
+pub async fn get_bookmarks(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+) -> Result<Json<Vec<PostWithReactions>>, AppError> {
+    let viewer = viewer(&claims)?;
+    let posts = reaction::bookmarked_posts(viewer.user_id, viewer.is_editor, &pool).await?;
+    Ok(Json(reaction::with_reactions(posts, viewer.user_id, &pool).await?))
+}
@@ -0,0 +1,93 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+
+use crate::audit::{AuditEvent, Auditor};
+use crate::auth::jwt::Claims;
+use crate::auth::rbac::{ensure_role, Role};
+use crate::models::tag::{Tag, TagCount};
+use crate::services::error::AppError;
+
+#[derive(Debug, Deserialize)]
+pub struct TagName {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeRequest {
+    into: i32,
+}
+
+// Every tag with its number of published posts, for tag clouds
+pub async fn get_tags(State(pool): State<Pool<Postgres>>) -> Result<Json<Vec<TagCount>>, AppError> {
+    Ok(Json(Tag::counts(&pool).await?))
+}
+
+pub async fn create_tag(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Json(payload): Json<TagName>,
+) -> Result<(StatusCode, Json<Tag>), AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    let tag = Tag::create(&payload.name, &pool).await?;
+    auditor
+        .record(AuditEvent::new("tag.created").resource(format!("tag:{}", tag.id)).detail("name", &tag.name))
+        .await;
+    Ok((StatusCode::CREATED, Json(tag)))
+}
+
+pub async fn delete_tag(
+    State(pool): State<Pool<Postgres>>,
+    claims: Claims,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    ensure_role(&claims, Role::Editor)?;
+    let tag = Tag::find(id, &pool).await?;
+    tag.delete(&pool).await?;
+    auditor
+        .record(AuditEvent::new("tag.deleted").resource(format!("tag:{}", id)).detail("name", &tag.name))
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Admin: a new name for the tag on all its posts
+pub async fn rename_tag(
+    State(pool): State<Pool<Postgres>>,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+    Json(payload): Json<TagName>,
+) -> Result<Json<Tag>, AppError> {
+    let tag = Tag::find(id, &pool).await?;
+    let renamed = tag.rename(&payload.name, &pool).await?;
+    auditor
+        .record(AuditEvent::new("tag.renamed").resource(format!("tag:{}", id)).change(Some(&tag), Some(&renamed)))
+        .await;
+    Ok(Json(renamed))
+}
+
+// Admin: folds the tag into `into`, e.g. "postgresql" into "postgres"
+pub async fn merge_tag(
+    State(pool): State<Pool<Postgres>>,
+    auditor: Auditor,
+    Path(id): Path<i32>,
+    Json(payload): Json<MergeRequest>,
+) -> Result<Json<Value>, AppError> {
+    let tag = Tag::find(id, &pool).await?;
+    let into = Tag::find(payload.into, &pool).await?;
+    let moved = tag.merge(&into, &pool).await?;
+    auditor
+        .record(
+            AuditEvent::new("tag.merged")
+                .resource(format!("tag:{}", id))
+                .detail("name", &tag.name)
+                .detail("into", into.id)
+                .detail("moved_posts", moved),
+        )
+        .await;
+    Ok(Json(json!({ "tag": into, "moved_posts": moved })))
+}
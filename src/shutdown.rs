@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{info, warn};
+
+// Coordinates graceful shutdown. Once shutdown begins the server reports
+// itself as not ready, stops accepting connections and gives in-flight
+// requests and background tasks a bounded amount of time to finish.
+#[derive(Clone, Default)]
+pub struct Lifecycle {
+    draining: Arc<AtomicBool>,
+    shutdown: CancellationToken,
+    tasks: TaskTracker,
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    // Reports the server as not ready, so load balancers stop sending it new
+    // requests; it keeps accepting connections until `begin_shutdown`
+    pub fn begin_draining(&self) {
+        if !self.draining.swap(true, Ordering::SeqCst) {
+            info!("Shutdown started, draining in-flight requests");
+        }
+    }
+
+    pub fn begin_shutdown(&self) {
+        self.begin_draining();
+        self.shutdown.cancel();
+    }
+
+    // Drains first and only stops accepting connections after `pre_stop_delay`,
+    // giving load balancers time to notice the failing readiness check
+    pub async fn shutdown_after(&self, pre_stop_delay: Duration) {
+        self.begin_draining();
+        if !pre_stop_delay.is_zero() {
+            info!(delay_secs = pre_stop_delay.as_secs_f64(), "Waiting before closing the listener");
+            tokio::time::sleep(pre_stop_delay).await;
+        }
+        self.begin_shutdown();
+    }
+
+    // Resolves once shutdown has begun; background tasks select on this to stop their loops
+    pub fn shutdown_requested(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.shutdown.clone().cancelled_owned()
+    }
+
+    // Spawns a background task that shutdown will wait for
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.spawn(task);
+    }
+
+    // Waits for the tracked background tasks, returns false if they did not finish in time
+    pub async fn wait_for_tasks(&self, timeout: Duration) -> bool {
+        self.tasks.close();
+        if tokio::time::timeout(timeout, self.tasks.wait()).await.is_err() {
+            warn!(remaining = self.tasks.len(), "Background tasks did not finish before the shutdown timeout");
+            return false;
+        }
+        true
+    }
+}
+
+// Resolves on SIGINT (ctrl-c) or SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install SIGINT handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received SIGINT"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
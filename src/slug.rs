@@ -0,0 +1,23 @@
+use deunicode::deunicode;
+
+// Longer slugs are cut back to a whole word
+const MAX_LEN: usize = 80;
+
+// Lowercase ASCII letters and digits, separated by single dashes. Other
+// scripts are transliterated first, so "Crème brûlée" becomes "creme-brulee"
+// and "Привет" "privet".
+pub fn slugify(text: &str) -> String {
+    let slug = deunicode(text)
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.len() <= MAX_LEN {
+        return slug;
+    }
+    match slug[..=MAX_LEN].rfind('-') {
+        Some(dash) => slug[..dash].to_string(),
+        None => slug[..MAX_LEN].to_string(),
+    }
+}
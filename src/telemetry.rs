@@ -0,0 +1,199 @@
+use axum::extract::{MatchedPath, Request};
+use axum::response::Response;
+use http::HeaderMap;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::global;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::Span;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+use crate::config::{LogConfig, LogFormat, LogRotation, OtelConfig};
+use crate::middleware::RequestId;
+use crate::redact::{self, RedactingWriter};
+use crate::services::error::AppError;
+
+// Owns the trace exporter and the log file writer; buffered spans and log lines are flushed on shutdown
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+    _log_writer: Option<WorkerGuard>,
+}
+
+impl Telemetry {
+    pub fn force_flush(&self) {
+        if let Some(provider) = &self.provider
+            && let Err(e) = provider.force_flush()
+        {
+            eprintln!("Failed to flush traces: {}", e);
+        }
+    }
+
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to shut down trace export: {}", e);
+        }
+    }
+}
+
+// Lets admins change the log filter of the running process
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+pub fn log_filter() -> Option<String> {
+    LOG_FILTER
+        .get()
+        .and_then(|handle| handle.with_current(|filter| filter.to_string()).ok())
+}
+
+pub fn set_log_filter(directives: &str) -> Result<(), AppError> {
+    let filter = EnvFilter::try_new(directives)
+        .map_err(|e| AppError::ValidationError(format!("Invalid log filter: {}", e)))?;
+    let handle = LOG_FILTER.get().ok_or(AppError::InternalServerError)?;
+    handle.reload(filter).map_err(|_| AppError::InternalServerError)?;
+    tracing::info!(filter = directives, "Log filter changed");
+    Ok(())
+}
+
+// Installs the global tracing subscriber: logs as text or JSON on stdout or a
+// rotating file, filtered per target like RUST_LOG and redacted, and, when configured,
+// OTLP/HTTP export of spans
+pub fn init(log: &LogConfig, otel: Option<&OtelConfig>) -> anyhow::Result<Telemetry> {
+    let filter = EnvFilter::try_new(&log.filter)
+        .map_err(|e| anyhow::anyhow!("invalid RUST_LOG filter: {}", e))?;
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let (writer, log_writer) = match &log.file {
+        Some(path) => {
+            let (writer, guard) = tracing_appender::non_blocking(rolling_file(path, log.rotation)?);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        None => (BoxMakeWriter::new(std::io::stdout), None),
+    };
+    redact::init(&log.redaction);
+    let writer = RedactingWriter(writer);
+    let ansi = log.file.is_none();
+    let fmt_layer = match log.format {
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_writer(writer)
+            .boxed(),
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_ansi(ansi)
+            .with_writer(writer)
+            .boxed(),
+    };
+
+    let provider = otel.map(tracer_provider).transpose()?;
+    let otel_layer = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("rustrest")));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .with(otel_layer)
+        .try_init()?;
+    let _ = LOG_FILTER.set(handle);
+
+    Ok(Telemetry { provider, _log_writer: log_writer })
+}
+
+fn rolling_file(path: &Path, rotation: LogRotation) -> anyhow::Result<RollingFileAppender> {
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let prefix = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("LOG_FILE must name a file"))?;
+    let rotation = match rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    Ok(RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(prefix.to_string_lossy())
+        .build(directory)?)
+}
+
+fn tracer_provider(config: &OtelConfig) -> anyhow::Result<SdkTracerProvider> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", config.endpoint.trim_end_matches('/')))
+        .build()?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        // Follow the caller's sampling decision, sample new traces by ratio
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sampling_ratio))))
+        .with_resource(Resource::builder().with_service_name(config.service_name.clone()).build())
+        .build();
+    global::set_tracer_provider(provider.clone());
+
+    Ok(provider)
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+// Server span for a request, continuing the trace from an incoming W3C `traceparent` header
+pub fn request_span(request: &Request) -> Span {
+    let method = request.method();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or("unmatched");
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.as_str())
+        .unwrap_or_default();
+
+    let span = tracing::info_span!(
+        "request",
+        request_id,
+        otel.name = %format!("{} {}", method, route),
+        otel.kind = "server",
+        http.request.method = %method,
+        http.route = route,
+        url.path = %request.uri().path(),
+        http.response.status_code = tracing::field::Empty,
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    // Fails only when no OpenTelemetry layer is installed
+    let _ = span.set_parent(parent);
+
+    span
+}
+
+pub fn record_response(response: &Response, _latency: Duration, span: &Span) {
+    span.record("http.response.status_code", response.status().as_u16());
+}
@@ -0,0 +1,310 @@
+use anyhow::Context;
+use axum::extract::ConnectInfo;
+use axum::http::{header, HeaderMap, StatusCode, Uri};
+use axum::middleware::AddExtension;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::serve::{IncomingStream, Listener};
+use axum::{Extension, Router};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::convert::Infallible;
+use std::fs::File;
+use std::future::{ready, Ready};
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tower::{Layer, Service};
+use tracing::{debug, error, info, warn};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::config::TlsConfig;
+
+// Connections that haven't finished the handshake by then are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The TLS settings currently in use. Reloading swaps in a new rustls config for
+// connections accepted from then on; established connections keep theirs.
+pub struct ServerTls {
+    config: TlsConfig,
+    current: RwLock<Arc<ServerConfig>>,
+    // Modification times of the files the current config was built from
+    loaded: Mutex<Vec<Option<SystemTime>>>,
+}
+
+impl ServerTls {
+    pub fn load(config: TlsConfig) -> anyhow::Result<Arc<Self>> {
+        let loaded = modification_times(&config);
+        let server_config = server_config(&config)?;
+        Ok(Arc::new(Self {
+            config,
+            current: RwLock::new(Arc::new(server_config)),
+            loaded: Mutex::new(loaded),
+        }))
+    }
+
+    fn server_config(&self) -> Arc<ServerConfig> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    // Re-reads the certificate, key and client CA files. On error the current
+    // config stays in place, so a half-written renewal doesn't take the server down.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let loaded = modification_times(&self.config);
+        let server_config = server_config(&self.config)?;
+        *self.current.write().unwrap() = Arc::new(server_config);
+        *self.loaded.lock().unwrap() = loaded;
+        info!(cert = %self.config.cert_path.display(), "TLS certificate reloaded");
+        Ok(())
+    }
+
+    // Reloads if any of the files changed since the last load, returns whether it did
+    pub fn reload_if_changed(&self) -> anyhow::Result<bool> {
+        if *self.loaded.lock().unwrap() == modification_times(&self.config) {
+            return Ok(false);
+        }
+        self.reload().map(|_| true)
+    }
+}
+
+fn modification_times(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    [Some(&config.cert_path), Some(&config.key_path), config.client_ca_path.as_ref()]
+        .into_iter()
+        .flatten()
+        .map(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+fn server_config(config: &TlsConfig) -> anyhow::Result<ServerConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider)).with_safe_default_protocol_versions()?;
+
+    let builder = match &config.client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in certificates(path)? {
+                roots.add(cert).with_context(|| format!("invalid client CA in {}", path.display()))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if config.client_auth_required {
+                verifier
+            } else {
+                verifier.allow_unauthenticated()
+            };
+            builder.with_client_cert_verifier(verifier.build()?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(certificates(&config.cert_path)?, private_key(&config.key_path)?)
+        .context("certificate does not match the private key")?;
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(server_config)
+}
+
+fn certificates(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("invalid PEM in {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificate found in {}", path.display());
+    }
+    Ok(certs)
+}
+
+fn private_key(path: &Path) -> anyhow::Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("cannot read {}", path.display()))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("invalid PEM in {}", path.display()))?
+        .with_context(|| format!("no private key found in {}", path.display()))
+}
+
+// Reloads the certificate on SIGHUP and when its files change on disk, until shutdown
+pub async fn watch(tls: Arc<ServerTls>, shutdown: impl Future<Output = ()>) {
+    #[cfg(unix)]
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .expect("failed to install SIGHUP handler");
+    let mut ticker = tokio::time::interval(tls.config.reload_interval);
+    ticker.tick().await;
+    tokio::pin!(shutdown);
+
+    loop {
+        #[cfg(unix)]
+        let reload_requested = hangup.recv();
+        #[cfg(not(unix))]
+        let reload_requested = std::future::pending::<Option<()>>();
+
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = reload_requested => {
+                info!("Received SIGHUP");
+                if let Err(e) = tls.reload() {
+                    error!("TLS reload failed, keeping the current certificate: {:#}", e);
+                }
+            }
+            _ = ticker.tick() => {
+                if let Err(e) = tls.reload_if_changed() {
+                    error!("TLS reload failed, keeping the current certificate: {:#}", e);
+                }
+            }
+        }
+    }
+}
+
+// Accepts TCP connections and hands out those that completed the TLS handshake.
+// Handshakes run in their own tasks so a slow client doesn't hold up the others.
+pub struct TlsListener {
+    established: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, tls: Arc<ServerTls>) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (sender, established) = mpsc::channel(64);
+        tokio::spawn(accept_connections(listener, tls, sender));
+        Ok(Self { established, local_addr })
+    }
+}
+
+// Stops, closing the socket, once the `TlsListener` is dropped
+async fn accept_connections(
+    listener: TcpListener,
+    tls: Arc<ServerTls>,
+    sender: mpsc::Sender<(TlsStream<TcpStream>, SocketAddr)>,
+) {
+    loop {
+        let (stream, addr) = tokio::select! {
+            _ = sender.closed() => return,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // Usually out of file descriptors, give connections time to close
+                    warn!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+        };
+
+        let acceptor = TlsAcceptor::from(tls.server_config());
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => {
+                    let _ = sender.send((stream, addr)).await;
+                }
+                Ok(Err(e)) => debug!(remote_addr = %addr, "TLS handshake failed: {}", e),
+                Err(_) => debug!(remote_addr = %addr, "TLS handshake timed out"),
+            }
+        });
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.established.recv().await {
+            Some(connection) => connection,
+            // The accept loop only ends once the listener is gone
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+// The verified client certificate of an mTLS connection
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    pub subject: String,
+    pub common_name: Option<String>,
+}
+
+impl ClientCertificate {
+    fn of(stream: &TlsStream<TcpStream>) -> Option<Self> {
+        let der = stream.get_ref().1.peer_certificates()?.first()?;
+        let (_, cert) = X509Certificate::from_der(der).ok()?;
+        Some(Self {
+            subject: cert.subject().to_string(),
+            common_name: cert
+                .subject()
+                .iter_common_name()
+                .next()
+                .and_then(|name| name.as_str().ok())
+                .map(str::to_string),
+        })
+    }
+}
+
+// Per-connection details, in the extensions of every request on the connection
+#[derive(Debug, Clone)]
+pub struct TlsSession {
+    pub client_certificate: Option<ClientCertificate>,
+}
+
+// Serves the router over a `TlsListener`. Like `into_make_service_with_connect_info`,
+// every request carries `ConnectInfo<SocketAddr>`, along with the `TlsSession`.
+pub fn make_service(router: Router) -> TlsMakeService {
+    TlsMakeService(router)
+}
+
+#[derive(Clone)]
+pub struct TlsMakeService(Router);
+
+type TlsConnectionService = AddExtension<AddExtension<Router, ConnectInfo<SocketAddr>>, TlsSession>;
+
+impl Service<IncomingStream<'_, TlsListener>> for TlsMakeService {
+    type Response = TlsConnectionService;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, stream: IncomingStream<'_, TlsListener>) -> Self::Future {
+        let session = TlsSession { client_certificate: ClientCertificate::of(stream.io()) };
+        let service = Extension(ConnectInfo(*stream.remote_addr())).layer(self.0.clone());
+        ready(Ok(Extension(session).layer(service)))
+    }
+}
+
+// Router for the plain HTTP port: every request is redirected to the same host
+// and path on the HTTPS port. 308 keeps the method and body.
+pub fn redirect_app(https_port: u16) -> Router {
+    Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move { redirect(https_port, &headers, &uri) })
+}
+
+fn redirect(https_port: u16, headers: &HeaderMap, uri: &Uri) -> Response {
+    let Some(host) = headers.get(header::HOST).and_then(|host| host.to_str().ok()) else {
+        return (StatusCode::BAD_REQUEST, "Missing Host header").into_response();
+    };
+    // Drop the port of the plain HTTP listener, keeping IPv6 literals intact
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let authority = match https_port {
+        443 => host.to_string(),
+        port => format!("{}:{}", host, port),
+    };
+    let path = uri.path_and_query().map(|path| path.as_str()).unwrap_or("/");
+    match Uri::builder().scheme("https").authority(authority).path_and_query(path).build() {
+        Ok(location) => Redirect::permanent(&location.to_string()).into_response(),
+        Err(_) => (StatusCode::BAD_REQUEST, "Invalid Host header").into_response(),
+    }
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use common::{TestApp, PASSWORD};
+use http::StatusCode;
+use rustrest::admin::{execute, AdminCommand};
+use rustrest::auth::JwtAuth;
+use rustrest::Config;
+use sqlx::PgPool;
+use std::time::Duration;
+
+async fn admin(app: &TestApp, command: AdminCommand) -> serde_json::Value {
+    execute(command, app.pool(), &app.state.jwt_auth).await.unwrap()
+}
+
+#[sqlx::test]
+async fn create_user_and_reset_password(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let created = admin(&app, AdminCommand::CreateUser {
+        username: "ivan".to_string(),
+        email: "ivan@example.com".to_string(),
+        password: Some(PASSWORD.to_string()),
+    })
+    .await;
+    assert_eq!(created["username"], "ivan");
+    assert_eq!(app.login("ivan", PASSWORD).await.status, StatusCode::OK);
+
+    admin(&app, AdminCommand::ResetPassword {
+        username: "ivan".to_string(),
+        password: Some("a-brand-new-password".to_string()),
+    })
+    .await;
+    assert_eq!(app.login("ivan", PASSWORD).await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.login("ivan", "a-brand-new-password").await.status, StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn reset_password_enforces_strength(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.register("judy").await;
+
+    let result = execute(
+        AdminCommand::ResetPassword { username: "judy".to_string(), password: Some("weak".to_string()) },
+        app.pool(),
+        &app.state.jwt_auth,
+    )
+    .await;
+
+    assert!(result.is_err());
+}
+
+#[sqlx::test]
+async fn granted_roles_end_up_in_login_tokens(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.register("karl").await;
+
+    let granted = admin(&app, AdminCommand::GrantRole { username: "karl".to_string(), role: "Editor".to_string() }).await;
+    assert_eq!(granted["granted"], true);
+    assert_eq!(granted["roles"], serde_json::json!(["user", "editor"]));
+
+    let token = app.login("karl", PASSWORD).await.json()["access_token"].as_str().unwrap().to_string();
+    let claims = app.state.jwt_auth.verify_token(&token).unwrap();
+    assert_eq!(claims.roles, vec!["user", "editor"]);
+
+    let revoked = admin(&app, AdminCommand::RevokeRole { username: "karl".to_string(), role: "editor".to_string() }).await;
+    assert_eq!(revoked["revoked"], true);
+    assert_eq!(revoked["roles"], serde_json::json!(["user"]));
+}
+
+#[sqlx::test]
+async fn grant_role_rejects_unknown_roles(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.register("lena").await;
+
+    let result = execute(
+        AdminCommand::GrantRole { username: "lena".to_string(), role: "superuser".to_string() },
+        app.pool(),
+        &app.state.jwt_auth,
+    )
+    .await;
+
+    assert!(result.is_err());
+}
+
+#[sqlx::test]
+async fn minted_tokens_authenticate(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.register("mallory").await;
+
+    let minted = admin(&app, AdminCommand::MintToken { username: "mallory".to_string(), ttl_minutes: 5 }).await;
+
+    let token = minted["access_token"].as_str().unwrap();
+    assert_eq!(minted["expires_in"], 300);
+    assert_eq!(app.get("/posts", Some(token)).await.status, StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn rotated_keys_sign_new_tokens_and_old_tokens_stay_valid(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, old_token) = app.user_with_token("niaj").await;
+
+    let rotated = admin(&app, AdminCommand::RotateKeys).await;
+    app.state.jwt_auth.reload_keys(app.pool()).await.unwrap();
+
+    let new_token = app.login("niaj", PASSWORD).await.json()["access_token"].as_str().unwrap().to_string();
+    let header = jsonwebtoken::decode_header(&new_token).unwrap();
+    assert_eq!(header.kid.as_deref(), rotated["kid"].as_str());
+    assert_eq!(app.get("/posts", Some(&new_token)).await.status, StatusCode::OK);
+    assert_eq!(app.get("/posts", Some(&old_token)).await.status, StatusCode::OK);
+
+    // A second rotation keeps the first stored key around for verification
+    admin(&app, AdminCommand::RotateKeys).await;
+    app.state.jwt_auth.reload_keys(app.pool()).await.unwrap();
+    assert_eq!(app.get("/posts", Some(&new_token)).await.status, StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn stored_signing_keys_are_sealed(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, old_token) = app.user_with_token("petra").await;
+    // A key stored in plain before sealing, already retired
+    sqlx::query("INSERT INTO signing_keys (kid, secret, retired_at) VALUES ('legacy', $1, NOW())")
+        .bind(vec![7u8; 64])
+        .execute(app.pool())
+        .await
+        .unwrap();
+
+    let rotated = admin(&app, AdminCommand::RotateKeys).await;
+    let stored: Vec<(String, Vec<u8>, bool)> = sqlx::query_as("SELECT kid, secret, sealed FROM signing_keys ORDER BY created_at")
+        .fetch_all(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 2);
+    for (_, secret, sealed) in &stored {
+        assert!(sealed);
+        assert_ne!(secret, &vec![7u8; 64]);
+    }
+    assert_eq!(stored[1].0, rotated["kid"].as_str().unwrap());
+    // Nonce, 64 byte secret and tag
+    assert_eq!(stored[1].1.len(), 12 + 64 + 16);
+
+    assert_eq!(app.state.jwt_auth.reload_keys(app.pool()).await.unwrap(), 3);
+    let new_token = app.login("petra", PASSWORD).await.json()["access_token"].as_str().unwrap().to_string();
+    assert_eq!(app.get("/posts", Some(&new_token)).await.status, StatusCode::OK);
+    assert_eq!(app.get("/posts", Some(&old_token)).await.status, StatusCode::OK);
+
+    // Under another secret the sealed keys are skipped, not used
+    let other = JwtAuth::new(b"some-other-secret");
+    assert_eq!(other.reload_keys(app.pool()).await.unwrap(), 1);
+    assert!(other.verify_token(&new_token).is_err());
+}
+
+#[sqlx::test]
+async fn revocation_checks_are_cached_briefly(pool: PgPool) {
+    let config = Config { revocation_cache_ttl: Duration::from_secs(60), ..Config::default() };
+    let app = TestApp::with_config(pool.clone(), config);
+    let (_, token) = app.user_with_token("quinn").await;
+    assert_eq!(app.get("/posts", Some(&token)).await.status, StatusCode::OK);
+
+    admin(&app, AdminCommand::RevokeToken { token: token.clone() }).await;
+    // Still accepted where the check was cached, rejected everywhere else
+    assert_eq!(app.get("/posts", Some(&token)).await.status, StatusCode::OK);
+    let uncached = TestApp::with_config(pool, Config { revocation_cache_ttl: Duration::ZERO, ..Config::default() });
+    let rejected = uncached.get("/posts", Some(&token)).await;
+    assert_eq!(rejected.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(rejected.json()["error"], "Token has been revoked");
+}
+
+#[sqlx::test]
+async fn revoked_tokens_are_rejected_and_purged_after_expiry(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, token) = app.user_with_token("olivia").await;
+
+    admin(&app, AdminCommand::RevokeToken { token: token.clone() }).await;
+    assert_eq!(app.get("/posts", Some(&token)).await.status, StatusCode::UNAUTHORIZED);
+
+    let nothing = admin(&app, AdminCommand::PurgeExpiredTokens).await;
+    assert_eq!(nothing["purged"], 0);
+
+    sqlx::query("UPDATE revoked_tokens SET expires_at = NOW() - INTERVAL '1 minute'")
+        .execute(app.pool())
+        .await
+        .unwrap();
+    let purged = admin(&app, AdminCommand::PurgeExpiredTokens).await;
+    assert_eq!(purged["purged"], 1);
+}
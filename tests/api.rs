@@ -0,0 +1,151 @@
+mod common;
+
+use common::{TestApp, PASSWORD};
+use http::StatusCode;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn register_creates_user(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.register("alice").await;
+
+    assert_eq!(response.status, StatusCode::CREATED);
+    let body = response.json();
+    assert_eq!(body["username"], "alice");
+    assert_eq!(body["email"], "alice@example.com");
+    assert!(body.get("password_hash").is_none());
+}
+
+#[sqlx::test]
+async fn register_rejects_weak_password(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app
+        .post_json(
+            "/register",
+            json!({ "username": "bob", "email": "bob@example.com", "password": "short" }),
+            None,
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["error"], "Password must be at least 12 characters");
+}
+
+#[sqlx::test]
+async fn register_rejects_duplicate_username(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.register("carol").await;
+
+    let response = app.register("carol").await;
+
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn login_returns_bearer_token(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.register("dave").await;
+
+    let response = app.login("dave", PASSWORD).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let body = response.json();
+    assert_eq!(body["token_type"], "Bearer");
+    assert_eq!(body["username"], "dave");
+    let claims = app
+        .state
+        .jwt_auth
+        .verify_token(body["access_token"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(claims.sub, body["user_id"].to_string());
+}
+
+#[sqlx::test]
+async fn login_rejects_wrong_password(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.register("erin").await;
+
+    let response = app.login("erin", "not-the-password").await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn login_rejects_unknown_user(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.login("nobody", PASSWORD).await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn posts_require_token(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.get("/posts", None).await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn posts_reject_invalid_token(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.get("/posts", Some("not-a-jwt")).await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn get_posts_lists_all_posts(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("frank").await;
+    app.insert_post(user_id, "First", "Hello").await;
+    app.insert_post(user_id, "Second", "World").await;
+
+    let response = app.get("/posts", Some(&token)).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let posts = response.json();
+    assert_eq!(posts.as_array().unwrap().len(), 2);
+    assert_eq!(posts[0]["title"], "First");
+}
+
+#[sqlx::test]
+async fn get_post_returns_single_post(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("grace").await;
+    let id = app.insert_post(user_id, "Title", "Body").await;
+
+    let response = app.get(&format!("/posts/{}", id), Some(&token)).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let post = response.json();
+    assert_eq!(post["id"], id);
+    assert_eq!(post["user_id"], user_id);
+    assert_eq!(post["body"], "Body");
+}
+
+#[sqlx::test]
+async fn get_post_unknown_id_is_not_found(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, token) = app.user_with_token("heidi").await;
+
+    let response = app.get("/posts/9999", Some(&token)).await;
+
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn responses_carry_security_headers(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.get("/posts", None).await;
+
+    assert_eq!(response.header("x-content-type-options"), Some("nosniff"));
+    assert_eq!(response.header("x-frame-options"), Some("DENY"));
+}
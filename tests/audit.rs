@@ -0,0 +1,190 @@
+mod common;
+
+use common::{TestApp, PASSWORD};
+use http::StatusCode;
+use rustrest::admin::{execute, AdminCommand};
+use rustrest::audit::DenialLog;
+use rustrest::models::audit_record::{AuditFilter, AuditRecord, NewAuditRecord, OUTCOME_SUCCESS};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+fn event(action: &str) -> NewAuditRecord {
+    NewAuditRecord {
+        actor: "42".to_string(),
+        ip: Some("10.0.0.1".to_string()),
+        action: action.to_string(),
+        resource: Some("/posts/1".to_string()),
+        outcome: OUTCOME_SUCCESS,
+        request_id: None,
+        details: json!({ "status": 200 }),
+    }
+}
+
+async fn events(app: &TestApp, action: &str) -> Vec<Value> {
+    AuditRecord::search(&action_filter(action), app.pool())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|record| serde_json::to_value(record).unwrap())
+        .collect()
+}
+
+fn action_filter(action: &str) -> AuditFilter {
+    AuditFilter { action: Some(action.to_string()), ..AuditFilter::default() }
+}
+
+#[sqlx::test]
+async fn login_failures_are_recorded_and_searchable_by_admins(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("uma").await;
+    let admin = app.token_with_roles(user_id, &["admin"]);
+    let failed = app.login("uma", "wrong-password").await;
+    let request_id = failed.header("x-request-id").unwrap().to_string();
+
+    let response = app.get("/admin/audit-events?action=login.failed&outcome=failure", Some(&admin)).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let events = response.json()["events"].as_array().unwrap().clone();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["actor"], "anonymous");
+    assert_eq!(events[0]["request_id"], request_id);
+    assert_eq!(events[0]["ip"], "127.0.0.1");
+    assert_eq!(events[0]["details"]["username"], "uma");
+    assert_eq!(events[0]["hash"].as_str().unwrap().len(), 64);
+
+    // The handler recorded the failure, so there is no generic access.denied for it
+    let response = app.get(&format!("/admin/audit-events?request_id={}", request_id), Some(&admin)).await;
+    assert_eq!(response.json()["events"].as_array().unwrap().len(), 1);
+
+    let response = app.get("/admin/audit-events", Some(&token)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn registration_and_login_record_the_user(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let user_id = app.register("wendy").await.json()["id"].as_i64().unwrap();
+    app.login("wendy", PASSWORD).await;
+
+    let registered = events(&app, "user.registered").await;
+    assert_eq!(registered[0]["resource"], format!("user:{}", user_id));
+    let changes = &registered[0]["details"]["changes"];
+    assert_eq!(changes["username"], json!({ "before": null, "after": "wendy" }));
+    assert_eq!(changes["email"]["after"], "w***@example.com");
+
+    let login = events(&app, "login.succeeded").await;
+    assert_eq!(login[0]["actor"], user_id.to_string());
+}
+
+#[sqlx::test]
+async fn denied_requests_are_recorded_with_the_verified_actor(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("xena").await;
+
+    assert_eq!(app.get("/admin/log-level", Some(&token)).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(app.get("/posts", Some("garbage")).await.status, StatusCode::UNAUTHORIZED);
+
+    // Written in the background, in batches
+    assert!(events(&app, "access.denied").await.is_empty());
+    assert_eq!(app.state.denials.flush().await.unwrap(), 2);
+    let denied = events(&app, "access.denied").await;
+    assert_eq!(denied.len(), 2);
+    assert_eq!(denied[0]["actor"], "anonymous");
+    assert_eq!(denied[0]["resource"], "GET /posts");
+    assert_eq!(denied[1]["actor"], user_id.to_string());
+    assert_eq!(denied[1]["resource"], "GET /admin/log-level");
+    assert_eq!(denied[1]["details"]["status"], 403);
+}
+
+#[sqlx::test]
+async fn denials_beyond_the_queue_are_dropped(pool: PgPool) {
+    let log = DenialLog::with_capacity(pool.clone(), 2);
+    for _ in 0..3 {
+        log.record(event("access.denied"));
+    }
+
+    assert_eq!(log.flush().await.unwrap(), 2);
+    assert_eq!(log.flush().await.unwrap(), 0);
+    let verification = AuditRecord::verify_chain(&pool).await.unwrap();
+    assert!(verification.is_intact());
+    assert_eq!(verification.events, 2);
+}
+
+#[sqlx::test]
+async fn admin_commands_record_role_changes(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    app.register("yara").await;
+    let grant = || AdminCommand::GrantRole { username: "yara".to_string(), role: "editor".to_string() };
+
+    execute(grant(), &pool, &app.state.jwt_auth).await.unwrap();
+    execute(grant(), &pool, &app.state.jwt_auth).await.unwrap();
+
+    let granted = events(&app, "role.granted").await;
+    assert_eq!(granted.len(), 1, "granting a role twice changes nothing");
+    assert!(granted[0]["actor"].as_str().unwrap().starts_with("rustrest-admin:"));
+    assert_eq!(granted[0]["details"]["role"], "editor");
+    assert_eq!(granted[0]["details"]["changes"]["roles"]["before"], json!(["user"]));
+    assert_eq!(granted[0]["details"]["changes"]["roles"]["after"], json!(["user", "editor"]));
+    assert!(AuditRecord::verify_chain(&pool).await.unwrap().is_intact());
+}
+
+#[sqlx::test]
+async fn search_pages_newest_first(pool: PgPool) {
+    for action in ["first", "second", "third"] {
+        AuditRecord::append(event(action), &pool).await.unwrap();
+    }
+    let app = TestApp::new(pool);
+    let (user_id, _) = app.user_with_token("vic").await;
+    let admin = app.token_with_roles(user_id, &["admin"]);
+
+    let page = app.get("/admin/audit-events?actor=42&limit=2", Some(&admin)).await.json();
+    let actions: Vec<_> = page["events"].as_array().unwrap().iter().map(|e| e["action"].clone()).collect();
+    assert_eq!(actions, ["third", "second"]);
+
+    let next = app
+        .get(&format!("/admin/audit-events?actor=42&limit=2&before={}", page["next_before"]), Some(&admin))
+        .await
+        .json();
+    assert_eq!(next["events"][0]["action"], "first");
+}
+
+#[sqlx::test]
+async fn audit_events_are_append_only(pool: PgPool) {
+    AuditRecord::append(event("post.deleted"), &pool).await.unwrap();
+
+    assert!(sqlx::query("UPDATE audit_events SET actor = 'someone-else'").execute(&pool).await.is_err());
+    assert!(sqlx::query("DELETE FROM audit_events").execute(&pool).await.is_err());
+}
+
+#[sqlx::test]
+async fn verify_chain_finds_tampered_events(pool: PgPool) {
+    let app = TestApp::new(pool.clone());
+    let mut ids = Vec::new();
+    for action in ["role.granted", "post.deleted", "role.revoked"] {
+        ids.push(AuditRecord::append(event(action), &pool).await.unwrap().id);
+    }
+
+    let report = execute(AdminCommand::VerifyAuditChain, &pool, &app.state.jwt_auth).await.unwrap();
+    assert_eq!(report, json!({ "events": 3, "intact": true }));
+
+    // Somebody with direct database access rewrites history
+    sqlx::query("ALTER TABLE audit_events DISABLE TRIGGER USER").execute(&pool).await.unwrap();
+    sqlx::query("UPDATE audit_events SET actor = 'someone-else' WHERE id = $1")
+        .bind(ids[1])
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let verification = AuditRecord::verify_chain(&pool).await.unwrap();
+    assert!(!verification.is_intact());
+    assert_eq!(verification.broken_at, Some(ids[1]));
+    assert_eq!(verification.events, 1);
+    let error = execute(AdminCommand::VerifyAuditChain, &pool, &app.state.jwt_auth).await.unwrap_err();
+    assert!(error.to_string().contains(&format!("event {}", ids[1])));
+
+    // Deleting a row breaks the link of the next one
+    sqlx::query("UPDATE audit_events SET actor = '42' WHERE id = $1").bind(ids[1]).execute(&pool).await.unwrap();
+    assert!(AuditRecord::verify_chain(&pool).await.unwrap().is_intact());
+    sqlx::query("DELETE FROM audit_events WHERE id = $1").bind(ids[1]).execute(&pool).await.unwrap();
+    assert_eq!(AuditRecord::verify_chain(&pool).await.unwrap().broken_at, Some(ids[2]));
+}
@@ -0,0 +1,213 @@
+mod common;
+
+use common::{TestApp, TestResponse};
+use http::{Method, StatusCode};
+use rustrest::Config;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::time::Duration;
+
+// Inserts a published post by `user_id`
+async fn published(app: &TestApp, user_id: i32, title: &str) -> i32 {
+    let id = app.insert_post(user_id, title, "Body").await;
+    sqlx::query("UPDATE posts SET status = 'published', published_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(app.pool())
+        .await
+        .unwrap();
+    id
+}
+
+async fn comment(app: &TestApp, post_id: i32, body: Value, token: &str) -> TestResponse {
+    app.post_json(&format!("/posts/{}/comments", post_id), body, Some(token)).await
+}
+
+async fn approve(app: &TestApp, id: &Value, token: &str) -> TestResponse {
+    app.post_json(&format!("/comments/{}/approve", id), json!({}), Some(token)).await
+}
+
+async fn bodies(app: &TestApp, uri: &str, token: &str) -> Vec<String> {
+    let page = app.get(uri, Some(token)).await.json();
+    page["comments"].as_array().unwrap().iter().map(|c| c["body"].as_str().unwrap().to_string()).collect()
+}
+
+#[sqlx::test]
+async fn comments_are_threaded_and_wait_for_approval(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, author) = app.user_with_token("petra").await;
+    let (_, reader) = app.user_with_token("quinn").await;
+    let editor = app.token_with_roles(user_id, &["user", "editor"]);
+    let post_id = published(&app, user_id, "Threads").await;
+    let draft = app.insert_post(user_id, "Draft", "Body").await;
+
+    assert_eq!(comment(&app, draft, json!({ "body": "Early" }), &author).await.status, StatusCode::CONFLICT);
+    assert_eq!(comment(&app, post_id, json!({ "body": " " }), &reader).await.status, StatusCode::BAD_REQUEST);
+    let first = comment(&app, post_id, json!({ "body": "First" }), &reader).await;
+    assert_eq!(first.status, StatusCode::CREATED, "{}", first.text());
+    let first = first.json();
+    assert_eq!(first["status"], "pending");
+    let second = comment(&app, post_id, json!({ "body": "Second" }), &author).await.json();
+    // Only visible comments can be replied to
+    let early = comment(&app, post_id, json!({ "body": "Reply", "parent_id": first["id"] }), &author).await;
+    assert_eq!(early.status, StatusCode::NOT_FOUND);
+    approve(&app, &first["id"], &editor).await;
+    let reply = comment(&app, post_id, json!({ "body": "Reply", "parent_id": first["id"] }), &author).await.json();
+    approve(&app, &reply["id"], &editor).await;
+    let nested = comment(&app, post_id, json!({ "body": "Nested", "parent_id": reply["id"] }), &reader).await.json();
+    assert_eq!(nested["root_id"], first["id"]);
+
+    // Pending comments are only shown to their authors and editors
+    let uri = format!("/posts/{}/comments?view=flat", post_id);
+    assert_eq!(bodies(&app, &uri, &reader).await, ["First", "Reply", "Nested"]);
+    let queue = app.get("/comments/moderation", Some(&editor)).await;
+    assert_eq!(queue.json()["total"], 2);
+    assert_eq!(app.get("/comments/moderation", Some(&reader)).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(approve(&app, &second["id"], &reader).await.status, StatusCode::FORBIDDEN);
+    for id in [&second["id"], &nested["id"]] {
+        assert_eq!(approve(&app, id, &editor).await.status, StatusCode::OK);
+    }
+
+    let page = app.get(&format!("/posts/{}/comments?per_page=1", post_id), Some(&reader)).await.json();
+    assert_eq!(page["total"], 2);
+    let thread = &page["comments"][0];
+    assert_eq!(thread["body"], "First");
+    assert_eq!(thread["replies"][0]["body"], "Reply");
+    assert_eq!(thread["replies"][0]["replies"][0]["body"], "Nested");
+    assert_eq!(bodies(&app, &format!("/posts/{}/comments?page=2&per_page=1", post_id), &reader).await, ["Second"]);
+    let deep = app.get(&format!("/posts/{}/comments?page={}&per_page=200", post_id, i64::MAX), Some(&reader)).await;
+    assert_eq!(deep.status, StatusCode::OK, "{}", deep.text());
+    assert_eq!(deep.json()["comments"], json!([]));
+    assert_eq!(bodies(&app, &uri, &reader).await, ["First", "Second", "Reply", "Nested"]);
+
+    // Replies stay on their parent's post
+    let other = published(&app, user_id, "Other").await;
+    let stray = comment(&app, other, json!({ "body": "Stray", "parent_id": first["id"] }), &reader).await;
+    assert_eq!(stray.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn authors_edit_and_delete_within_the_window(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, author) = app.user_with_token("rosa").await;
+    let (_, other) = app.user_with_token("sami").await;
+    let editor = app.token_with_roles(user_id, &["user", "editor"]);
+    let post_id = published(&app, user_id, "Edits").await;
+    let created = comment(&app, post_id, json!({ "body": "Tpyo" }), &author).await.json();
+    approve(&app, &created["id"], &editor).await;
+    let uri = format!("/comments/{}", created["id"]);
+
+    let edit = json!({ "body": "Typo" });
+    assert_eq!(app.request(Method::PUT, &uri, Some(edit.clone()), Some(&other)).await.status, StatusCode::FORBIDDEN);
+    let edited = app.request(Method::PUT, &uri, Some(edit), Some(&author)).await;
+    assert_eq!(edited.status, StatusCode::OK, "{}", edited.text());
+    let edited = edited.json();
+    assert_eq!(edited["body"], "Typo");
+    assert!(edited["edited_at"].is_string());
+    // Edits go back through moderation
+    assert_eq!(edited["status"], "pending");
+
+    sqlx::query("UPDATE comments SET created_at = NOW() - INTERVAL '1 hour'")
+        .execute(app.pool())
+        .await
+        .unwrap();
+    let late = app.request(Method::PUT, &uri, Some(json!({ "body": "Later" })), Some(&author)).await;
+    assert_eq!(late.status, StatusCode::FORBIDDEN);
+    assert_eq!(late.json()["error"], "Comments can only be changed within 15 minutes");
+    assert_eq!(app.request(Method::DELETE, &uri, None, Some(&author)).await.status, StatusCode::FORBIDDEN);
+
+    let fresh = comment(&app, post_id, json!({ "body": "Oops" }), &author).await.json();
+    approve(&app, &fresh["id"], &editor).await;
+    let reply = comment(&app, post_id, json!({ "body": "Reply", "parent_id": fresh["id"] }), &other).await.json();
+    approve(&app, &reply["id"], &editor).await;
+    let uri = format!("/comments/{}", fresh["id"]);
+    assert_eq!(app.request(Method::DELETE, &uri, None, Some(&author)).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(app.request(Method::DELETE, &uri, None, Some(&author)).await.status, StatusCode::NOT_FOUND);
+    // Nor can editors bring a deleted comment back
+    assert_eq!(approve(&app, &fresh["id"], &editor).await.status, StatusCode::NOT_FOUND);
+
+    // Deleted comments keep their place in the thread, without a body
+    let page = app.get(&format!("/posts/{}/comments", post_id), Some(&other)).await.json();
+    let thread = &page["comments"][0];
+    assert_eq!(thread["body"], "");
+    assert!(thread["deleted_at"].is_string());
+    assert_eq!(thread["replies"][0]["body"], "Reply");
+}
+
+#[sqlx::test]
+async fn short_edit_windows_are_given_in_seconds(pool: PgPool) {
+    let app = TestApp::with_config(pool, Config { comment_edit_window: Duration::from_secs(30), ..Config::default() });
+    let (user_id, author) = app.user_with_token("rune").await;
+    let post_id = published(&app, user_id, "Quick").await;
+    let created = comment(&app, post_id, json!({ "body": "Fast" }), &author).await.json();
+    sqlx::query("UPDATE comments SET created_at = NOW() - INTERVAL '1 minute'")
+        .execute(app.pool())
+        .await
+        .unwrap();
+
+    let late = app.request(Method::PUT, &format!("/comments/{}", created["id"]), Some(json!({ "body": "Late" })), Some(&author)).await;
+    assert_eq!(late.status, StatusCode::FORBIDDEN);
+    assert_eq!(late.json()["error"], "Comments can only be changed within 30 seconds");
+}
+
+#[sqlx::test]
+async fn editors_hide_comments_and_ban_authors(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, _) = app.user_with_token("tove").await;
+    let (troll_id, troll) = app.user_with_token("ulf").await;
+    let editor = app.token_with_roles(user_id, &["user", "editor"]);
+    let post_id = published(&app, user_id, "Moderated").await;
+    let spam = comment(&app, post_id, json!({ "body": "Spam" }), &troll).await.json();
+    let more = comment(&app, post_id, json!({ "body": "More spam" }), &troll).await.json();
+
+    let hidden = app.post_json(&format!("/comments/{}/hide", more["id"]), json!({}), Some(&editor)).await;
+    assert_eq!(hidden.json()["status"], "hidden");
+
+    let uri = format!("/comments/{}/ban", spam["id"]);
+    assert_eq!(app.post_json(&uri, json!({}), Some(&troll)).await.status, StatusCode::FORBIDDEN);
+    let ban = app.post_json(&uri, json!({ "reason": "spam" }), Some(&editor)).await;
+    assert_eq!(ban.status, StatusCode::OK, "{}", ban.text());
+    assert_eq!(ban.json()["user_id"], troll_id);
+    assert_eq!(app.get("/comments/moderation", Some(&editor)).await.json()["total"], 0);
+    assert_eq!(comment(&app, post_id, json!({ "body": "Again" }), &troll).await.status, StatusCode::FORBIDDEN);
+
+    let lift = format!("/comments/bans/{}", troll_id);
+    assert_eq!(app.request(Method::DELETE, &lift, None, Some(&editor)).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(app.request(Method::DELETE, &lift, None, Some(&editor)).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(comment(&app, post_id, json!({ "body": "Sorry" }), &troll).await.status, StatusCode::CREATED);
+}
+
+#[sqlx::test]
+async fn posts_close_or_disable_comments(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, author) = app.user_with_token("vera").await;
+    let (_, reader) = app.user_with_token("wim").await;
+    let editor = app.token_with_roles(user_id, &["user", "editor"]);
+    let post_id = published(&app, user_id, "Settings").await;
+    let created = comment(&app, post_id, json!({ "body": "Before" }), &reader).await.json();
+    approve(&app, &created["id"], &editor).await;
+
+    let settings = format!("/posts/{}/comments/settings", post_id);
+    let set = |setting: &str| json!({ "comments": setting });
+    assert_eq!(app.put_if_match(post_id, &settings, set("closed"), &reader).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(app.put_if_match(post_id, &settings, set("never"), &author).await.status.as_u16(), 422);
+    assert_eq!(app.request(Method::PUT, &settings, Some(set("closed")), Some(&author)).await.status, StatusCode::PRECONDITION_REQUIRED);
+    let stale = app.get(&format!("/posts/{}", post_id), Some(&author)).await.header("etag").unwrap().to_string();
+    let closed = app.put_if_match(post_id, &settings, set("closed"), &author).await;
+    assert_eq!(closed.json()["comments"], "closed");
+    let overwrite = app.request_with_headers(Method::PUT, &settings, Some(set("open")), Some(&editor), &[("if-match", &stale)]).await;
+    assert_eq!(overwrite.status, StatusCode::PRECONDITION_FAILED);
+
+    // Closed: the existing comments stay readable
+    assert_eq!(comment(&app, post_id, json!({ "body": "After" }), &reader).await.status, StatusCode::CONFLICT);
+    let uri = format!("/posts/{}/comments", post_id);
+    assert_eq!(bodies(&app, &uri, &reader).await, ["Before"]);
+
+    // Disabled: comments are gone from view
+    app.put_if_match(post_id, &settings, set("disabled"), &editor).await;
+    assert!(bodies(&app, &uri, &reader).await.is_empty());
+    let edit = app.request(Method::PUT, &format!("/comments/{}", created["id"]), Some(json!({ "body": "x" })), Some(&reader)).await;
+    assert_eq!(edit.status, StatusCode::NOT_FOUND);
+
+    app.put_if_match(post_id, &settings, set("open"), &author).await;
+    assert_eq!(comment(&app, post_id, json!({ "body": "After" }), &reader).await.status, StatusCode::CREATED);
+}
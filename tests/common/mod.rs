@@ -0,0 +1,183 @@
+// Shared harness for the integration tests: drives the router in-process
+// with `tower::ServiceExt::oneshot`, no listener required.
+#![allow(dead_code)]
+
+use axum::body::{Body, Bytes};
+use axum::extract::connect_info::MockConnectInfo;
+use axum::Router;
+use http::{header, HeaderMap, Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use metrics_exporter_prometheus::PrometheusHandle;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use tower::ServiceExt;
+
+use rustrest::auth::jwt::Claims;
+use rustrest::services::metrics::install_recorder;
+use rustrest::{app, AppState, Config};
+
+pub const PASSWORD: &str = "correct-horse-battery";
+
+// The recorder is global, all tests in a binary share one
+fn recorder() -> PrometheusHandle {
+    static RECORDER: OnceLock<PrometheusHandle> = OnceLock::new();
+    RECORDER.get_or_init(|| install_recorder().unwrap()).clone()
+}
+
+pub struct TestApp {
+    pub router: Router,
+    pub state: AppState,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).expect("response body is not JSON")
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+}
+
+impl TestApp {
+    pub fn new(pool: PgPool) -> Self {
+        Self::with_config(pool, Config::default())
+    }
+
+    pub fn with_config(pool: PgPool, config: Config) -> Self {
+        let state = AppState::new(pool, config, recorder());
+        let router = app(state.clone())
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+        Self { router, state }
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.state.pool
+    }
+
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        TestResponse { status, headers, body }
+    }
+
+    pub async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+        token: Option<&str>,
+    ) -> TestResponse {
+        self.request_with_headers(method, uri, body, token, &[]).await
+    }
+
+    pub async fn request_with_headers(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+        token: Option<&str>,
+        headers: &[(&str, &str)],
+    ) -> TestResponse {
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let request = match body {
+            Some(body) => builder
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => builder.body(Body::empty()),
+        };
+        self.send(request.unwrap()).await
+    }
+
+    pub async fn get(&self, uri: &str, token: Option<&str>) -> TestResponse {
+        self.request(Method::GET, uri, None, token).await
+    }
+
+    pub async fn post_json(&self, uri: &str, body: Value, token: Option<&str>) -> TestResponse {
+        self.request(Method::POST, uri, Some(body), token).await
+    }
+
+    pub async fn register(&self, username: &str) -> TestResponse {
+        self.post_json(
+            "/register",
+            json!({
+                "username": username,
+                "email": format!("{}@example.com", username),
+                "password": PASSWORD,
+            }),
+            None,
+        )
+        .await
+    }
+
+    pub async fn login(&self, username: &str, password: &str) -> TestResponse {
+        self.post_json(
+            "/login",
+            json!({ "username": username, "password": password }),
+            None,
+        )
+        .await
+    }
+
+    // Registers a user and returns its id and a valid access token
+    pub async fn user_with_token(&self, username: &str) -> (i32, String) {
+        let registered = self.register(username).await;
+        assert_eq!(registered.status, StatusCode::CREATED, "{}", registered.text());
+        let login = self.login(username, PASSWORD).await;
+        assert_eq!(login.status, StatusCode::OK, "{}", login.text());
+        let body = login.json();
+        (
+            body["user_id"].as_i64().unwrap() as i32,
+            body["access_token"].as_str().unwrap().to_string(),
+        )
+    }
+
+    // Mints a token directly, for roles that cannot be obtained through /login
+    pub fn token_with_roles(&self, user_id: i32, roles: &[&str]) -> String {
+        let claims = Claims::new(
+            user_id.to_string(),
+            roles.iter().map(|r| r.to_string()).collect(),
+            chrono::Duration::minutes(5),
+        );
+        self.state.jwt_auth.create_token(&claims).unwrap()
+    }
+
+    pub async fn insert_post(&self, user_id: i32, title: &str, body: &str) -> i32 {
+        sqlx::query_scalar("INSERT INTO posts (user_id, title, body) VALUES ($1, $2, $3) RETURNING id")
+            .bind(user_id)
+            .bind(title)
+            .bind(body)
+            .fetch_one(self.pool())
+            .await
+            .unwrap()
+    }
+
+    // PUT `body` to `uri` with the post's current ETag in `If-Match`, as the
+    // token's user sees it (none when they can't see the post)
+    pub async fn put_if_match(&self, post_id: impl std::fmt::Display, uri: &str, body: Value, token: &str) -> TestResponse {
+        let current = self.get(&format!("/posts/{}", post_id), Some(token)).await;
+        let etag = current.header("etag").map(str::to_string);
+        let headers: Vec<_> = etag.iter().map(|etag| ("if-match", etag.as_str())).collect();
+        self.request_with_headers(Method::PUT, uri, Some(body), Some(token), &headers).await
+    }
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use axum::body::Body;
+use common::TestApp;
+use http::{Method, Request, StatusCode};
+use rustrest::config::CorsConfig;
+use rustrest::Config;
+use sqlx::PgPool;
+use std::time::Duration;
+
+fn app_with_cors(pool: PgPool) -> TestApp {
+    TestApp::with_config(pool, Config {
+        cors: Some(CorsConfig {
+            allowed_origins: vec!["https://blog.example.org".to_string(), "https://*.example.com".to_string()],
+            allow_credentials: true,
+            max_age: Duration::from_secs(120),
+            ..CorsConfig::default()
+        }),
+        ..Config::default()
+    })
+}
+
+fn preflight(uri: &str, origin: &str) -> Request<Body> {
+    Request::builder()
+        .method(Method::OPTIONS)
+        .uri(uri)
+        .header("origin", origin)
+        .header("access-control-request-method", "GET")
+        .header("access-control-request-headers", "authorization")
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn preflights_are_answered_without_a_token(pool: PgPool) {
+    let app = app_with_cors(pool);
+
+    let response = app.send(preflight("/posts", "https://blog.example.org")).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://blog.example.org"));
+    assert_eq!(response.header("access-control-allow-credentials"), Some("true"));
+    assert_eq!(response.header("access-control-max-age"), Some("120"));
+    assert!(response.header("access-control-allow-methods").unwrap().contains("DELETE"));
+    assert!(response.header("access-control-allow-headers").unwrap().contains("authorization"));
+}
+
+#[sqlx::test]
+async fn wildcard_origins_match_subdomains_only(pool: PgPool) {
+    let app = app_with_cors(pool);
+
+    for (origin, allowed) in [
+        ("https://app.example.com", true),
+        ("https://eu.app.example.com", true),
+        ("https://example.com", false),
+        ("https://evilexample.com", false),
+        ("http://app.example.com", false),
+        ("https://app.example.com.evil.net", false),
+    ] {
+        let response = app.send(preflight("/posts", origin)).await;
+        assert_eq!(response.header("access-control-allow-origin").is_some(), allowed, "{}", origin);
+    }
+}
+
+#[sqlx::test]
+async fn responses_carry_cors_headers(pool: PgPool) {
+    let app = app_with_cors(pool);
+    let (_, token) = app.user_with_token("bea").await;
+
+    for token in [Some(token.as_str()), None] {
+        let mut request = Request::get("/posts").header("origin", "https://app.example.com");
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        let response = app.send(request.body(Body::empty()).unwrap()).await;
+
+        assert_eq!(response.header("access-control-allow-origin"), Some("https://app.example.com"));
+        assert!(response.header("access-control-expose-headers").unwrap().contains("x-request-id"));
+    }
+}
+
+#[sqlx::test]
+async fn cors_is_off_unless_configured(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.send(preflight("/posts", "https://blog.example.org")).await;
+
+    assert_eq!(response.header("access-control-allow-origin"), None);
+    assert_ne!(response.status, StatusCode::OK);
+}
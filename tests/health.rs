@@ -0,0 +1,110 @@
+mod common;
+
+use common::TestApp;
+use http::StatusCode;
+use sqlx::PgPool;
+use std::time::Duration;
+
+#[sqlx::test]
+async fn healthz_is_public(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.get("/healthz", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let body = response.json();
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["checks"]["process"]["status"], "ok");
+}
+
+#[sqlx::test]
+async fn readyz_reports_each_check_with_latency(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.get("/readyz", None).await;
+
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    let body = response.json();
+    assert_eq!(body["status"], "ok");
+    for check in ["database", "migrations", "signing_keys"] {
+        assert_eq!(body["checks"][check]["status"], "ok", "{}", check);
+        assert!(body["checks"][check]["latency_ms"].is_number(), "{}", check);
+    }
+    assert!(body.get("pool").is_none());
+}
+
+#[sqlx::test]
+async fn readyz_fails_with_pending_migrations(pool: PgPool) {
+    let app = TestApp::new(pool);
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = (SELECT max(version) FROM _sqlx_migrations)")
+        .execute(app.pool())
+        .await
+        .unwrap();
+
+    let response = app.get("/readyz", None).await;
+
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    let body = response.json();
+    assert_eq!(body["status"], "fail");
+    assert_eq!(body["checks"]["migrations"]["status"], "fail");
+    assert_eq!(body["checks"]["database"]["status"], "ok");
+}
+
+#[sqlx::test]
+async fn readyz_fails_while_draining(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    app.state.lifecycle.begin_shutdown();
+
+    let response = app.get("/readyz", None).await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.json()["status"], "draining");
+}
+
+#[sqlx::test]
+async fn readyz_verbose_requires_admin(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let user = app.token_with_roles(1, &["user"]);
+
+    let anonymous = app.get("/readyz?verbose=true", None).await;
+    assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+
+    let forbidden = app.get("/readyz?verbose=true", Some(&user)).await;
+    assert_eq!(forbidden.status, StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn readyz_verbose_shows_pool_statistics(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let admin = app.token_with_roles(1, &["admin"]);
+
+    let response = app.get("/readyz?verbose=true", Some(&admin)).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let pool = &response.json()["pool"];
+    assert!(pool["size"].is_number());
+    assert!(pool["idle"].is_number());
+    assert!(pool["max_connections"].is_number());
+}
+
+#[sqlx::test]
+async fn listener_stays_open_for_the_pre_stop_delay(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let lifecycle = app.state.lifecycle.clone();
+
+    let shutdown = tokio::spawn({
+        let lifecycle = lifecycle.clone();
+        async move { lifecycle.shutdown_after(Duration::from_millis(200)).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // Not ready, but still serving
+    assert!(lifecycle.is_draining());
+    assert_eq!(app.get("/readyz", None).await.status, StatusCode::SERVICE_UNAVAILABLE);
+    let stopped = tokio::time::timeout(Duration::from_millis(10), lifecycle.shutdown_requested()).await;
+    assert!(stopped.is_err(), "the listener closed before the pre-stop delay");
+
+    shutdown.await.unwrap();
+    let stopped = tokio::time::timeout(Duration::from_millis(10), lifecycle.shutdown_requested()).await;
+    assert!(stopped.is_ok());
+}
@@ -0,0 +1,205 @@
+mod common;
+
+use chrono::{Duration as TimeDelta, Utc};
+use common::TestApp;
+use http::{Method, StatusCode};
+use rustrest::jobs::{self, JobRunner, PUBLISH_POST};
+use rustrest::models::audit_record::{AuditFilter, AuditRecord};
+use rustrest::models::job::Job;
+use serde_json::json;
+use sqlx::PgPool;
+use std::time::Duration;
+
+// Submits a new post by `author` and returns its id
+async fn submitted_post(app: &TestApp, author: &str, title: &str) -> i64 {
+    let created = app.post_json("/posts", json!({ "title": title, "body": "Body" }), Some(author)).await;
+    let id = created.json()["id"].as_i64().unwrap();
+    app.post_json(&format!("/posts/{}/submit", id), json!({}), Some(author)).await;
+    id
+}
+
+// Moves the post's schedule and pending jobs `by` into the past
+async fn travel(app: &TestApp, id: i64, by: TimeDelta) {
+    sqlx::query("UPDATE posts SET publish_at = publish_at - $2, unpublish_at = unpublish_at - $2 WHERE id = $1")
+        .bind(id as i32)
+        .bind(by)
+        .execute(app.pool())
+        .await
+        .unwrap();
+    sqlx::query("UPDATE jobs SET run_at = run_at - $1 WHERE status = 'pending'")
+        .bind(by)
+        .execute(app.pool())
+        .await
+        .unwrap();
+}
+
+#[sqlx::test]
+async fn scheduled_posts_go_live_and_expire_on_time(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, author) = app.user_with_token("wendy").await;
+    let (editor_id, reader) = app.user_with_token("xavier").await;
+    let editor = app.token_with_roles(editor_id, &["user", "editor"]);
+    let runner = JobRunner::new(app.pool().clone());
+    let id = submitted_post(&app, &author, "Embargoed").await;
+
+    let now = Utc::now();
+    let schedule = json!({ "publish_at": now + TimeDelta::hours(1), "unpublish_at": now + TimeDelta::hours(3) });
+    let response = app.request(Method::PUT, &format!("/posts/{}/schedule", id), Some(schedule), Some(&editor)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    let approved = app.post_json(&format!("/posts/{}/approve", id), json!({}), Some(&editor)).await;
+    assert_eq!(approved.json()["status"], "scheduled");
+
+    // Nothing is due yet
+    assert_eq!(runner.run_due().await, 0);
+    assert_eq!(app.get(&format!("/posts/{}", id), Some(&reader)).await.status, StatusCode::NOT_FOUND);
+
+    travel(&app, id, TimeDelta::hours(2)).await;
+    assert_eq!(runner.run_due().await, 1);
+    let post = app.get(&format!("/posts/{}", id), Some(&reader)).await;
+    assert_eq!(post.json()["status"], "published");
+
+    travel(&app, id, TimeDelta::hours(2)).await;
+    assert_eq!(runner.run_due().await, 1);
+    assert_eq!(app.get(&format!("/posts/{}", id), Some(&reader)).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(app.get(&format!("/posts/{}", id), Some(&author)).await.json()["status"], "archived");
+
+    let filter = AuditFilter { actor: Some(jobs::SCHEDULER.to_string()), ..AuditFilter::default() };
+    let actions: Vec<String> = AuditRecord::search(&filter, app.pool()).await.unwrap().into_iter().map(|e| e.action).collect();
+    assert_eq!(actions, ["post.unpublished", "post.published"]);
+}
+
+#[sqlx::test]
+async fn schedules_are_validated(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (author_id, author) = app.user_with_token("yusuf").await;
+    let editor = app.token_with_roles(author_id, &["user", "editor"]);
+    let id = submitted_post(&app, &author, "Timing").await;
+    let uri = format!("/posts/{}/schedule", id);
+    let schedule = |body| app.request(Method::PUT, &uri, Some(body), Some(&editor));
+
+    let now = Utc::now();
+    let backwards = json!({ "publish_at": now + TimeDelta::hours(2), "unpublish_at": now + TimeDelta::hours(1) });
+    assert_eq!(schedule(backwards).await.status, StatusCode::BAD_REQUEST);
+    let by_author = app.request(Method::PUT, &uri, Some(json!({ "publish_at": now })), Some(&author)).await;
+    assert_eq!(by_author.status, StatusCode::FORBIDDEN);
+
+    // A past publish_at means approving publishes right away
+    assert_eq!(schedule(json!({ "publish_at": now - TimeDelta::minutes(1) })).await.status, StatusCode::OK);
+    let approved = app.post_json(&format!("/posts/{}/approve", id), json!({}), Some(&editor)).await;
+    assert_eq!(approved.json()["status"], "published");
+    assert_eq!(schedule(json!({ "publish_at": now + TimeDelta::hours(1) })).await.status, StatusCode::CONFLICT);
+}
+
+#[sqlx::test]
+async fn approvals_and_their_jobs_are_stored_together(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (editor_id, author) = app.user_with_token("zora").await;
+    let editor = app.token_with_roles(editor_id, &["user", "editor"]);
+    let id = submitted_post(&app, &author, "Atomic").await;
+    let schedule = json!({ "publish_at": Utc::now() + TimeDelta::hours(1) });
+    app.request(Method::PUT, &format!("/posts/{}/schedule", id), Some(schedule), Some(&editor)).await;
+
+    // The job can't be stored, so the approval isn't either
+    sqlx::query("ALTER TABLE jobs ADD CONSTRAINT no_jobs CHECK (FALSE) NOT VALID").execute(app.pool()).await.unwrap();
+    let approved = app.post_json(&format!("/posts/{}/approve", id), json!({}), Some(&editor)).await;
+    assert_eq!(approved.status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(app.get(&format!("/posts/{}", id), Some(&editor)).await.json()["status"], "in_review");
+
+    sqlx::query("ALTER TABLE jobs DROP CONSTRAINT no_jobs").execute(app.pool()).await.unwrap();
+    let approved = app.post_json(&format!("/posts/{}/approve", id), json!({}), Some(&editor)).await;
+    assert_eq!(approved.json()["status"], "scheduled");
+    let pending: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM jobs WHERE kind = $1 AND status = 'pending'")
+        .bind(PUBLISH_POST)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(pending, 1);
+}
+
+#[sqlx::test]
+async fn concurrent_workers_never_run_a_job_twice(pool: PgPool) {
+    let app = TestApp::new(pool);
+    for post_id in 0..20 {
+        Job::enqueue(PUBLISH_POST, json!({ "post_id": post_id }), Utc::now(), app.pool()).await.unwrap();
+    }
+
+    let first = JobRunner::new(app.pool().clone());
+    let second = JobRunner::new(app.pool().clone());
+    let (ran_first, ran_second) = tokio::join!(first.run_due(), second.run_due());
+
+    assert_eq!(ran_first + ran_second, 20);
+    let attempts: Vec<i32> = sqlx::query_scalar("SELECT attempts FROM jobs WHERE status = 'succeeded'")
+        .fetch_all(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(attempts, vec![1; 20]);
+}
+
+#[sqlx::test]
+async fn jobs_of_stopped_workers_are_claimed_again_until_out_of_attempts(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let stopped = Job::enqueue(PUBLISH_POST, json!({ "post_id": 1 }), Utc::now(), app.pool()).await.unwrap();
+    let exhausted = Job::enqueue(PUBLISH_POST, json!({ "post_id": 2 }), Utc::now(), app.pool()).await.unwrap();
+    sqlx::query("UPDATE jobs SET status = 'running', locked_by = 'gone', locked_at = NOW() - INTERVAL '1 hour'")
+        .execute(app.pool())
+        .await
+        .unwrap();
+    sqlx::query("UPDATE jobs SET attempts = CASE WHEN id = $1 THEN 1 ELSE max_attempts END")
+        .bind(stopped.id)
+        .execute(app.pool())
+        .await
+        .unwrap();
+
+    let stale_after = Duration::from_secs(60);
+    let claimed = Job::claim("worker", stale_after, app.pool()).await.unwrap().unwrap();
+    assert_eq!((claimed.id, claimed.attempts), (stopped.id, 2));
+    assert!(Job::claim("worker", stale_after, app.pool()).await.unwrap().is_none());
+
+    let (status, attempts): (String, i32) = sqlx::query_as("SELECT status, attempts FROM jobs WHERE id = $1")
+        .bind(exhausted.id)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!((status.as_str(), attempts), ("failed", exhausted.max_attempts));
+}
+
+#[sqlx::test]
+async fn failing_jobs_back_off_and_are_listed_for_admins(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("zoe").await;
+    let admin = app.token_with_roles(user_id, &["admin"]);
+    let runner = JobRunner::new(app.pool().clone());
+    let job = Job::enqueue("newsletter.send", json!({}), Utc::now(), app.pool()).await.unwrap();
+
+    assert_eq!(jobs::backoff(1), Duration::from_secs(30));
+    assert_eq!(jobs::backoff(3), Duration::from_secs(120));
+    assert_eq!(jobs::backoff(20), Duration::from_secs(3600));
+
+    assert_eq!(runner.run_due().await, 1);
+    let pending = app.get("/admin/jobs?status=pending", Some(&admin)).await.json();
+    assert_eq!(pending["jobs"][0]["id"], job.id);
+    assert_eq!(pending["jobs"][0]["attempts"], 1);
+    assert_eq!(pending["jobs"][0]["last_error"], "unknown job kind: newsletter.send");
+    let run_at: chrono::DateTime<Utc> = serde_json::from_value(pending["jobs"][0]["run_at"].clone()).unwrap();
+    assert!(run_at > Utc::now() + TimeDelta::seconds(20));
+
+    // The last attempt fails for good
+    sqlx::query("UPDATE jobs SET attempts = max_attempts - 1, run_at = NOW() WHERE id = $1")
+        .bind(job.id)
+        .execute(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(runner.run_due().await, 1);
+    assert_eq!(runner.run_due().await, 0);
+
+    assert_eq!(app.get("/admin/jobs?status=failed", Some(&token)).await.status, StatusCode::FORBIDDEN);
+    let failed = app.get("/admin/jobs?status=failed", Some(&admin)).await.json();
+    assert_eq!(failed["jobs"].as_array().unwrap().len(), 1);
+    assert_eq!(failed["jobs"][0]["status"], "failed");
+
+    let retried = app.post_json(&format!("/admin/jobs/{}/retry", job.id), json!({}), Some(&admin)).await;
+    assert_eq!(retried.json()["status"], "pending");
+    assert_eq!(retried.json()["attempts"], 0);
+    let again = app.post_json(&format!("/admin/jobs/{}/retry", job.id), json!({}), Some(&admin)).await;
+    assert_eq!(again.status, StatusCode::NOT_FOUND);
+}
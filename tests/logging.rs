@@ -0,0 +1,135 @@
+mod common;
+
+use axum::body::Body;
+use common::{TestApp, PASSWORD};
+use http::{Method, Request, StatusCode};
+use rustrest::config::{LogConfig, LogFormat, LogRotation, RedactionConfig};
+use rustrest::redact::Redactor;
+use rustrest::telemetry::{self, Telemetry};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const PSEUDONYM_KEY: &str = "logging-test-key";
+
+// The subscriber is global, every test in this binary shares it
+fn log_file() -> &'static PathBuf {
+    static TELEMETRY: OnceLock<(PathBuf, Telemetry)> = OnceLock::new();
+    let (path, _) = TELEMETRY.get_or_init(|| {
+        let path = std::env::temp_dir()
+            .join(format!("rustrest-logging-{}", std::process::id()))
+            .join("server.log");
+        let config = LogConfig {
+            format: LogFormat::Json,
+            filter: "info,sqlx=warn".to_string(),
+            file: Some(path.clone()),
+            rotation: LogRotation::Never,
+            redaction: RedactionConfig {
+                pseudonymize_key: Some(PSEUDONYM_KEY.to_string()),
+                ..RedactionConfig::default()
+            },
+        };
+        (path.clone(), telemetry::init(&config, None).unwrap())
+    });
+    path
+}
+
+// The file writer runs on its own thread
+async fn wait_for_line(path: &PathBuf, matches: impl Fn(&Value) -> bool) -> Option<Value> {
+    for _ in 0..50 {
+        let contents = std::fs::read_to_string(path).unwrap_or_default();
+        let line = contents
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).expect("every line is JSON"))
+            .find(&matches);
+        if line.is_some() {
+            return line;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    None
+}
+
+#[sqlx::test]
+async fn logs_are_written_as_json_lines(pool: PgPool) {
+    let path = log_file();
+    let app = TestApp::new(pool);
+    let request = Request::post("/register")
+        .header("content-type", "application/json")
+        .header("x-request-id", "logging-test-1")
+        .body(Body::from(
+            json!({ "username": "rita", "email": "rita@example.com", "password": PASSWORD }).to_string(),
+        ))
+        .unwrap();
+    app.send(request).await;
+
+    let line = wait_for_line(path, |line| line["target"] == "AUDIT" && line["fields"]["request_id"] == "logging-test-1")
+        .await
+        .expect("audit entry for /register");
+    assert_eq!(line["level"], "INFO");
+    assert!(line["timestamp"].is_string());
+    assert_eq!(line["span"]["name"], "request");
+    assert_eq!(line["span"]["request_id"], "logging-test-1");
+    assert_eq!(line["fields"]["action"], "user.registered");
+}
+
+#[sqlx::test]
+async fn admins_change_the_log_filter_at_runtime(pool: PgPool) {
+    log_file();
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("sam").await;
+    let admin = app.token_with_roles(user_id, &["admin"]);
+
+    let response = app.get("/admin/log-level", Some(&token)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    let response = app.get("/admin/log-level", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .request(Method::PUT, "/admin/log-level", Some(json!({ "filter": "info,sqlx=error,AUDIT=info" })), Some(&admin))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let filter = response.json()["filter"].as_str().unwrap().to_string();
+    assert!(filter.contains("sqlx=error"));
+    assert_eq!(app.get("/admin/log-level", Some(&admin)).await.json()["filter"], filter);
+
+    let response = app
+        .request(Method::PUT, "/admin/log-level", Some(json!({ "filter": "sqlx=loud" })), Some(&admin))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(app.get("/admin/log-level", Some(&admin)).await.json()["filter"], filter);
+}
+
+#[sqlx::test]
+async fn logs_are_redacted(pool: PgPool) {
+    let path = log_file();
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("tess").await;
+
+    app.get("/posts?access_token=leaked-secret&page=2", None).await;
+    let request = Request::get("/admin/log-level?email=tess@example.com")
+        .header("x-request-id", "redaction-test-1")
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    app.send(request).await;
+
+    let line = wait_for_line(path, |line| line["fields"]["request_id"] == "redaction-test-1" && line["fields"]["message"] == "Request failed")
+        .await
+        .expect("failed request is logged");
+    let pseudonym = Redactor::new(&RedactionConfig {
+        pseudonymize_key: Some(PSEUDONYM_KEY.to_string()),
+        ..RedactionConfig::default()
+    })
+    .user(&user_id.to_string());
+    assert_eq!(line["fields"]["user_id"], pseudonym);
+    assert_eq!(line["fields"]["uri"], "/admin/log-level?email=[REDACTED]");
+
+    let contents = std::fs::read_to_string(path).unwrap();
+    assert!(contents.contains("access_token=[REDACTED]&page=2"));
+    assert!(!contents.contains("leaked-secret"));
+    assert!(!contents.contains("tess@example.com"));
+    assert!(!contents.contains(&token));
+}
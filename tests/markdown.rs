@@ -0,0 +1,106 @@
+mod common;
+
+use common::{TestApp, TestResponse};
+use http::{Method, StatusCode};
+use rustrest::markdown::{render, RENDERER_VERSION};
+use rustrest::models::post::rerender_outdated;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+// PUT /posts/{id} based on the post's current ETag
+async fn update(app: &TestApp, id: &Value, body: Value, token: &str) -> TestResponse {
+    let uri = format!("/posts/{}", id);
+    let current = app.get(&uri, Some(token)).await;
+    let etag = current.header("etag").unwrap();
+    app.request_with_headers(Method::PUT, &uri, Some(body), Some(token), &[("if-match", etag)]).await
+}
+
+#[test]
+fn bodies_render_as_github_flavoured_markdown() {
+    let rendered = render("# Title\n\nSome *emphasis* and ~~gone~~.\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] done\n");
+    assert!(rendered.html.contains("<h1>Title</h1>"), "{}", rendered.html);
+    assert!(rendered.html.contains("<em>emphasis</em>"));
+    assert!(rendered.html.contains("<del>gone</del>"));
+    assert!(rendered.html.contains("<td>1</td>"));
+    assert!(rendered.html.contains("<input"), "{}", rendered.html);
+    assert!(rendered.html.contains("type=\"checkbox\""));
+    assert_eq!(rendered.excerpt, "Title Some emphasis and gone. a b 1 2 done");
+    assert_eq!(rendered.reading_minutes, 1);
+}
+
+#[test]
+fn rendered_html_is_sanitised() {
+    let rendered = render(
+        "<script>alert(1)</script>\n\n<img src=\"x.png\" onerror=\"alert(2)\">\n\n\
+         [click](javascript:alert(3)) <a href=\"https://example.com\" onclick=\"alert(4)\">site</a> \
+         <input type=\"text\" value=\"x\"> <iframe src=\"https://example.com\"></iframe>",
+    );
+    for forbidden in ["<script", "alert", "onerror", "onclick", "javascript:", "<iframe", "type=\"text\""] {
+        assert!(!rendered.html.contains(forbidden), "{} in {}", forbidden, rendered.html);
+    }
+    assert!(rendered.html.contains("<img src=\"x.png\">"), "{}", rendered.html);
+    assert!(rendered.html.contains("href=\"https://example.com\""));
+}
+
+#[test]
+fn excerpts_are_cut_at_a_word_and_long_bodies_take_longer() {
+    let body = "word ".repeat(450);
+    let rendered = render(&body);
+    assert!(rendered.excerpt.ends_with("word…"), "{}", rendered.excerpt);
+    assert!(rendered.excerpt.chars().count() <= 201);
+    assert_eq!(rendered.reading_minutes, 3);
+    assert_eq!(render("").reading_minutes, 1);
+}
+
+#[sqlx::test]
+async fn posts_carry_their_rendered_body(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("dora").await;
+
+    let created = app.post_json("/posts", json!({ "title": "Markdown", "body": "Hello **world**" }), Some(&token)).await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.text());
+    let created = created.json();
+    assert_eq!(created["body"], "Hello **world**");
+    assert_eq!(created["body_html"], "<p>Hello <strong>world</strong></p>\n");
+    assert_eq!(created["excerpt"], "Hello world");
+    assert_eq!(created["reading_minutes"], 1);
+    let id = &created["id"];
+
+    // Only a changed body is rendered again
+    sqlx::query("UPDATE posts SET body_html = 'cached' WHERE id = $1")
+        .bind(id.as_i64().unwrap() as i32)
+        .execute(app.pool())
+        .await
+        .unwrap();
+    let retitled = update(&app, id, json!({ "title": "Renamed", "body": "Hello **world**" }), &token).await;
+    assert_eq!(retitled.json()["body_html"], "cached");
+    let edited = update(&app, id, json!({ "title": "Renamed", "body": "Hello <em onclick=\"x()\">you</em>" }), &token).await;
+    assert_eq!(edited.json()["body_html"], "<p>Hello <em>you</em></p>\n");
+    // A new body that renders to the same HTML keeps its rendering
+    let same = update(&app, id, json!({ "title": "Renamed", "body": "Hello *you*" }), &token).await;
+    assert_eq!(same.json()["body_html"], "<p>Hello <em>you</em></p>\n");
+    assert_eq!(same.json()["excerpt"], "Hello you");
+
+    // Bodies written some other way wait for the next rerender, reads don't write
+    let raw = app.insert_post(user_id, "Raw", "# Heading").await;
+    let post = app.get(&format!("/posts/{}", raw), Some(&token)).await.json();
+    assert_eq!(post["body_html"], Value::Null);
+    assert_eq!(rerender_outdated(app.pool()).await.unwrap(), 1);
+
+    let posts = app.get("/posts", Some(&token)).await.json();
+    let post = |id: &Value| posts.as_array().unwrap().iter().find(|post| post["id"] == *id).unwrap().clone();
+    assert_eq!(post(&json!(raw))["body_html"], "<h1>Heading</h1>\n");
+    assert_eq!(post(id)["body_html"], "<p>Hello <em>you</em></p>\n");
+    assert_eq!(rerender_outdated(app.pool()).await.unwrap(), 0);
+
+    // As do posts of an older renderer
+    sqlx::query("UPDATE posts SET body_html = 'old', render_version = $2 WHERE id = $1")
+        .bind(raw)
+        .bind(RENDERER_VERSION - 1)
+        .execute(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(rerender_outdated(app.pool()).await.unwrap(), 1);
+    let post = app.get(&format!("/posts/{}", raw), Some(&token)).await.json();
+    assert_eq!(post["body_html"], "<h1>Heading</h1>\n");
+}
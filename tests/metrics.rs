@@ -0,0 +1,54 @@
+mod common;
+
+use common::{TestApp, PASSWORD};
+use http::StatusCode;
+use rustrest::Config;
+use sqlx::PgPool;
+
+// Value of the first sample whose line starts with `series`
+fn sample(metrics: &str, series: &str) -> Option<f64> {
+    metrics
+        .lines()
+        .find(|line| line.starts_with(series))
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|value| value.parse().ok())
+}
+
+#[sqlx::test]
+async fn metrics_are_served_in_prometheus_format(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("paul").await;
+    let id = app.insert_post(user_id, "Title", "Body").await;
+    app.get(&format!("/posts/{}", id), Some(&token)).await;
+    app.login("paul", "wrong-password").await;
+    app.get("/posts", Some("garbage")).await;
+
+    let response = app.get("/metrics", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.header("content-type").unwrap().starts_with("text/plain"));
+    let metrics = response.text();
+    assert!(sample(&metrics, r#"http_requests_total{method="GET",route="/posts/{id}",status="2xx"}"#).unwrap() >= 1.0);
+    assert!(metrics.contains(r#"http_request_duration_seconds_bucket{method="GET",route="/posts/{id}",le="0.005"}"#));
+    assert!(metrics.contains("http_requests_in_flight"));
+    assert!(sample(&metrics, r#"auth_logins_total{outcome="success"}"#).unwrap() >= 1.0);
+    assert!(sample(&metrics, r#"auth_logins_total{outcome="failure"}"#).unwrap() >= 1.0);
+    assert!(sample(&metrics, r#"auth_token_verification_failures_total{reason="invalid"}"#).unwrap() >= 1.0);
+    assert!(sample(&metrics, "db_pool_max_connections").is_some());
+    assert!(sample(&metrics, "db_pool_idle_connections").is_some());
+}
+
+#[sqlx::test]
+async fn metrics_token_protects_the_endpoint(pool: PgPool) {
+    let config = Config {
+        metrics_token: Some("scrape-secret".to_string()),
+        ..Config::default()
+    };
+    let app = TestApp::with_config(pool, config);
+    app.register("quinn").await;
+    app.login("quinn", PASSWORD).await;
+
+    assert_eq!(app.get("/metrics", None).await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.get("/metrics", Some("wrong")).await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.get("/metrics", Some("scrape-secret")).await.status, StatusCode::OK);
+}
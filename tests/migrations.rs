@@ -0,0 +1,74 @@
+use rustrest::db::{self, MIGRATOR};
+use sqlx::PgPool;
+
+// Whether a table of that name exists
+async fn has_table(pool: &PgPool, table: &str) -> bool {
+    sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+        .bind(table)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(migrations = false)]
+async fn migrations_apply_and_revert_cleanly(pool: PgPool) {
+    let versions: Vec<i64> = MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| m.version)
+        .collect();
+    assert_eq!(db::migration_state(&pool).await.unwrap().pending, versions);
+
+    db::run_migrations(&pool).await.unwrap();
+    let state = db::ensure_compatible(&pool).await.unwrap();
+    assert!(state.is_current(), "{:?}", state);
+    assert_eq!(state.applied, versions);
+    assert!(has_table(&pool, "posts").await);
+
+    // Without a target only the latest migration goes
+    let latest = *versions.last().unwrap();
+    assert_eq!(db::revert_migrations(&pool, None).await.unwrap(), [latest]);
+    assert_eq!(db::migration_state(&pool).await.unwrap().pending, [latest]);
+
+    // Every down migration runs, and leaves nothing behind for the next up
+    let reverted = db::revert_migrations(&pool, Some(0)).await.unwrap();
+    assert_eq!(reverted, versions[..versions.len() - 1]);
+    assert!(!has_table(&pool, "posts").await);
+    assert!(!has_table(&pool, "users").await);
+    assert_eq!(db::migration_state(&pool).await.unwrap().applied, Vec::<i64>::new());
+
+    db::run_migrations(&pool).await.unwrap();
+    assert!(db::migration_state(&pool).await.unwrap().is_current());
+}
+
+#[sqlx::test]
+async fn newer_schemas_are_refused(pool: PgPool) {
+    assert!(db::ensure_compatible(&pool).await.is_ok());
+
+    // As left behind by a later release
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) \
+         VALUES (99991231000000, 'from the future', TRUE, '\\x00', 0)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let state = db::migration_state(&pool).await.unwrap();
+    assert_eq!(state.unknown, [99991231000000]);
+    let error = db::ensure_compatible(&pool).await.unwrap_err();
+    assert!(error.to_string().contains("ahead of this binary"), "{}", error);
+    let error = db::revert_migrations(&pool, None).await.unwrap_err();
+    assert!(error.to_string().contains("not known to this binary"), "{}", error);
+}
+
+#[sqlx::test]
+async fn modified_migrations_are_refused(pool: PgPool) {
+    sqlx::query("UPDATE _sqlx_migrations SET checksum = '\\x00' WHERE version = (SELECT min(version) FROM _sqlx_migrations)")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let error = db::ensure_compatible(&pool).await.unwrap_err();
+    assert!(error.to_string().contains("differ from the ones embedded"), "{}", error);
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use common::{TestApp, TestResponse};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+// Creates a draft as the token's user and returns its id
+async fn draft(app: &TestApp, token: &str, title: &str) -> i64 {
+    let response = app.post_json("/posts", json!({ "title": title, "body": "Body" }), Some(token)).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+    response.json()["id"].as_i64().unwrap()
+}
+
+// POST /posts/{id}/{action}
+async fn action(app: &TestApp, id: i64, action: &str, body: Value, token: &str) -> TestResponse {
+    app.post_json(&format!("/posts/{}/{}", id, action), body, Some(token)).await
+}
+
+// PUT /posts/{id} based on the post's current ETag
+async fn update(app: &TestApp, id: i64, body: Value, token: &str) -> TestResponse {
+    let uri = format!("/posts/{}", id);
+    let current = app.get(&uri, Some(token)).await;
+    let etag = current.header("etag").unwrap();
+    app.request_with_headers(Method::PUT, &uri, Some(body), Some(token), &[("if-match", etag)]).await
+}
+
+// PUT /posts/{id} with a new title and the given `If-Match`
+async fn put(app: &TestApp, id: i64, title: &str, if_match: Option<&str>, token: &str) -> TestResponse {
+    let headers: Vec<_> = if_match.map(|etag| ("if-match", etag)).into_iter().collect();
+    let body = json!({ "title": title, "body": "Body" });
+    app.request_with_headers(Method::PUT, &format!("/posts/{}", id), Some(body), Some(token), &headers).await
+}
+
+fn titles(posts: &Value) -> Vec<&str> {
+    posts.as_array().unwrap().iter().map(|post| post["title"].as_str().unwrap()).collect()
+}
+
+#[sqlx::test]
+async fn drafts_are_only_visible_to_their_author(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, author) = app.user_with_token("olive").await;
+    let (reader_id, reader) = app.user_with_token("peter").await;
+    let editor = app.token_with_roles(reader_id, &["user", "editor"]);
+
+    let id = draft(&app, &author, "Work in progress").await;
+    let post = app.get(&format!("/posts/{}", id), Some(&author)).await.json();
+    assert_eq!(post["status"], "draft");
+    assert_eq!(post["published_at"], Value::Null);
+
+    assert_eq!(titles(&app.get("/posts", Some(&author)).await.json()), ["Work in progress"]);
+    assert!(titles(&app.get("/posts", Some(&reader)).await.json()).is_empty());
+    assert!(titles(&app.get("/posts", Some(&editor)).await.json()).is_empty());
+    assert_eq!(app.get(&format!("/posts/{}", id), Some(&reader)).await.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn post_lists_are_paged(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, author) = app.user_with_token("quinn").await;
+    for title in ["One", "Two", "Three"] {
+        draft(&app, &author, title).await;
+    }
+
+    assert_eq!(titles(&app.get("/posts?per_page=2", Some(&author)).await.json()), ["One", "Two"]);
+    assert_eq!(titles(&app.get("/posts?page=2&per_page=2", Some(&author)).await.json()), ["Three"]);
+    assert_eq!(titles(&app.get("/posts?per_page=0", Some(&author)).await.json()), ["One"]);
+    let deep = app.get(&format!("/posts?page={}", i64::MAX), Some(&author)).await;
+    assert_eq!(deep.status, StatusCode::OK);
+    assert!(titles(&deep.json()).is_empty());
+}
+
+#[sqlx::test]
+async fn editors_publish_submitted_posts(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, author) = app.user_with_token("quinn").await;
+    let (editor_id, reader) = app.user_with_token("rosa").await;
+    let editor = app.token_with_roles(editor_id, &["user", "editor"]);
+    let id = draft(&app, &author, "Ready").await;
+
+    let submitted = action(&app, id, "submit", json!({}), &author).await;
+    assert_eq!(submitted.json()["status"], "in_review");
+
+    // The review queue
+    let queue = app.get("/posts?status=in_review", Some(&editor)).await.json();
+    assert_eq!(titles(&queue), ["Ready"]);
+    assert!(titles(&app.get("/posts", Some(&reader)).await.json()).is_empty());
+
+    assert_eq!(action(&app, id, "approve", json!({}), &reader).await.status, StatusCode::FORBIDDEN);
+    let approved = action(&app, id, "approve", json!({}), &editor).await;
+    assert_eq!(approved.status, StatusCode::OK);
+    assert_eq!(approved.json()["status"], "published");
+    assert!(approved.json()["published_at"].is_string());
+
+    assert_eq!(titles(&app.get("/posts", Some(&reader)).await.json()), ["Ready"]);
+    let reviews = app.get(&format!("/posts/{}/reviews", id), Some(&author)).await.json();
+    assert_eq!(reviews[0]["decision"], "approved");
+    assert_eq!(reviews[0]["reviewer_id"], editor_id);
+}
+
+#[sqlx::test]
+async fn rejected_posts_go_back_to_their_author(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, author) = app.user_with_token("sam").await;
+    let (editor_id, _) = app.user_with_token("tara").await;
+    let editor = app.token_with_roles(editor_id, &["user", "editor"]);
+    let id = draft(&app, &author, "Rough").await;
+    action(&app, id, "submit", json!({}), &author).await;
+
+    // Submitted posts are frozen until the editors decide
+    let edit = json!({ "title": "Polished", "body": "Better body" });
+    let response = update(&app, id, edit.clone(), &author).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    assert_eq!(action(&app, id, "reject", json!({}), &editor).await.status, StatusCode::BAD_REQUEST);
+    let rejected = action(&app, id, "reject", json!({ "comment": "Needs a conclusion" }), &editor).await;
+    assert_eq!(rejected.json()["status"], "draft");
+
+    let reviews = app.get(&format!("/posts/{}/reviews", id), Some(&author)).await.json();
+    assert_eq!(reviews[0]["decision"], "rejected");
+    assert_eq!(reviews[0]["comment"], "Needs a conclusion");
+
+    let response = update(&app, id, edit, &author).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["title"], "Polished");
+}
+
+#[sqlx::test]
+async fn transitions_follow_the_workflow(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, author) = app.user_with_token("uma").await;
+    let (editor_id, other) = app.user_with_token("victor").await;
+    let editor = app.token_with_roles(editor_id, &["user", "editor"]);
+    let id = draft(&app, &author, "Steps").await;
+    let step = |name, token| action(&app, id, name, json!({}), token);
+
+    // Drafts can't skip the review, and only the author submits
+    assert_eq!(step("approve", &editor).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(step("archive", &author).await.status, StatusCode::CONFLICT);
+    assert_eq!(step("submit", &other).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(step("submit", &author).await.status, StatusCode::OK);
+    assert_eq!(step("submit", &author).await.status, StatusCode::CONFLICT);
+    assert_eq!(step("approve", &editor).await.status, StatusCode::OK);
+    assert_eq!(step("approve", &editor).await.status, StatusCode::CONFLICT);
+
+    // Archived posts disappear for readers
+    assert_eq!(step("archive", &other).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(step("archive", &author).await.status, StatusCode::OK);
+    assert_eq!(app.get(&format!("/posts/{}", id), Some(&other)).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(app.get(&format!("/posts/{}", id), Some(&author)).await.json()["status"], "archived");
+
+    assert_eq!(app.get("/posts?status=bogus", Some(&author)).await.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn concurrent_edits_need_the_current_etag(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, author) = app.user_with_token("wanda").await;
+    let id = draft(&app, &author, "Shared").await;
+    let uri = format!("/posts/{}", id);
+
+    let fetched = app.get(&uri, Some(&author)).await;
+    let etag = fetched.header("etag").unwrap().to_string();
+    assert!(etag.starts_with('"'), "{}", etag);
+
+    assert_eq!(put(&app, id, "No header", None, &author).await.status, StatusCode::PRECONDITION_REQUIRED);
+    let first = put(&app, id, "First save", Some(&etag), &author).await;
+    assert_eq!(first.status, StatusCode::OK, "{}", first.text());
+    let newer = first.header("etag").unwrap().to_string();
+    assert_ne!(newer, etag);
+
+    // The second writer still has the old ETag
+    let second = put(&app, id, "Second save", Some(&etag), &author).await;
+    assert_eq!(second.status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(app.get(&uri, Some(&author)).await.json()["title"], "First save");
+    assert_eq!(put(&app, id, "Forced", Some("*"), &author).await.status, StatusCode::OK);
+
+    // A client with the current version gets an empty 304
+    let current = app.get(&uri, Some(&author)).await.header("etag").unwrap().to_string();
+    let cached = app.request_with_headers(Method::GET, &uri, None, Some(&author), &[("if-none-match", &current)]).await;
+    assert_eq!(cached.status, StatusCode::NOT_MODIFIED);
+    assert!(cached.body.is_empty());
+    assert_eq!(cached.header("etag"), Some(current.as_str()));
+    let stale = app.request_with_headers(Method::GET, &uri, None, Some(&author), &[("if-none-match", &newer)]).await;
+    assert_eq!(stale.status, StatusCode::OK);
+    assert_eq!(stale.json()["title"], "Forced");
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::{TestApp, TestResponse};
+use http::{Method, StatusCode};
+use rustrest::config::Config;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+// 👍, percent-encoded for the path
+const THUMBS_UP: &str = "%F0%9F%91%8D";
+
+// Inserts a published post by `user_id`
+async fn published(app: &TestApp, user_id: i32, title: &str) -> i32 {
+    let id = app.insert_post(user_id, title, "Body").await;
+    sqlx::query("UPDATE posts SET status = 'published', published_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(app.pool())
+        .await
+        .unwrap();
+    id
+}
+
+async fn react(app: &TestApp, method: Method, post_id: i32, reaction: &str, token: &str) -> TestResponse {
+    app.request(method, &format!("/posts/{}/reactions/{}", post_id, reaction), None, Some(token)).await
+}
+
+async fn bookmark(app: &TestApp, method: Method, post_id: i32, token: &str) -> TestResponse {
+    app.request(method, &format!("/posts/{}/bookmark", post_id), None, Some(token)).await
+}
+
+async fn stored_count(app: &TestApp, post_id: i32) -> Option<i32> {
+    sqlx::query_scalar("SELECT count FROM post_reaction_counts WHERE post_id = $1")
+        .bind(post_id)
+        .fetch_optional(app.pool())
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn reactions_are_counted_once_per_user(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, author) = app.user_with_token("xena").await;
+    let (_, reader) = app.user_with_token("yusuf").await;
+    let post_id = published(&app, user_id, "Reactions").await;
+    let draft = app.insert_post(user_id, "Draft", "Body").await;
+
+    let reacted = react(&app, Method::PUT, post_id, THUMBS_UP, &reader).await;
+    assert_eq!(reacted.status, StatusCode::OK, "{}", reacted.text());
+    assert_eq!(reacted.json(), json!({ "reactions": { "👍": 1 }, "my_reactions": ["👍"], "bookmarked": false }));
+    // PUT is idempotent
+    assert_eq!(react(&app, Method::PUT, post_id, THUMBS_UP, &reader).await.json()["reactions"]["👍"], 1);
+    let summary = react(&app, Method::PUT, post_id, THUMBS_UP, &author).await.json();
+    assert_eq!(summary["reactions"]["👍"], 2);
+    assert_eq!(stored_count(&app, post_id).await, Some(2));
+
+    assert_eq!(react(&app, Method::PUT, post_id, "meh", &reader).await.status, StatusCode::BAD_REQUEST);
+    assert_eq!(react(&app, Method::PUT, draft, THUMBS_UP, &author).await.status, StatusCode::CONFLICT);
+    assert_eq!(react(&app, Method::PUT, 999, THUMBS_UP, &reader).await.status, StatusCode::NOT_FOUND);
+
+    // Posts carry the counts, flags are per caller
+    let post = app.get(&format!("/posts/{}", post_id), Some(&reader)).await.json();
+    assert_eq!(post["title"], "Reactions");
+    assert_eq!(post["reactions"], json!({ "👍": 2 }));
+    assert_eq!(post["my_reactions"], json!(["👍"]));
+    let (_, stranger) = app.user_with_token("zoe").await;
+    let posts = app.get("/posts", Some(&stranger)).await.json();
+    assert_eq!(posts[0]["reactions"], json!({ "👍": 2 }));
+    assert_eq!(posts[0]["my_reactions"], json!([]));
+
+    // DELETE is idempotent too, and the counter goes away with the last reaction
+    let removed = react(&app, Method::DELETE, post_id, THUMBS_UP, &reader).await.json();
+    assert_eq!(removed["reactions"]["👍"], 1);
+    assert_eq!(removed["my_reactions"], json!([]));
+    assert_eq!(react(&app, Method::DELETE, post_id, THUMBS_UP, &reader).await.json()["reactions"]["👍"], 1);
+    react(&app, Method::DELETE, post_id, THUMBS_UP, &author).await;
+    assert_eq!(stored_count(&app, post_id).await, None);
+}
+
+#[sqlx::test]
+async fn the_reaction_set_is_configurable(pool: PgPool) {
+    let config = Config { reactions: vec!["+1".to_string(), "heart".to_string()], ..Config::default() };
+    let app = TestApp::with_config(pool, config);
+    let (user_id, token) = app.user_with_token("anke").await;
+    let post_id = published(&app, user_id, "Custom").await;
+
+    assert_eq!(react(&app, Method::PUT, post_id, THUMBS_UP, &token).await.status, StatusCode::BAD_REQUEST);
+    react(&app, Method::PUT, post_id, "heart", &token).await;
+    let summary = react(&app, Method::PUT, post_id, "+1", &token).await.json();
+    assert_eq!(summary["reactions"], json!({ "+1": 1, "heart": 1 }));
+    assert_eq!(summary["my_reactions"], json!(["heart", "+1"]));
+
+    // Deleting the user takes their reactions out of the counts
+    sqlx::query("DELETE FROM users WHERE id = $1").bind(user_id).execute(app.pool()).await.unwrap();
+    assert_eq!(stored_count(&app, post_id).await, None);
+}
+
+#[sqlx::test]
+async fn users_bookmark_posts(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, author) = app.user_with_token("bert").await;
+    let (_, reader) = app.user_with_token("cleo").await;
+    let first = published(&app, user_id, "First").await;
+    let second = published(&app, user_id, "Second").await;
+
+    for post_id in [first, second, first] {
+        assert_eq!(bookmark(&app, Method::PUT, post_id, &reader).await.status, StatusCode::NO_CONTENT);
+    }
+    let titles = |bookmarks: Value| -> Vec<String> {
+        bookmarks.as_array().unwrap().iter().map(|post| post["title"].as_str().unwrap().to_string()).collect()
+    };
+    let bookmarks = app.get("/me/bookmarks", Some(&reader)).await.json();
+    assert_eq!(titles(bookmarks.clone()), ["Second", "First"]);
+    assert_eq!(bookmarks[0]["bookmarked"], true);
+    assert!(app.get("/me/bookmarks", Some(&author)).await.json().as_array().unwrap().is_empty());
+    assert_eq!(app.get(&format!("/posts/{}", first), Some(&reader)).await.json()["bookmarked"], true);
+    assert_eq!(app.get(&format!("/posts/{}", first), Some(&author)).await.json()["bookmarked"], false);
+
+    assert_eq!(bookmark(&app, Method::DELETE, second, &reader).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(bookmark(&app, Method::DELETE, second, &reader).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(titles(app.get("/me/bookmarks", Some(&reader)).await.json()), ["First"]);
+
+    // Posts that are no longer visible drop out of the list
+    sqlx::query("UPDATE posts SET status = 'archived' WHERE id = $1").bind(first).execute(app.pool()).await.unwrap();
+    assert!(app.get("/me/bookmarks", Some(&reader)).await.json().as_array().unwrap().is_empty());
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use axum::body::Body;
+use common::TestApp;
+use http::{Request, StatusCode};
+use sqlx::PgPool;
+
+fn get_with_request_id(uri: &str, request_id: &str) -> Request<Body> {
+    Request::get(uri)
+        .header("x-request-id", request_id)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn request_id_is_generated_when_missing(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let first = app.get("/healthz", None).await;
+    let second = app.get("/healthz", None).await;
+
+    let first = first.header("x-request-id").expect("request id header");
+    assert!(uuid::Uuid::parse_str(first).is_ok());
+    assert_ne!(Some(first), second.header("x-request-id"));
+}
+
+#[sqlx::test]
+async fn incoming_request_id_is_echoed(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.send(get_with_request_id("/healthz", "support-ticket-42")).await;
+
+    assert_eq!(response.header("x-request-id"), Some("support-ticket-42"));
+}
+
+#[sqlx::test]
+async fn unsafe_request_id_is_replaced(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let response = app.send(get_with_request_id("/healthz", "a b\"c")).await;
+    let too_long = app.send(get_with_request_id("/healthz", &"x".repeat(200))).await;
+
+    assert!(uuid::Uuid::parse_str(response.header("x-request-id").unwrap()).is_ok());
+    assert!(uuid::Uuid::parse_str(too_long.header("x-request-id").unwrap()).is_ok());
+}
+
+#[sqlx::test]
+async fn error_bodies_carry_the_request_id(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, token) = app.user_with_token("tara").await;
+
+    let request = Request::get("/posts/999999")
+        .header("x-request-id", "lookup-1")
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.header("x-request-id"), Some("lookup-1"));
+
+    let response = app.login("tara", "wrong-password").await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.json()["request_id"], response.header("x-request-id").unwrap());
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::{TestApp, TestResponse};
+use http::{Method, StatusCode};
+use serde_json::json;
+use sqlx::PgPool;
+
+// The post's current ETag, for `If-Match`
+async fn etag(app: &TestApp, id: i64, token: &str) -> String {
+    app.get(&format!("/posts/{}", id), Some(token)).await.header("etag").unwrap().to_string()
+}
+
+// Saves a new title and body for the post, as the token's user
+async fn edit(app: &TestApp, id: i64, title: &str, body: &str, token: &str) -> StatusCode {
+    let uri = format!("/posts/{}", id);
+    let body = json!({ "title": title, "body": body });
+    let etag = etag(app, id, token).await;
+    app.request_with_headers(Method::PUT, &uri, Some(body), Some(token), &[("if-match", &etag)]).await.status
+}
+
+// POST /posts/{id}/revisions/{rev}/restore based on the post's current ETag
+async fn restore(app: &TestApp, id: i64, revision: i32, token: &str) -> TestResponse {
+    let uri = format!("/posts/{}/revisions/{}/restore", id, revision);
+    let etag = etag(app, id, token).await;
+    app.request_with_headers(Method::POST, &uri, Some(json!({})), Some(token), &[("if-match", &etag)]).await
+}
+
+#[sqlx::test]
+async fn every_edit_is_kept_as_a_revision(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (author_id, author) = app.user_with_token("amir").await;
+    let created = app.post_json("/posts", json!({ "title": "Draft", "body": "one\ntwo\n" }), Some(&author)).await;
+    let id = created.json()["id"].as_i64().unwrap();
+
+    assert_eq!(edit(&app, id, "Draft", "one\n2\nthree\n", &author).await, StatusCode::OK);
+    assert_eq!(edit(&app, id, "Final", "one\n2\nthree\n", &author).await, StatusCode::OK);
+
+    let revisions = app.get(&format!("/posts/{}/revisions", id), Some(&author)).await.json();
+    let numbers: Vec<i64> = revisions.as_array().unwrap().iter().map(|r| r["revision"].as_i64().unwrap()).collect();
+    assert_eq!(numbers, [3, 2, 1]);
+    assert_eq!(revisions[0]["title"], "Final");
+    assert_eq!(revisions[2]["author_id"], author_id);
+    assert!(revisions[2]["created_at"].is_string());
+
+    let first = app.get(&format!("/posts/{}/revisions/1", id), Some(&author)).await.json();
+    assert_eq!(first["body"], "one\ntwo\n");
+    assert_eq!(app.get(&format!("/posts/{}/revisions/9", id), Some(&author)).await.status, StatusCode::NOT_FOUND);
+
+    let diff = app.get(&format!("/posts/{}/revisions/diff?from=1&to=3", id), Some(&author)).await.json();
+    assert_eq!(diff["title_before"], "Draft");
+    assert_eq!(diff["title_after"], "Final");
+    assert_eq!(diff["additions"], 2);
+    assert_eq!(diff["deletions"], 1);
+    let unified = diff["diff"].as_str().unwrap();
+    assert!(unified.starts_with("--- revision 1\n+++ revision 3\n"), "{}", unified);
+    assert!(unified.contains("-two\n+2\n+three\n"), "{}", unified);
+}
+
+#[sqlx::test]
+async fn restoring_a_revision_adds_a_new_one(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, author) = app.user_with_token("bea").await;
+    let (other_id, other) = app.user_with_token("cyril").await;
+    let editor = app.token_with_roles(other_id, &["user", "editor"]);
+    let created = app.post_json("/posts", json!({ "title": "Original", "body": "First" }), Some(&author)).await;
+    let id = created.json()["id"].as_i64().unwrap();
+    edit(&app, id, "Rewrite", "Second", &author).await;
+
+    let uri = format!("/posts/{}/revisions/1/restore", id);
+    assert_eq!(app.post_json(&uri, json!({}), Some(&other)).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(app.post_json(&uri, json!({}), Some(&author)).await.status, StatusCode::PRECONDITION_REQUIRED);
+    let restored = restore(&app, id, 1, &author).await;
+    assert_eq!(restored.status, StatusCode::OK, "{}", restored.text());
+    assert_eq!(restored.json()["title"], "Original");
+    assert_eq!(restored.json()["body"], "First");
+
+    let revisions = app.get(&format!("/posts/{}/revisions", id), Some(&author)).await.json();
+    assert_eq!(revisions[0]["revision"], 3);
+    assert_eq!(revisions[0]["restored_from"], 1);
+
+    // Once submitted the history is frozen, and visible to editors but not readers
+    app.post_json(&format!("/posts/{}/submit", id), json!({}), Some(&author)).await;
+    assert_eq!(restore(&app, id, 1, &author).await.status, StatusCode::CONFLICT);
+    let history = format!("/posts/{}/revisions", id);
+    assert_eq!(app.get(&history, Some(&editor)).await.status, StatusCode::OK);
+    app.post_json(&format!("/posts/{}/approve", id), json!({}), Some(&editor)).await;
+    assert_eq!(app.get(&format!("/posts/{}", id), Some(&other)).await.status, StatusCode::OK);
+    assert_eq!(app.get(&history, Some(&other)).await.status, StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn approved_posts_stay_editable_but_not_unreviewed(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (author_id, author) = app.user_with_token("dora").await;
+    let (editor_id, _) = app.user_with_token("emil").await;
+    let editor = app.token_with_roles(editor_id, &["user", "editor"]);
+    let (_, reader) = app.user_with_token("fern").await;
+    let created = app.post_json("/posts", json!({ "title": "Launch", "body": "First" }), Some(&author)).await;
+    let id = created.json()["id"].as_i64().unwrap();
+    let uri = format!("/posts/{}", id);
+    app.post_json(&format!("/posts/{}/submit", id), json!({}), Some(&author)).await;
+    app.post_json(&format!("/posts/{}/approve", id), json!({}), Some(&editor)).await;
+
+    // Editors fix published posts in place
+    assert_eq!(edit(&app, id, "Launch day", "First", &editor).await, StatusCode::OK);
+    assert_eq!(edit(&app, id, "Mine now", "Gone", &reader).await, StatusCode::FORBIDDEN);
+    let post = app.get(&uri, Some(&reader)).await.json();
+    assert_eq!(post["status"], "published");
+    assert_eq!(post["title"], "Launch day");
+
+    // The author's edit waits for the editors before readers see it
+    assert_eq!(edit(&app, id, "Launch day", "Something else entirely", &author).await, StatusCode::OK);
+    assert_eq!(app.get(&uri, Some(&author)).await.json()["status"], "in_review");
+    assert_eq!(app.get(&uri, Some(&reader)).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(edit(&app, id, "Sneaky", "Again", &author).await, StatusCode::CONFLICT);
+    app.post_json(&format!("/posts/{}/approve", id), json!({}), Some(&editor)).await;
+    let post = app.get(&uri, Some(&reader)).await.json();
+    assert_eq!(post["status"], "published");
+    assert_eq!(post["body"], "Something else entirely");
+
+    let revisions = app.get(&format!("/posts/{}/revisions", id), Some(&author)).await.json();
+    assert_eq!(revisions.as_array().unwrap().len(), 3);
+    assert_eq!(revisions[0]["author_id"], author_id);
+    assert_eq!(revisions[1]["author_id"], editor_id);
+
+    app.post_json(&format!("/posts/{}/archive", id), json!({}), Some(&editor)).await;
+    assert_eq!(edit(&app, id, "Revived", "Again", &editor).await, StatusCode::CONFLICT);
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use common::TestApp;
+use http::StatusCode;
+use rustrest::models::post_search::{parse_query, Term};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+// Inserts a post by `user_id` that everyone can see
+async fn published(app: &TestApp, user_id: i32, title: &str, body: &str) -> i32 {
+    let id = app.insert_post(user_id, title, body).await;
+    sqlx::query("UPDATE posts SET status = 'published', published_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(app.pool())
+        .await
+        .unwrap();
+    id
+}
+
+fn hit_ids(results: &Value) -> Vec<i64> {
+    results["hits"].as_array().unwrap().iter().map(|hit| hit["id"].as_i64().unwrap()).collect()
+}
+
+#[test]
+fn search_text_is_split_into_terms() {
+    let terms = parse_query(r#"rust  async* "zero cost" -java"#);
+    assert_eq!(
+        terms,
+        [
+            Term::Word("rust".to_string()),
+            Term::Prefix("async".to_string()),
+            Term::Phrase("zero cost".to_string()),
+            Term::Exclude("java".to_string()),
+        ]
+    );
+    // Nothing of the tsquery syntax gets through
+    assert_eq!(parse_query(r#"a:&|!*  "  "#), [Term::Prefix("a".to_string())]);
+    assert_eq!(parse_query(r#""open ended"#), [Term::Phrase("open ended".to_string())]);
+    assert!(parse_query("  *  ").is_empty());
+}
+
+#[sqlx::test]
+async fn search_ranks_and_highlights_matches(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("ines").await;
+    let (other_id, _) = app.user_with_token("jonas").await;
+    let in_body = published(&app, user_id, "Weekly notes", "Some thoughts on databases and Postgres indexes.").await;
+    let in_title = published(&app, user_id, "Postgres indexes", "Everything about B-trees.").await;
+    published(&app, user_id, "Gardening", "Tomatoes need sun.").await;
+    app.insert_post(other_id, "Postgres draft", "Not visible to others").await;
+
+    let results = app.get("/posts/search?q=postgres", Some(&token)).await.json();
+    assert_eq!(results["total"], 2);
+    assert_eq!(hit_ids(&results), [in_title as i64, in_body as i64]);
+    assert_eq!(results["hits"][0]["title"], "<mark>Postgres</mark> indexes");
+    assert!(results["hits"][1]["snippet"].as_str().unwrap().contains("<mark>Postgres</mark>"), "{}", results);
+
+    // Stemming, phrases, prefixes and exclusions
+    assert_eq!(hit_ids(&app.get("/posts/search?q=index", Some(&token)).await.json()).len(), 2);
+    let phrase = app.get("/posts/search?q=%22postgres%20indexes%22%20-trees", Some(&token)).await.json();
+    assert_eq!(hit_ids(&phrase), [in_body as i64]);
+    let prefix = app.get("/posts/search?q=datab*", Some(&token)).await.json();
+    assert_eq!(hit_ids(&prefix), [in_body as i64]);
+
+    let second_page = app.get("/posts/search?q=postgres&per_page=1&page=2", Some(&token)).await.json();
+    assert_eq!(second_page["total"], 2);
+    assert_eq!(hit_ids(&second_page), [in_body as i64]);
+    let deep = app.get(&format!("/posts/search?q=postgres&page={}", i64::MAX), Some(&token)).await;
+    assert_eq!(deep.status, StatusCode::OK, "{}", deep.text());
+    assert_eq!(hit_ids(&deep.json()), Vec::<i64>::new());
+
+    assert_eq!(app.get("/posts/search?q=%20", Some(&token)).await.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn search_highlights_are_escaped(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("lena").await;
+    published(&app, user_id, "<b onclick=\"x()\">Bold</b> & Postgres", "<script>alert('Postgres')</script>").await;
+
+    let results = app.get("/posts/search?q=postgres", Some(&token)).await.json();
+    let hit = &results["hits"][0];
+    assert_eq!(hit["title"], "&lt;b onclick=&quot;x()&quot;&gt;Bold&lt;/b&gt; &amp; <mark>Postgres</mark>");
+    // Snippets are cut at whole tokens, an escaped character is one
+    let snippet = hit["snippet"].as_str().unwrap();
+    assert!(snippet.contains("script&gt;alert(&#39;<mark>Postgres</mark>&#39;)&lt;/script"), "{}", snippet);
+    assert!(!snippet.replace("<mark>", "").replace("</mark>", "").contains(['<', '>']), "{}", snippet);
+}
+
+#[sqlx::test]
+async fn posts_are_indexed_in_their_language(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, token) = app.user_with_token("karl").await;
+    let german = json!({ "title": "Katzen", "body": "Die Katzen im Garten", "language": "german" });
+    let created = app.post_json("/posts", german, Some(&token)).await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.text());
+    assert_eq!(created.json()["language"], "german");
+
+    // The query is stemmed like the post only in the same language
+    let results = app.get("/posts/search?q=katze%20garten&lang=german", Some(&token)).await.json();
+    assert_eq!(results["total"], 1);
+    assert_eq!(app.get("/posts/search?q=katze%20garten", Some(&token)).await.json()["total"], 0);
+
+    assert_eq!(app.get("/posts/search?q=katze&lang=klingon", Some(&token)).await.status, StatusCode::BAD_REQUEST);
+    let unknown = json!({ "title": "Qapla'", "body": "", "language": "klingon" });
+    assert_eq!(app.post_json("/posts", unknown, Some(&token)).await.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn autocomplete_suggests_similar_titles(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("lena").await;
+    let async_rust = published(&app, user_id, "Async Rust", "Body").await;
+    published(&app, user_id, "Gardening tips", "Body").await;
+
+    for typed in ["asy", "Asynk", "rust"] {
+        let suggestions = app.get(&format!("/posts/autocomplete?q={}", typed), Some(&token)).await.json();
+        assert_eq!(suggestions, json!([{ "id": async_rust, "title": "Async Rust" }]), "{}", typed);
+    }
+    assert_eq!(app.get("/posts/autocomplete?q=", Some(&token)).await.json(), json!([]));
+}
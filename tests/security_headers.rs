@@ -0,0 +1,138 @@
+mod common;
+
+use axum::body::Body;
+use axum::routing::get;
+use axum::{middleware, Router};
+use common::TestApp;
+use http::{Request, StatusCode};
+use rustrest::config::{Environment, SecurityHeadersConfig};
+use rustrest::middleware::security_headers::{security_headers, CspNonce, HeaderPolicy};
+use rustrest::Config;
+use serde_json::json;
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+fn nonce(csp: &str) -> &str {
+    let start = csp.find("'nonce-").expect("policy has a nonce") + "'nonce-".len();
+    &csp[start..start + 32]
+}
+
+fn report_request(content_type: &str, body: serde_json::Value) -> Request<Body> {
+    Request::post("/csp-report")
+        .header("content-type", content_type)
+        .header("user-agent", "test-browser")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn hsts_is_only_sent_in_production(pool: PgPool) {
+    let development = TestApp::new(pool.clone());
+    let production = TestApp::with_config(pool, Config {
+        environment: Environment::Production,
+        security_headers: SecurityHeadersConfig::for_environment(Environment::Production),
+        ..Config::default()
+    });
+
+    assert_eq!(development.get("/healthz", None).await.header("strict-transport-security"), None);
+    assert_eq!(
+        production.get("/healthz", None).await.header("strict-transport-security"),
+        Some("max-age=31536000; includeSubDomains")
+    );
+}
+
+#[sqlx::test]
+async fn every_response_gets_a_fresh_csp_nonce(pool: PgPool) {
+    let app = TestApp::new(pool);
+
+    let first = app.get("/posts", None).await;
+    let second = app.get("/no-such-route", None).await;
+
+    let first = first.header("content-security-policy").unwrap().to_string();
+    let second = second.header("content-security-policy").expect("unmatched routes get headers too").to_string();
+    assert!(first.contains("report-uri /csp-report"));
+    assert!(nonce(&first).chars().all(|c| c.is_ascii_hexdigit()));
+    assert_ne!(nonce(&first), nonce(&second));
+}
+
+#[sqlx::test]
+async fn route_groups_override_headers(pool: PgPool) {
+    let mut security_headers = SecurityHeadersConfig::for_environment(Environment::Development);
+    security_headers.groups.entry("ops".to_string()).or_default().insert("content-security-policy".to_string(), String::new());
+    let app = TestApp::with_config(pool, Config { security_headers, ..Config::default() });
+    let (user_id, token) = app.user_with_token("zoe").await;
+    let admin = app.token_with_roles(user_id, &["admin"]);
+
+    let ops = app.get("/healthz", None).await;
+    assert_eq!(ops.header("content-security-policy"), None);
+    assert_eq!(ops.header("x-content-type-options"), Some("nosniff"));
+
+    assert_eq!(app.get("/admin/log-level", Some(&admin)).await.header("cache-control"), Some("no-store"));
+    assert_eq!(app.get("/posts", Some(&token)).await.header("cache-control"), None);
+}
+
+#[tokio::test]
+async fn handlers_can_use_the_csp_nonce() {
+    let policy = HeaderPolicy::for_group(&SecurityHeadersConfig::for_environment(Environment::Development), "public");
+    let router = Router::new()
+        .route("/page", get(|nonce: CspNonce| async move { format!("<script nonce=\"{}\"></script>", nonce.as_str()) }))
+        .layer(middleware::from_fn_with_state(policy, security_headers));
+
+    let response = router.oneshot(Request::get("/page").body(Body::empty()).unwrap()).await.unwrap();
+    let csp = response.headers()["content-security-policy"].to_str().unwrap().to_string();
+    let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+
+    assert_eq!(String::from_utf8(body.to_vec()).unwrap(), format!("<script nonce=\"{}\"></script>", nonce(&csp)));
+}
+
+#[sqlx::test]
+async fn csp_reports_are_stored_for_admins(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, _) = app.user_with_token("amir").await;
+    let admin = app.token_with_roles(user_id, &["admin"]);
+
+    let legacy = app
+        .send(report_request("application/csp-report", json!({
+            "csp-report": {
+                "document-uri": "https://blog.example.com/posts/1",
+                "violated-directive": "script-src",
+                "blocked-uri": "https://evil.example.net/x.js",
+                "line-number": 12,
+            }
+        })))
+        .await;
+    assert_eq!(legacy.status, StatusCode::NO_CONTENT);
+
+    let reporting_api = app
+        .send(report_request("application/reports+json", json!([
+            { "type": "csp-violation", "url": "https://blog.example.com/", "body": {
+                "documentURL": "https://blog.example.com/", "blockedURL": "inline",
+                "effectiveDirective": "style-src-elem", "disposition": "enforce" } },
+            { "type": "deprecation", "body": {} },
+        ])))
+        .await;
+    assert_eq!(reporting_api.status, StatusCode::NO_CONTENT);
+
+    let reports = app.get("/admin/csp-reports", Some(&admin)).await.json();
+    let reports = reports["reports"].as_array().unwrap();
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0]["effective_directive"], "style-src-elem");
+    assert_eq!(reports[0]["disposition"], "enforce");
+    assert_eq!(reports[1]["effective_directive"], "script-src");
+    assert_eq!(reports[1]["blocked_uri"], "https://evil.example.net/x.js");
+    assert_eq!(reports[1]["line_number"], 12);
+    assert_eq!(reports[1]["user_agent"], "test-browser");
+
+    let rejected = app.send(report_request("text/plain", json!({}))).await;
+    assert_eq!(rejected.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn csp_reports_are_rate_limited(pool: PgPool) {
+    let app = TestApp::with_config(pool, Config { csp_reports_per_minute: 2, ..Config::default() });
+    let report = || report_request("application/csp-report", json!({ "csp-report": { "blocked-uri": "inline" } }));
+
+    assert_eq!(app.send(report()).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(app.send(report()).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(app.send(report()).await.status, StatusCode::TOO_MANY_REQUESTS);
+}
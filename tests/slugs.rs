@@ -0,0 +1,181 @@
+mod common;
+
+use common::{TestApp, TestResponse};
+use http::{Method, StatusCode};
+use rustrest::slug::slugify;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+async fn create(app: &TestApp, title: &str, token: &str) -> Value {
+    let created = app.post_json("/posts", json!({ "title": title, "body": "Body" }), Some(token)).await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.text());
+    created.json()
+}
+
+// PUT /posts/{id} with a new title, based on the post's current ETag
+async fn retitle(app: &TestApp, post: &Value, title: &str, token: &str) -> TestResponse {
+    let uri = format!("/posts/{}", post["id"]);
+    let current = app.get(&uri, Some(token)).await;
+    let etag = current.header("etag").unwrap();
+    let body = json!({ "title": title, "body": "Body" });
+    app.request_with_headers(Method::PUT, &uri, Some(body), Some(token), &[("if-match", etag)]).await
+}
+
+// PUT /posts/{id}/slug, based on the post's current ETag
+async fn set_slug(app: &TestApp, post: &Value, slug: Value, token: &str) -> TestResponse {
+    let uri = format!("/posts/{}/slug", post["id"]);
+    app.put_if_match(&post["id"], &uri, json!({ "slug": slug }), token).await
+}
+
+async fn by_slug(app: &TestApp, slug: &str, token: &str) -> TestResponse {
+    app.get(&format!("/posts/by-slug/{}", slug), Some(token)).await
+}
+
+#[test]
+fn slugs_are_transliterated_ascii() {
+    assert_eq!(slugify("Hello, World!"), "hello-world");
+    assert_eq!(slugify("Crème brûlée à la carte"), "creme-brulee-a-la-carte");
+    assert_eq!(slugify("Привет мир"), "privet-mir");
+    assert_eq!(slugify("Straße"), "strasse");
+    assert_eq!(slugify("?!"), "");
+    let long = slugify(&"word ".repeat(30));
+    assert!(long.len() <= 80 && long.ends_with("word"), "{}", long);
+}
+
+#[sqlx::test]
+async fn posts_get_unique_slugs_from_their_titles(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, token) = app.user_with_token("emil").await;
+    let (_, other) = app.user_with_token("fay").await;
+
+    let first = create(&app, "Hello World", &token).await;
+    assert_eq!(first["slug"], "hello-world");
+    assert_eq!(first["custom_slug"], false);
+    assert_eq!(create(&app, "Hello, world!", &token).await["slug"], "hello-world-2");
+    assert_eq!(create(&app, "Hello world", &token).await["slug"], "hello-world-3");
+    assert_eq!(create(&app, "???", &token).await["slug"], "post");
+
+    let found = by_slug(&app, "hello-world", &token).await;
+    assert_eq!(found.status, StatusCode::OK);
+    assert_eq!(found.json()["id"], first["id"]);
+    assert!(found.header("etag").is_some());
+    // Drafts stay private under their slug too
+    assert_eq!(by_slug(&app, "hello-world", &other).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(by_slug(&app, "nothing-here", &token).await.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn old_slugs_redirect_after_a_title_change(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, token) = app.user_with_token("gus").await;
+    let post = create(&app, "First Title", &token).await;
+
+    let renamed = retitle(&app, &post, "Second Title", &token).await;
+    assert_eq!(renamed.status, StatusCode::OK, "{}", renamed.text());
+    assert_eq!(renamed.json()["slug"], "second-title");
+    let moved = by_slug(&app, "first-title", &token).await;
+    assert_eq!(moved.status, StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(moved.header("location"), Some("/posts/by-slug/second-title"));
+    assert_eq!(by_slug(&app, "second-title", &token).await.json()["id"], post["id"]);
+
+    // The old slug stays reserved for the post, which may take it back
+    assert_eq!(create(&app, "First title", &token).await["slug"], "first-title-2");
+    let back = retitle(&app, &post, "First Title", &token).await;
+    assert_eq!(back.json()["slug"], "first-title");
+    assert_eq!(by_slug(&app, "first-title", &token).await.status, StatusCode::OK);
+    let moved = by_slug(&app, "second-title", &token).await;
+    assert_eq!(moved.header("location"), Some("/posts/by-slug/first-title"));
+}
+
+#[sqlx::test]
+async fn authors_choose_custom_slugs(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, token) = app.user_with_token("hana").await;
+    let (_, other) = app.user_with_token("ivo").await;
+    let post = create(&app, "Generated", &token).await;
+    create(&app, "Taken", &token).await;
+
+    assert_eq!(set_slug(&app, &post, json!("mine"), &other).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(set_slug(&app, &post, json!("taken"), &token).await.status, StatusCode::CONFLICT);
+    assert_eq!(set_slug(&app, &post, json!("!!"), &token).await.status, StatusCode::BAD_REQUEST);
+    let custom = set_slug(&app, &post, json!("My Custom Slug"), &token).await;
+    assert_eq!(custom.status, StatusCode::OK, "{}", custom.text());
+    let custom = custom.json();
+    assert_eq!(custom["slug"], "my-custom-slug");
+    assert_eq!(custom["custom_slug"], true);
+    assert_eq!(by_slug(&app, "generated", &token).await.status, StatusCode::MOVED_PERMANENTLY);
+
+    // A chosen slug survives title changes
+    assert_eq!(retitle(&app, &post, "Another Title", &token).await.json()["slug"], "my-custom-slug");
+    let generated = set_slug(&app, &post, Value::Null, &token).await.json();
+    assert_eq!(generated["slug"], "another-title");
+    assert_eq!(generated["custom_slug"], false);
+    let moved = by_slug(&app, "my-custom-slug", &token).await;
+    assert_eq!(moved.header("location"), Some("/posts/by-slug/another-title"));
+
+    // In review the slug is fixed like the rest of the post
+    app.post_json(&format!("/posts/{}/submit", post["id"]), json!({}), Some(&token)).await;
+    assert_eq!(set_slug(&app, &post, json!("in-review"), &token).await.status, StatusCode::CONFLICT);
+    assert_eq!(by_slug(&app, "another-title", &token).await.status, StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn suffixes_fill_the_first_gap(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (_, token) = app.user_with_token("jan").await;
+    create(&app, "Rust", &token).await;
+    let second = create(&app, "Rust", &token).await;
+    create(&app, "Rust", &token).await;
+    // Longer titles sharing the prefix aren't suffixes
+    assert_eq!(create(&app, "Rust 2024", &token).await["slug"], "rust-2024");
+    assert_eq!(create(&app, "Rust async", &token).await["slug"], "rust-async");
+
+    assert_eq!(retitle(&app, &second, "Moved away", &token).await.status, StatusCode::OK);
+    assert_eq!(create(&app, "Rust", &token).await["slug"], "rust-4");
+    set_slug(&app, &second, json!("rust-05"), &token).await;
+    assert_eq!(create(&app, "Rust", &token).await["slug"], "rust-5");
+}
+
+#[sqlx::test]
+async fn slugs_taken_meanwhile_are_generated_again(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("kai").await;
+
+    // Another request holds "race" without having committed it yet, so the
+    // insert waits on the unique index and then collides
+    let mut other = app.pool().begin().await.unwrap();
+    sqlx::query("INSERT INTO posts (user_id, title, body, slug) VALUES ($1, 'Race', 'Body', 'race')")
+        .bind(user_id)
+        .execute(&mut *other)
+        .await
+        .unwrap();
+    let (created, _) = tokio::join!(
+        app.post_json("/posts", json!({ "title": "Race", "body": "Body" }), Some(&token)),
+        async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            other.commit().await.unwrap();
+        },
+    );
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.text());
+    assert_eq!(created.json()["slug"], "race-2");
+    let revisions = app.get(&format!("/posts/{}/revisions", created.json()["id"]), Some(&token)).await.json();
+    assert_eq!(revisions.as_array().unwrap().len(), 1);
+}
+
+#[sqlx::test]
+async fn rows_written_without_a_slug_get_a_free_placeholder(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, token) = app.user_with_token("pia").await;
+
+    // The next post's placeholder is taken, and so was its first alternative
+    let post = create(&app, "Squatter", &token).await;
+    let next = post["id"].as_i64().unwrap() + 1;
+    set_slug(&app, &post, json!(format!("post-{}-2", next)), &token).await;
+    let moved = set_slug(&app, &post, json!(format!("post-{}", next)), &token).await;
+    assert_eq!(moved.status, StatusCode::OK, "{}", moved.text());
+
+    let raw = app.insert_post(user_id, "Raw", "Body").await;
+    assert_eq!(i64::from(raw), next);
+    let found = app.get(&format!("/posts/{}", raw), Some(&token)).await.json();
+    assert_eq!(found["slug"], format!("post-{}-3", next));
+}
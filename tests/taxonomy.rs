@@ -0,0 +1,149 @@
+mod common;
+
+use common::{TestApp, TestResponse};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+// Inserts a published post by `user_id`
+async fn published(app: &TestApp, user_id: i32, title: &str) -> i32 {
+    let id = app.insert_post(user_id, title, "Body").await;
+    sqlx::query("UPDATE posts SET status = 'published', published_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(app.pool())
+        .await
+        .unwrap();
+    id
+}
+
+async fn put(app: &TestApp, uri: &str, body: Value, token: &str) -> TestResponse {
+    app.request(Method::PUT, uri, Some(body), Some(token)).await
+}
+
+// PUT /posts/{id}/category, based on the post's current ETag
+async fn file_under(app: &TestApp, id: i32, category: &Value, token: &str) -> TestResponse {
+    app.put_if_match(id, &format!("/posts/{}/category", id), json!({ "category_id": category["id"] }), token).await
+}
+
+async fn titles(app: &TestApp, uri: &str, token: &str) -> Vec<String> {
+    let posts = app.get(uri, Some(token)).await.json();
+    posts.as_array().unwrap().iter().map(|post| post["title"].as_str().unwrap().to_string()).collect()
+}
+
+#[sqlx::test]
+async fn editors_tag_posts_and_readers_filter_by_tag(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, reader) = app.user_with_token("maria").await;
+    let editor = app.token_with_roles(user_id, &["user", "editor"]);
+    let first = published(&app, user_id, "Ownership").await;
+    let second = published(&app, user_id, "Lifetimes").await;
+    published(&app, user_id, "Untagged").await;
+
+    let uri = format!("/posts/{}/tags", first);
+    assert_eq!(put(&app, &uri, json!({ "tags": ["rust"] }), &reader).await.status, StatusCode::FORBIDDEN);
+    let tags = put(&app, &uri, json!({ "tags": ["Rust ", "memory", "rust"] }), &editor).await;
+    assert_eq!(tags.status, StatusCode::OK, "{}", tags.text());
+    let tags = tags.json();
+    let names: Vec<&str> = tags.as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["memory", "rust"]);
+    put(&app, &format!("/posts/{}/tags", second), json!({ "tags": ["rust"] }), &editor).await;
+
+    assert_eq!(titles(&app, "/posts?tag=RUST", &reader).await, ["Ownership", "Lifetimes"]);
+    assert_eq!(titles(&app, "/posts?tag=memory", &reader).await, ["Ownership"]);
+    assert!(titles(&app, "/posts?tag=java", &reader).await.is_empty());
+    assert_eq!(app.get(&uri, Some(&reader)).await.json().as_array().unwrap().len(), 2);
+
+    // The tag cloud counts published posts
+    let counts = app.get("/tags", Some(&reader)).await.json();
+    assert_eq!(counts[0]["name"], "rust");
+    assert_eq!(counts[0]["post_count"], 2);
+    assert_eq!(counts[1]["name"], "memory");
+    assert_eq!(counts[1]["post_count"], 1);
+
+    let created = app.post_json("/tags", json!({ "name": "Async" }), Some(&editor)).await;
+    assert_eq!(created.status, StatusCode::CREATED);
+    assert_eq!(created.json()["name"], "async");
+    assert_eq!(app.post_json("/tags", json!({ "name": "async" }), Some(&editor)).await.status, StatusCode::CONFLICT);
+    assert_eq!(app.post_json("/tags", json!({ "name": "a,b" }), Some(&editor)).await.status, StatusCode::BAD_REQUEST);
+    let tag = format!("/tags/{}", created.json()["id"]);
+    assert_eq!(app.request(Method::DELETE, &tag, None, Some(&reader)).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(app.request(Method::DELETE, &tag, None, Some(&editor)).await.status, StatusCode::NO_CONTENT);
+}
+
+#[sqlx::test]
+async fn admins_rename_and_merge_tags(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, _) = app.user_with_token("nils").await;
+    let editor = app.token_with_roles(user_id, &["user", "editor"]);
+    let admin = app.token_with_roles(user_id, &["admin"]);
+    let both = published(&app, user_id, "Both").await;
+    let old_only = published(&app, user_id, "Old only").await;
+    put(&app, &format!("/posts/{}/tags", both), json!({ "tags": ["postgres", "postgresql"] }), &editor).await;
+    put(&app, &format!("/posts/{}/tags", old_only), json!({ "tags": ["postgresql"] }), &editor).await;
+
+    let counts = app.get("/tags", Some(&admin)).await.json();
+    let id_of = |name: &str| counts.as_array().unwrap().iter().find(|t| t["name"] == name).unwrap()["id"].clone();
+    let (postgres, postgresql) = (id_of("postgres"), id_of("postgresql"));
+
+    // Renaming onto an existing name is refused, that's what merging is for
+    let rename = format!("/admin/tags/{}", postgresql);
+    assert_eq!(put(&app, &rename, json!({ "name": "Postgres" }), &editor).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(put(&app, &rename, json!({ "name": "Postgres" }), &admin).await.status, StatusCode::CONFLICT);
+    assert_eq!(put(&app, &rename, json!({ "name": "PostgreSQL 16" }), &admin).await.json()["name"], "postgresql 16");
+
+    let merge = format!("/admin/tags/{}/merge", postgresql);
+    let merged = app.post_json(&merge, json!({ "into": postgres }), Some(&admin)).await;
+    assert_eq!(merged.status, StatusCode::OK, "{}", merged.text());
+    assert_eq!(merged.json()["moved_posts"], 1);
+    assert_eq!(titles(&app, "/posts?tag=postgres", &admin).await, ["Both", "Old only"]);
+    let counts = app.get("/tags", Some(&admin)).await.json();
+    assert_eq!(counts, json!([{ "id": postgres, "name": "postgres", "post_count": 2 }]));
+    assert_eq!(app.post_json(&merge, json!({ "into": postgres }), Some(&admin)).await.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn categories_form_a_tree(pool: PgPool) {
+    let app = TestApp::new(pool);
+    let (user_id, reader) = app.user_with_token("olga").await;
+    let editor = app.token_with_roles(user_id, &["user", "editor"]);
+    let create = |body| app.post_json("/categories", body, Some(&editor));
+
+    assert_eq!(app.post_json("/categories", json!({ "name": "Tech" }), Some(&reader)).await.status, StatusCode::FORBIDDEN);
+    let tech = create(json!({ "name": "Tech" })).await.json();
+    assert_eq!(tech["slug"], "tech");
+    let databases = create(json!({ "name": "Databases & SQL", "parent_id": tech["id"] })).await.json();
+    assert_eq!(databases["slug"], "databases-sql");
+    let life = create(json!({ "name": "Life", "slug": "life" })).await.json();
+    assert_eq!(create(json!({ "name": "Tech" })).await.status, StatusCode::CONFLICT);
+    assert_eq!(create(json!({ "name": "Orphan", "parent_id": 999 })).await.status, StatusCode::BAD_REQUEST);
+
+    let tree = app.get("/categories", Some(&reader)).await.json();
+    let slugs: Vec<&str> = tree.as_array().unwrap().iter().map(|c| c["slug"].as_str().unwrap()).collect();
+    assert_eq!(slugs, ["life", "tech", "databases-sql"]);
+
+    let indexes = published(&app, user_id, "Indexes").await;
+    let gadgets = published(&app, user_id, "Gadgets").await;
+    published(&app, user_id, "Holidays").await;
+    let unconditional = put(&app, &format!("/posts/{}/category", indexes), json!({ "category_id": tech["id"] }), &editor).await;
+    assert_eq!(unconditional.status, StatusCode::PRECONDITION_REQUIRED);
+    let filed = file_under(&app, indexes, &databases, &editor).await;
+    assert_eq!(filed.json()["category_id"], databases["id"]);
+    file_under(&app, gadgets, &tech, &editor).await;
+
+    // A category includes its subcategories
+    assert_eq!(titles(&app, "/posts?category=tech", &reader).await, ["Indexes", "Gadgets"]);
+    assert_eq!(titles(&app, "/posts?category=databases-sql", &reader).await, ["Indexes"]);
+    assert!(titles(&app, "/posts?category=life&tag=rust", &reader).await.is_empty());
+
+    // No cycles, and only leaves can go
+    let move_tech = json!({ "name": "Tech", "parent_id": databases["id"] });
+    let uri = format!("/categories/{}", tech["id"]);
+    assert_eq!(put(&app, &uri, move_tech, &editor).await.status, StatusCode::BAD_REQUEST);
+    assert_eq!(app.request(Method::DELETE, &uri, None, Some(&editor)).await.status, StatusCode::CONFLICT);
+    let move_databases = json!({ "name": "Databases", "parent_id": life["id"] });
+    let moved = put(&app, &format!("/categories/{}", databases["id"]), move_databases, &editor).await;
+    assert_eq!(moved.json()["slug"], "databases");
+    assert_eq!(titles(&app, "/posts?category=life", &reader).await, ["Indexes"]);
+    assert_eq!(app.request(Method::DELETE, &uri, None, Some(&editor)).await.status, StatusCode::NO_CONTENT);
+    assert_eq!(app.get(&format!("/posts/{}", gadgets), Some(&reader)).await.json()["category_id"], Value::Null);
+}
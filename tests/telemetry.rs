@@ -0,0 +1,74 @@
+mod common;
+
+use axum::body::{Body, Bytes};
+use axum::routing::post;
+use axum::Router;
+use common::TestApp;
+use http::Request;
+use rustrest::config::{LogConfig, OtelConfig};
+use rustrest::telemetry;
+use sqlx::PgPool;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+// Minimal in-process OTLP/HTTP collector, keeps the raw protobuf payloads
+async fn start_collector() -> (String, Arc<Mutex<Vec<Bytes>>>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let router = Router::new().route(
+        "/v1/traces",
+        post({
+            let received = Arc::clone(&received);
+            move |body: Bytes| async move {
+                received.lock().unwrap().push(body);
+            }
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    (endpoint, received)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+fn hex_bytes(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[sqlx::test]
+async fn spans_are_exported_and_continue_incoming_traces(pool: PgPool) {
+    let (endpoint, received) = start_collector().await;
+    let telemetry = telemetry::init(&LogConfig::default(), Some(&OtelConfig {
+        endpoint,
+        service_name: "rustrest-under-test".to_string(),
+        sampling_ratio: 1.0,
+    }))
+    .unwrap();
+    let app = TestApp::new(pool);
+
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let request = Request::post("/register")
+        .header("traceparent", format!("00-{}-00f067aa0ba902b7-01", trace_id))
+        .header("content-type", "application/json")
+        .body(Body::from(
+            r#"{"username":"rita","email":"rita@example.com","password":"correct-horse-battery"}"#,
+        ))
+        .unwrap();
+    app.send(request).await;
+
+    // The exporter blocks while it posts to the collector running on this runtime
+    tokio::task::spawn_blocking(move || telemetry.shutdown()).await.unwrap();
+
+    let exported = received.lock().unwrap().concat();
+    assert!(contains(&exported, b"rustrest-under-test"), "service name");
+    assert!(contains(&exported, &hex_bytes(trace_id)), "propagated trace id");
+    assert!(contains(&exported, b"POST /register"), "request span");
+    assert!(contains(&exported, b"password.hash"), "password hash span");
+    assert!(contains(&exported, b"INSERT INTO users"), "query span");
+    assert!(contains(&exported, b"INSERT INTO audit_events"), "query span in a transaction");
+}
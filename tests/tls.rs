@@ -0,0 +1,248 @@
+mod common;
+
+use axum::body::Body;
+use common::TestApp;
+use http::{Request, StatusCode};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, RootCertStore};
+use rustrest::config::TlsConfig;
+use rustrest::tls::{self, ServerTls, TlsListener};
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use tower::ServiceExt;
+
+struct Ca {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl Ca {
+    fn new() -> Self {
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.distinguished_name.push(DnType::CommonName, "rustrest test CA");
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let key = KeyPair::generate().unwrap();
+        Self { cert: params.self_signed(&key).unwrap(), key }
+    }
+
+    fn issue(&self, params: CertificateParams) -> (Certificate, KeyPair) {
+        let key = KeyPair::generate().unwrap();
+        (params.signed_by(&key, &self.cert, &self.key).unwrap(), key)
+    }
+
+    fn server(&self) -> (Certificate, KeyPair) {
+        self.issue(CertificateParams::new(vec!["localhost".to_string()]).unwrap())
+    }
+
+    fn client(&self, common_name: &str) -> (Certificate, KeyPair) {
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.distinguished_name.push(DnType::CommonName, common_name);
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        self.issue(params)
+    }
+}
+
+struct TlsServer {
+    addr: SocketAddr,
+    tls: Arc<ServerTls>,
+    dir: PathBuf,
+    ca: Ca,
+}
+
+impl TlsServer {
+    // `client_auth`: None without mTLS, otherwise whether a client certificate is required
+    async fn start(app: TestApp, client_auth: Option<bool>) -> Self {
+        let ca = Ca::new();
+        let dir = std::env::temp_dir().join(format!("rustrest-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ca.pem"), ca.cert.pem()).unwrap();
+        write_server_certificate(&ca, &dir);
+
+        let tls = ServerTls::load(TlsConfig {
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("key.pem"),
+            redirect_bind: None,
+            client_ca_path: client_auth.map(|_| dir.join("ca.pem")),
+            client_auth_required: client_auth.unwrap_or(false),
+            reload_interval: Duration::from_secs(60),
+        })
+        .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let listener = TlsListener::new(listener, Arc::clone(&tls)).unwrap();
+        tokio::spawn(async move { axum::serve(listener, tls::make_service(app.router)).await });
+
+        Self { addr, tls, dir, ca }
+    }
+
+    async fn connect(&self, client: Option<&(Certificate, KeyPair)>) -> std::io::Result<TlsStream<TcpStream>> {
+        let mut roots = RootCertStore::empty();
+        roots.add(self.ca.cert.der().clone()).unwrap();
+        let builder = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let config = match client {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(
+                    vec![cert.der().clone()],
+                    PrivateKeyDer::try_from(key.serialize_der()).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        let stream = TcpStream::connect(self.addr).await?;
+        TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+    }
+}
+
+impl Drop for TlsServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+// A new server certificate and key for localhost, from the given CA
+fn write_server_certificate(ca: &Ca, dir: &Path) {
+    let (cert, key) = ca.server();
+    std::fs::write(dir.join("cert.pem"), cert.pem()).unwrap();
+    std::fs::write(dir.join("key.pem"), key.serialize_pem()).unwrap();
+}
+
+fn served_certificate(stream: &TlsStream<TcpStream>) -> CertificateDer<'static> {
+    stream.get_ref().1.peer_certificates().unwrap()[0].clone().into_owned()
+}
+
+// Sends a keep-alive HTTP/1.1 request and reads one response, returns its status and body
+async fn get(stream: &mut TlsStream<TcpStream>, path: &str) -> std::io::Result<(u16, String)> {
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    let mut buffer = [0u8; 4096];
+    loop {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        response.extend_from_slice(&buffer[..read]);
+        let text = String::from_utf8_lossy(&response).into_owned();
+        let Some((head, body)) = text.split_once("\r\n\r\n") else {
+            continue;
+        };
+        let length = head
+            .lines()
+            .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|value| value.trim().to_string()))
+            .and_then(|value| value.parse::<usize>().ok())
+            .unwrap_or(0);
+        if body.len() >= length {
+            let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+            return Ok((status, body[..length].to_string()));
+        }
+    }
+}
+
+#[sqlx::test]
+async fn certificate_reloads_keep_established_connections(pool: PgPool) {
+    let server = TlsServer::start(TestApp::new(pool), None).await;
+    let mut established = server.connect(None).await.unwrap();
+    assert_eq!(get(&mut established, "/healthz").await.unwrap().0, 200);
+    let original = served_certificate(&established);
+
+    assert!(!server.tls.reload_if_changed().unwrap());
+    write_server_certificate(&server.ca, &server.dir);
+    assert!(server.tls.reload_if_changed().unwrap());
+
+    // New connections get the new certificate, the open one keeps working
+    let renewed = server.connect(None).await.unwrap();
+    assert_ne!(served_certificate(&renewed), original);
+    assert_eq!(get(&mut established, "/healthz").await.unwrap().0, 200);
+}
+
+#[sqlx::test]
+async fn broken_certificate_files_keep_the_current_certificate(pool: PgPool) {
+    let server = TlsServer::start(TestApp::new(pool), None).await;
+    let original = served_certificate(&server.connect(None).await.unwrap());
+
+    std::fs::write(server.dir.join("key.pem"), "not a key").unwrap();
+    assert!(server.tls.reload().is_err());
+
+    let mut stream = server.connect(None).await.unwrap();
+    assert_eq!(served_certificate(&stream), original);
+    assert_eq!(get(&mut stream, "/healthz").await.unwrap().0, 200);
+}
+
+#[sqlx::test]
+async fn client_certificates_sign_in_as_their_user(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.register("alice").await;
+    let server = TlsServer::start(app, Some(false)).await;
+
+    let alice = server.ca.client("alice");
+    let mut stream = server.connect(Some(&alice)).await.unwrap();
+    let (status, _) = get(&mut stream, "/posts").await.unwrap();
+    assert_eq!(status, 200);
+
+    // Unknown users are rejected, connections without a certificate fall back to tokens
+    let mallory = server.ca.client("mallory");
+    let mut stream = server.connect(Some(&mallory)).await.unwrap();
+    let (status, body) = get(&mut stream, "/posts").await.unwrap();
+    assert_eq!(status, 401);
+    assert!(body.contains("Unknown client certificate"), "{}", body);
+    let mut stream = server.connect(None).await.unwrap();
+    assert_eq!(get(&mut stream, "/posts").await.unwrap().0, 401);
+
+    // Certificates from another CA don't get through the handshake
+    let stranger = Ca::new().client("alice");
+    let rejected = match server.connect(Some(&stranger)).await {
+        Ok(mut stream) => get(&mut stream, "/posts").await.is_err(),
+        Err(_) => true,
+    };
+    assert!(rejected);
+}
+
+#[sqlx::test]
+async fn required_client_certificates_refuse_other_connections(pool: PgPool) {
+    let app = TestApp::new(pool);
+    app.register("alice").await;
+    let server = TlsServer::start(app, Some(true)).await;
+
+    // With TLS 1.3 the client learns about the rejection on its first read
+    let rejected = match server.connect(None).await {
+        Ok(mut stream) => get(&mut stream, "/healthz").await.is_err(),
+        Err(_) => true,
+    };
+    assert!(rejected);
+
+    let mut stream = server.connect(Some(&server.ca.client("alice"))).await.unwrap();
+    assert_eq!(get(&mut stream, "/healthz").await.unwrap().0, 200);
+}
+
+#[tokio::test]
+async fn plain_http_redirects_to_https() {
+    for (https_port, host, uri, location) in [
+        (8443, "blog.example.com:8080", "/posts?page=2", "https://blog.example.com:8443/posts?page=2"),
+        (443, "blog.example.com", "/login", "https://blog.example.com/login"),
+        (443, "[::1]:80", "/", "https://[::1]/"),
+    ] {
+        let request = Request::builder().uri(uri).header("host", host).body(Body::empty()).unwrap();
+        let response = tls::redirect_app(https_port).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()["location"], location);
+    }
+
+    let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+    let response = tls::redirect_app(443).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}